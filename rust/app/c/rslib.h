@@ -9,6 +9,10 @@ void zemu_log(const char *buf);
 void
 rs_handle_apdu(volatile uint32_t *flags, volatile uint32_t *tx, uint32_t rx, const uint8_t *buffer, uint16_t bufferLen);
 
+void rs_settings_init();
+
+void rs_session_tick();
+
 /////////////
 
 void view_init();
@@ -28,8 +28,13 @@
 
 #if defined(TARGET_NANOS)
 
-void rs_h_expert_toggle();
 void rs_h_expert_update();
+
+void rs_settings_toggle_expert();
+void rs_settings_toggle_blind_signing();
+bool rs_settings_blind_signing();
+void rs_settings_toggle_unit();
+bool rs_settings_unit_mutez();
 void rs_h_review_button_left();
 void rs_h_review_button_right();
 void rs_h_review_button_both();
@@ -44,10 +49,40 @@ void os_exit(uint32_t id) {
     os_sched_exit(0);
 }
 
+static char blind_signing_label[9];
+
+static void update_blind_signing_label() {
+    const char *label = rs_settings_blind_signing() ? "enabled" : "disabled";
+    snprintf(blind_signing_label, sizeof(blind_signing_label), "%s", label);
+}
+
+static char unit_label[6];
+
+static void update_unit_label() {
+    const char *label = rs_settings_unit_mutez() ? "mutez" : "tez";
+    snprintf(unit_label, sizeof(unit_label), "%s", label);
+}
+
+void crapoline_ux_menu_display(uint8_t item_idx);
+
+static void h_blind_signing_toggle(unsigned int _) {
+    UNUSED(_);
+    rs_settings_toggle_blind_signing();
+    crapoline_ux_menu_display(2);
+}
+
+static void h_unit_toggle(unsigned int _) {
+    UNUSED(_);
+    rs_settings_toggle_unit();
+    crapoline_ux_menu_display(3);
+}
+
 //Referenced in crapoline_ux_menu_display
 const ux_menu_entry_t menu_main[] = {
     {NULL, NULL, 0, &C_icon_app, MENU_MAIN_APP_LINE1, (const char *) BACKEND_LAZY.key, 33, 12},
-    {NULL, rs_h_expert_toggle, 0, &C_icon_app, "Expert mode:", (const char *) BACKEND_LAZY.value, 33, 12},
+    {NULL, rs_settings_toggle_expert, 0, &C_icon_app, "Expert mode:", (const char *) BACKEND_LAZY.value, 33, 12},
+    {NULL, h_blind_signing_toggle, 0, &C_icon_app, "Blind signing:", blind_signing_label, 33, 12},
+    {NULL, h_unit_toggle, 0, &C_icon_app, "Amount unit:", unit_label, 33, 12},
     {NULL, NULL, 0, &C_icon_app, APPVERSION_LINE1, APPVERSION_LINE2, 33, 12},
 
     {NULL,
@@ -154,6 +189,7 @@ static const bagl_element_t view_message[] = {
     UI_BACKGROUND,
     UI_LabelLine(UIID_LABEL + 0, 0, 8, UI_SCREEN_WIDTH, UI_11PX, UI_WHITE, UI_BLACK, (const char *) BACKEND_LAZY.key),
     UI_LabelLine(UIID_LABEL + 1, 0, 19, UI_SCREEN_WIDTH, UI_11PX, UI_WHITE, UI_BLACK, (const char *) BACKEND_LAZY.value),
+    UI_LabelLine(UIID_LABEL + 2, 0, 30, UI_SCREEN_WIDTH, UI_11PX, UI_WHITE, UI_BLACK, (const char *) BACKEND_LAZY.value2),
 };
 
 static unsigned int view_message_button(unsigned int button_mask, unsigned int button_mask_counter) {
@@ -161,7 +197,10 @@ static unsigned int view_message_button(unsigned int button_mask, unsigned int b
     switch (button_mask) {
         case BUTTON_EVT_RELEASED | BUTTON_LEFT | BUTTON_RIGHT:
         case BUTTON_EVT_RELEASED | BUTTON_LEFT:
+            break;
         case BUTTON_EVT_RELEASED | BUTTON_RIGHT:
+            // Dismiss the message, going back to the menu
+            rs_h_error_accept(0);
             break;
     }
     return 0;
@@ -174,6 +213,9 @@ void crapoline_ux_wait() {
 }
 
 void crapoline_ux_menu_display(uint8_t item_idx) {
+    update_blind_signing_label();
+    update_unit_label();
+
     //menu_main is ux_menu_t above
     UX_MENU_DISPLAY(item_idx, menu_main, NULL);
 }
@@ -29,9 +29,14 @@
 
 #if defined(TARGET_NANOX) || defined(TARGET_NANOS2)
 
-void rs_h_expert_toggle();
 void rs_h_expert_update();
 
+void rs_settings_toggle_expert();
+void rs_settings_toggle_blind_signing();
+bool rs_settings_blind_signing();
+void rs_settings_toggle_unit();
+bool rs_settings_unit_mutez();
+
 void rs_h_review_loop_start();
 void rs_h_review_loop_inside();
 void rs_h_review_loop_end();
@@ -43,12 +48,31 @@ void rs_h_reject(unsigned int);
 ux_state_t G_ux;
 bolos_ux_params_t G_ux_params;
 
+static char blind_signing_label[9];
+
+static void update_blind_signing_label() {
+    const char *label = rs_settings_blind_signing() ? "enabled" : "disabled";
+    snprintf(blind_signing_label, sizeof(blind_signing_label), "%s", label);
+}
+
+static char unit_label[6];
+
+static void update_unit_label() {
+    const char *label = rs_settings_unit_mutez() ? "mutez" : "tez";
+    snprintf(unit_label, sizeof(unit_label), "%s", label);
+}
+
+static void h_blind_signing_toggle();
+static void h_unit_toggle();
+
 UX_STEP_NOCB(ux_idle_flow_1_step, pbb, { &C_icon_app, MENU_MAIN_APP_LINE1, (const char *) BACKEND_LAZY.key,});
-UX_STEP_CB_INIT(ux_idle_flow_2_step, bn,  rs_h_expert_update(), rs_h_expert_toggle(), { "Expert mode:", (const char *) BACKEND_LAZY.message, });
-UX_STEP_NOCB(ux_idle_flow_3_step, bn, { APPVERSION_LINE1, APPVERSION_LINE2, });
-UX_STEP_NOCB(ux_idle_flow_4_step, bn, { "Developed by:", "Zondax.ch", });
-UX_STEP_NOCB(ux_idle_flow_5_step, bn, { "License:", "Apache 2.0", });
-UX_STEP_CB(ux_idle_flow_6_step, pb, os_sched_exit(-1), { &C_icon_dashboard, "Quit",});
+UX_STEP_CB_INIT(ux_idle_flow_2_step, bn,  rs_h_expert_update(), rs_settings_toggle_expert(), { "Expert mode:", (const char *) BACKEND_LAZY.message, });
+UX_STEP_CB_INIT(ux_idle_flow_3_step, bn,  update_blind_signing_label(), h_blind_signing_toggle(), { "Blind signing:", blind_signing_label, });
+UX_STEP_CB_INIT(ux_idle_flow_4_step, bn,  update_unit_label(), h_unit_toggle(), { "Amount unit:", unit_label, });
+UX_STEP_NOCB(ux_idle_flow_5_step, bn, { APPVERSION_LINE1, APPVERSION_LINE2, });
+UX_STEP_NOCB(ux_idle_flow_6_step, bn, { "Developed by:", "Zondax.ch", });
+UX_STEP_NOCB(ux_idle_flow_7_step, bn, { "License:", "Apache 2.0", });
+UX_STEP_CB(ux_idle_flow_8_step, pb, os_sched_exit(-1), { &C_icon_dashboard, "Quit",});
 
 const ux_flow_step_t *const ux_idle_flow [] = {
   &ux_idle_flow_1_step,
@@ -57,9 +81,21 @@ const ux_flow_step_t *const ux_idle_flow [] = {
   &ux_idle_flow_4_step,
   &ux_idle_flow_5_step,
   &ux_idle_flow_6_step,
+  &ux_idle_flow_7_step,
+  &ux_idle_flow_8_step,
   FLOW_END_STEP,
 };
 
+static void h_blind_signing_toggle() {
+    rs_settings_toggle_blind_signing();
+    ux_flow_init(0, ux_idle_flow, &ux_idle_flow_3_step);
+}
+
+static void h_unit_toggle() {
+    rs_settings_toggle_unit();
+    ux_flow_init(0, ux_idle_flow, &ux_idle_flow_4_step);
+}
+
 ///////////
 
 UX_STEP_NOCB(ux_error_flow_1_step, bnnn_paging, { .title = (const char *) BACKEND_LAZY.key, .text = (const char *) BACKEND_LAZY.message, });
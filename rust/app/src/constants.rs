@@ -25,6 +25,7 @@ pub enum ApduError {
     DataInvalid = 0x6984,
     ApduCodeConditionsNotSatisfied = 0x6985,
     CommandNotAllowed = 0x6986,
+    UnknownChain = 0x6987,
     BadKeyExample = 0x6A80,
     InvalidP1P2 = 0x6B00,
     InsNotSupported = 0x6D00,
@@ -33,6 +34,46 @@ pub enum ApduError {
     SignVerifyError = 0x6F01,
     Success = 0x9000,
     Busy = 0x9001,
+    //parsing failures (see `ParserError`), app specific
+    // so they don't clash with the ISO 7816 status words
+    ParserUnexpectedEof = 0xB001,
+    ParserValueOutOfRange = 0xB002,
+    ParserInvalidAddress = 0xB003,
+    ParserInvalidPublicKey = 0xB004,
+    ParserInvalidEntrypoint = 0xB005,
+    ParserInvalidPreemble = 0xB006,
+    ParserUnknownOperation = 0xB007,
+    ParserInvalidBallotVote = 0xB008,
+    ParserInvalidProposalsLength = 0xB009,
+    ParserInvalidEndorsementType = 0xB00A,
+    ParserInvalidProtocolVersion = 0xB00B,
+    ParserInvalidPayload = 0xB00C,
+    ParserUnexpected = 0xB00D,
+}
+
+impl ApduError {
+    /// Status word to send back on the legacy instructions
+    ///
+    /// Legacy clients only know the ISO 7816 status words,
+    /// so parsing failures are sent back as `DataInvalid`
+    pub fn legacy(self) -> Self {
+        match self {
+            Self::ParserUnexpectedEof
+            | Self::ParserValueOutOfRange
+            | Self::ParserInvalidAddress
+            | Self::ParserInvalidPublicKey
+            | Self::ParserInvalidEntrypoint
+            | Self::ParserInvalidPreemble
+            | Self::ParserUnknownOperation
+            | Self::ParserInvalidBallotVote
+            | Self::ParserInvalidProposalsLength
+            | Self::ParserInvalidEndorsementType
+            | Self::ParserInvalidProtocolVersion
+            | Self::ParserInvalidPayload
+            | Self::ParserUnexpected => Self::DataInvalid,
+            other => other,
+        }
+    }
 }
 
 #[cfg_attr(test, derive(Debug))]
@@ -74,6 +115,7 @@ impl std::convert::TryFrom<u16> for ApduError {
             0x6984 => Ok(Self::DataInvalid),
             0x6985 => Ok(Self::ApduCodeConditionsNotSatisfied),
             0x6986 => Ok(Self::CommandNotAllowed),
+            0x6987 => Ok(Self::UnknownChain),
             0x6A80 => Ok(Self::BadKeyExample),
             0x6B00 => Ok(Self::InvalidP1P2),
             0x6D00 => Ok(Self::InsNotSupported),
@@ -82,6 +124,19 @@ impl std::convert::TryFrom<u16> for ApduError {
             0x6F01 => Ok(Self::SignVerifyError),
             0x9000 => Ok(Self::Success),
             0x9001 => Ok(Self::Busy),
+            0xB001 => Ok(Self::ParserUnexpectedEof),
+            0xB002 => Ok(Self::ParserValueOutOfRange),
+            0xB003 => Ok(Self::ParserInvalidAddress),
+            0xB004 => Ok(Self::ParserInvalidPublicKey),
+            0xB005 => Ok(Self::ParserInvalidEntrypoint),
+            0xB006 => Ok(Self::ParserInvalidPreemble),
+            0xB007 => Ok(Self::ParserUnknownOperation),
+            0xB008 => Ok(Self::ParserInvalidBallotVote),
+            0xB009 => Ok(Self::ParserInvalidProposalsLength),
+            0xB00A => Ok(Self::ParserInvalidEndorsementType),
+            0xB00B => Ok(Self::ParserInvalidProtocolVersion),
+            0xB00C => Ok(Self::ParserInvalidPayload),
+            0xB00D => Ok(Self::ParserUnexpected),
             err => Err(Self::Error::Unknown(err)),
         }
     }
@@ -21,7 +21,12 @@ use cfg_if::cfg_if;
 use crate::constants::ApduError;
 use crate::constants::ApduError::{ClaNotSupported, CommandNotAllowed};
 
+use crate::handlers::address_book::{AddContact, GetContact, RemoveContact};
+use crate::handlers::policy::{GetPolicy, SetPolicy};
 use crate::handlers::public_key::GetAddress;
+use crate::handlers::rejection::GetLastError;
+use crate::handlers::session::{EndSession, StartSession};
+use crate::handlers::settings::{GetSettings, SetSettings};
 use crate::handlers::signing::Sign;
 use crate::handlers::version::GetVersion;
 
@@ -47,10 +52,16 @@ cfg_if! {
         pub const INS_LEGACY_HMAC: u8 = 0xE;
 
         pub const INS_AUTHORIZE_BAKING: u8 = 0xA1;
+        pub const INS_SETUP_BAKING: u8 = 0xA2;
+        pub const INS_QUERY_HWM: u8 = 0xA3;
+        pub const INS_RESET_HWM: u8 = 0xA4;
         pub const INS_DEAUTHORIZE_BAKING: u8 = 0xAC;
         pub const INS_QUERY_AUTH_KEY: u8 = 0xA7;
         pub const INS_QUERY_AUTH_KEY_WITH_CURVE: u8 = 0xAD;
         pub const INS_BAKER_SIGN: u8 = 0xAF;
+        pub const INS_QUERY_SIGN_LOG: u8 = 0xA8;
+        pub const INS_SET_VOTE_POLICY: u8 = 0xA9;
+        pub const INS_GET_VOTE_POLICY: u8 = 0xAA;
 
         //baking-only legacy imports
         use crate::handlers::legacy::hwm::{LegacyResetHWM, LegacyQueryMainHWM,
@@ -62,7 +73,9 @@ cfg_if! {
 
         //baking-only new instructions
         use crate::handlers::baking::{AuthorizeBaking, DeAuthorizeBaking, QueryAuthKey,
-                                      QueryAuthKeyWithCurve, Baking};
+                                      QueryAuthKeyWithCurve, Baking, SetVotePolicy,
+                                      GetVotePolicy, SetupBaking, QueryHWM, ResetHWM};
+        use crate::handlers::sign_log::QuerySignLog;
     } else if #[cfg(feature = "wallet")] {
         //wallet-only legacy instructions
         pub const INS_LEGACY_SIGN_UNSAFE: u8 = 0x5;
@@ -86,6 +99,16 @@ pub const INS_LEGACY_SIGN_WITH_HASH: u8 = 0xF;
 pub const INS_GET_VERSION: u8 = 0x10;
 pub const INS_GET_ADDRESS: u8 = 0x11;
 pub const INS_SIGN: u8 = 0x12;
+pub const INS_GET_SETTINGS: u8 = 0x13;
+pub const INS_SET_SETTINGS: u8 = 0x14;
+pub const INS_GET_LAST_ERROR: u8 = 0x15;
+pub const INS_SET_POLICY: u8 = 0x16;
+pub const INS_GET_POLICY: u8 = 0x17;
+pub const INS_START_SESSION: u8 = 0x18;
+pub const INS_END_SESSION: u8 = 0x19;
+pub const INS_ADD_CONTACT: u8 = 0x1A;
+pub const INS_REMOVE_CONTACT: u8 = 0x1B;
+pub const INS_GET_CONTACT: u8 = 0x1C;
 
 //dev-only
 cfg_if! {
@@ -148,10 +171,16 @@ pub fn apdu_dispatch<'apdu>(
                 INS_LEGACY_QUERY_ALL_HWM => return LegacyQueryAllHWM::handle(flags, tx, apdu_buffer),
 
                 INS_AUTHORIZE_BAKING => return AuthorizeBaking::handle(flags, tx, apdu_buffer),
+                INS_SETUP_BAKING => return SetupBaking::handle(flags, tx, apdu_buffer),
+                INS_QUERY_HWM => return QueryHWM::handle(flags, tx, apdu_buffer),
+                INS_RESET_HWM => return ResetHWM::handle(flags, tx, apdu_buffer),
                 INS_DEAUTHORIZE_BAKING => return DeAuthorizeBaking::handle(flags, tx, apdu_buffer),
                 INS_QUERY_AUTH_KEY => return QueryAuthKey::handle(flags, tx, apdu_buffer),
                 INS_QUERY_AUTH_KEY_WITH_CURVE => return QueryAuthKeyWithCurve::handle(flags, tx, apdu_buffer),
                 INS_BAKER_SIGN => return Baking::handle(flags, tx, apdu_buffer),
+                INS_QUERY_SIGN_LOG => return QuerySignLog::handle(flags, tx, apdu_buffer),
+                INS_SET_VOTE_POLICY => return SetVotePolicy::handle(flags, tx, apdu_buffer),
+                INS_GET_VOTE_POLICY => return GetVotePolicy::handle(flags, tx, apdu_buffer),
 
                 INS_LEGACY_AUTHORIZE_BAKING => return LegacyAuthorize::handle(flags, tx, apdu_buffer),
                 INS_LEGACY_DEAUTHORIZE => return LegacyDeAuthorize::handle(flags, tx, apdu_buffer),
@@ -188,6 +217,17 @@ pub fn apdu_dispatch<'apdu>(
 
         INS_GET_VERSION => GetVersion::handle(flags, tx, apdu_buffer),
 
+        INS_GET_SETTINGS => GetSettings::handle(flags, tx, apdu_buffer),
+        INS_SET_SETTINGS => SetSettings::handle(flags, tx, apdu_buffer),
+        INS_GET_LAST_ERROR => GetLastError::handle(flags, tx, apdu_buffer),
+        INS_SET_POLICY => SetPolicy::handle(flags, tx, apdu_buffer),
+        INS_GET_POLICY => GetPolicy::handle(flags, tx, apdu_buffer),
+        INS_START_SESSION => StartSession::handle(flags, tx, apdu_buffer),
+        INS_END_SESSION => EndSession::handle(flags, tx, apdu_buffer),
+        INS_ADD_CONTACT => AddContact::handle(flags, tx, apdu_buffer),
+        INS_REMOVE_CONTACT => RemoveContact::handle(flags, tx, apdu_buffer),
+        INS_GET_CONTACT => GetContact::handle(flags, tx, apdu_buffer),
+
         #[cfg(feature = "dev")]
         _ => Debug::handle(flags, tx, apdu_buffer),
         #[allow(unreachable_patterns)] //not unrechable for all feature configurations
@@ -0,0 +1,489 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use bolos::{pic_str, PIC};
+use zemu_sys::{Show, ViewError, Viewable};
+
+use crate::{
+    constants::ApduError as Error,
+    dispatcher::ApduHandler,
+    handlers::{handle_ui_message, handle_ui_named},
+    parser::{baker_lookup, operations::ContractID},
+    sys::{self, flash_slot::Wear, new_flash_slot},
+    utils::{ApduBufferRead, ApduPanic},
+};
+
+/// Maximum number of addresses in the address book
+pub const MAX_ENTRIES: usize = 8;
+
+//each entry is in its own slot, written only when the user confirms it
+#[bolos::lazy_static]
+static mut BOOK: [Wear<'static, 1>; MAX_ENTRIES] = [
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+    new_flash_slot!(1).apdu_expect("NVM might be corrupted"),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+/// Label given by the user to an address, persisted in NVM
+///
+/// # Codec
+///
+/// [0] = label length, at most `MAX_LABEL_LEN`; 0 is an empty entry
+///
+/// [1..1 + len] = label, printable ASCII
+///
+/// [1 + len..] = address, encoded like a contract id (22 bytes)
+pub struct Entry {
+    label: [u8; Self::MAX_LABEL_LEN],
+    label_len: u8,
+    address: [u8; Self::ADDRESS_LEN],
+}
+
+impl Entry {
+    pub const MAX_LABEL_LEN: usize = 24;
+    const ADDRESS_LEN: usize = 22;
+    pub const MAX_SERIALIZED_LEN: usize = 1 + Self::MAX_LABEL_LEN + Self::ADDRESS_LEN;
+
+    /// Attempt to read the entry from some bytes
+    pub fn try_from_bytes(from: &[u8]) -> Result<Self, Error> {
+        let len = *from.first().ok_or(Error::WrongLength)? as usize;
+        if len > Self::MAX_LABEL_LEN || from.len() != 1 + len + Self::ADDRESS_LEN {
+            return Err(Error::WrongLength);
+        }
+
+        let (label, address) = from[1..].split_at(len);
+        //the label is shown as is, so it must be readable
+        if len == 0 || !label.iter().all(|&c| (0x20..0x7F).contains(&c)) {
+            return Err(Error::DataInvalid);
+        }
+        match ContractID::from_bytes(address) {
+            Ok(([], _)) => {}
+            _ => return Err(Error::DataInvalid),
+        }
+
+        let mut entry = Self {
+            label: [0; Self::MAX_LABEL_LEN],
+            label_len: len as u8,
+            address: [0; Self::ADDRESS_LEN],
+        };
+        entry.label[..len].copy_from_slice(label);
+        entry.address.copy_from_slice(address);
+
+        Ok(entry)
+    }
+
+    pub fn to_bytes(self) -> (usize, [u8; Self::MAX_SERIALIZED_LEN]) {
+        let len = self.label_len as usize;
+
+        let mut out = [0; Self::MAX_SERIALIZED_LEN];
+        out[0] = self.label_len;
+        out[1..1 + len].copy_from_slice(self.label());
+        out[1 + len..1 + len + Self::ADDRESS_LEN].copy_from_slice(&self.address);
+
+        (1 + len + Self::ADDRESS_LEN, out)
+    }
+
+    pub fn label(&self) -> &[u8] {
+        &self.label[..self.label_len as usize]
+    }
+
+    pub fn address(&self) -> ContractID<'_> {
+        //checked when the entry was created
+        ContractID::from_bytes(&self.address)
+            .map(|(_, address)| address)
+            .apdu_unwrap()
+    }
+
+    /// Reads the entry at `index` from NVM, if any
+    pub fn load(index: usize) -> Option<Self> {
+        let data = unsafe { BOOK.get(index)?.read() }.ok()?;
+        let len = 1 + data[0] as usize + Self::ADDRESS_LEN;
+
+        Self::try_from_bytes(data.get(..len)?).ok()
+    }
+
+    /// Writes the entry to NVM at `index`
+    fn store(&self, index: usize) -> Result<(), Error> {
+        let (len, bytes) = self.to_bytes();
+
+        let mut data = [0; 52];
+        data[..len].copy_from_slice(&bytes[..len]);
+        unsafe { BOOK[index].write(data) }.map_err(|_| Error::ExecutionError)
+    }
+
+    /// Removes the entry at `index` from NVM
+    fn remove(index: usize) -> Result<(), Error> {
+        unsafe { BOOK[index].write([0; 52]) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+/// Checks if `a` and `b` are the same address
+///
+/// tz1 addresses can come from either ed25519 curve
+fn same_address(a: &ContractID<'_>, b: &ContractID<'_>) -> bool {
+    match (a, b) {
+        (ContractID::Implicit(crv_a, a), ContractID::Implicit(crv_b, b)) => {
+            crv_a.to_hash_prefix() == crv_b.to_hash_prefix() && a == b
+        }
+        (ContractID::Originated(a), ContractID::Originated(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Finds the index of the entry with the given `address`
+fn position(address: &ContractID<'_>) -> Option<usize> {
+    (0..MAX_ENTRIES).find(|&i| match Entry::load(i) {
+        Some(entry) => same_address(&entry.address(), address),
+        None => false,
+    })
+}
+
+/// Looks up `address` in the address book
+pub fn lookup(address: &ContractID<'_>) -> Option<Entry> {
+    position(address).and_then(Entry::load)
+}
+
+/// Renders `address` along with the name it's known by, as "name (address)"
+///
+/// The label in the address book is preferred over the name of a known baker,
+/// if neither is found only the address is shown
+#[inline(never)]
+pub fn render_address(
+    address: ContractID<'_>,
+    message: &mut [u8],
+    page: u8,
+) -> Result<u8, ViewError> {
+    sys::zemu_log_stack("address_book::render_address\x00");
+
+    let (len, base58) = address.base58().map_err(|_| ViewError::Unknown)?;
+    let base58 = &base58[..len];
+
+    if let Some(entry) = lookup(&address) {
+        return handle_ui_named(entry.label(), base58, message, page);
+    }
+
+    let baker = match address {
+        ContractID::Implicit(crv, hash) => {
+            baker_lookup(arrayref::array_ref!(crv.to_hash_prefix(), 0, 3), hash).ok()
+        }
+        ContractID::Originated(_) => None,
+    };
+
+    match baker {
+        Some(name) => handle_ui_named(name.as_bytes(), base58, message, page),
+        None => handle_ui_message(base58, message, page),
+    }
+}
+
+pub struct AddContact;
+
+impl ApduHandler for AddContact {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("AddContact::handle\x00");
+
+        *tx = 0;
+
+        let payload = buffer.payload().map_err(|_| Error::DataInvalid)?;
+        let entry = Entry::try_from_bytes(payload)?;
+
+        //a known address is renamed, otherwise the first free entry is used
+        let index = position(&entry.address())
+            .or_else(|| (0..MAX_ENTRIES).find(|&i| Entry::load(i).is_none()))
+            .ok_or(Error::ApduCodeConditionsNotSatisfied)?;
+
+        let ui = ContactUI {
+            entry,
+            index,
+            remove: false,
+        };
+
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+pub struct RemoveContact;
+
+impl ApduHandler for RemoveContact {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("RemoveContact::handle\x00");
+
+        *tx = 0;
+
+        let index = buffer.p1() as usize;
+        if index >= MAX_ENTRIES {
+            return Err(Error::InvalidP1P2);
+        }
+
+        let ui = ContactUI {
+            entry: Entry::load(index).ok_or(Error::ApduCodeEmptyBuffer)?,
+            index,
+            remove: true,
+        };
+
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+pub struct GetContact;
+
+impl ApduHandler for GetContact {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("GetContact::handle\x00");
+
+        *tx = 0;
+
+        let index = buffer.p1() as usize;
+        if index >= MAX_ENTRIES {
+            return Err(Error::InvalidP1P2);
+        }
+
+        let entry = Entry::load(index).ok_or(Error::ApduCodeEmptyBuffer)?;
+        let (len, bytes) = entry.to_bytes();
+
+        let buffer = buffer.write();
+        buffer[..len].copy_from_slice(&bytes[..len]);
+        *tx = len as u32;
+
+        Ok(())
+    }
+}
+
+struct ContactUI {
+    entry: Entry,
+    index: usize,
+    remove: bool,
+}
+
+impl Viewable for ContactUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        Ok(3)
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        match item_n {
+            0 => {
+                let title_content = pic_str!(b"Address book");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let message_content = if self.remove {
+                    &pic_str!(b"Remove contact")[..]
+                } else {
+                    &pic_str!(b"Add contact")[..]
+                };
+                handle_ui_message(message_content, message, page)
+            }
+            1 => {
+                let title_content = pic_str!(b"Label");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(self.entry.label(), message, page)
+            }
+            2 => {
+                let title_content = pic_str!(b"Address");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (len, mex) = self
+                    .entry
+                    .address()
+                    .base58()
+                    .map_err(|_| ViewError::Unknown)?;
+                handle_ui_message(&mex[..len], message, page)
+            }
+            _ => Err(ViewError::NoData),
+        }
+    }
+
+    fn accept(&mut self, _: &mut [u8]) -> (usize, u16) {
+        let stored = if self.remove {
+            Entry::remove(self.index)
+        } else {
+            self.entry.store(self.index)
+        };
+
+        match stored {
+            Ok(_) => (0, Error::Success as _),
+            Err(e) => (0, e as _),
+        }
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        crypto::Curve,
+        dispatcher::{handle_apdu, CLA, INS_ADD_CONTACT, INS_GET_CONTACT, INS_REMOVE_CONTACT},
+        sys::get_out,
+    };
+    use std::{convert::TryInto, vec::Vec};
+
+    use serial_test::serial;
+
+    fn entry(label: &[u8], address: &[u8; 22]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + label.len() + address.len());
+        out.push(label.len() as u8);
+        out.extend_from_slice(label);
+        out.extend_from_slice(address);
+
+        out
+    }
+
+    fn tz1(hash: u8) -> [u8; 22] {
+        let mut out = [hash; 22];
+        out[0] = 0x00;
+        out[1] = 0x00;
+        out
+    }
+
+    #[test]
+    fn roundtrip() {
+        let bytes = entry(b"Cold storage", &tz1(0x42));
+        let parsed = Entry::try_from_bytes(&bytes).unwrap();
+
+        assert_eq!(parsed.label(), b"Cold storage");
+        assert_eq!(
+            parsed.address(),
+            ContractID::Implicit(Curve::Bip32Ed25519, &[0x42; 20])
+        );
+        let (len, out) = parsed.to_bytes();
+        assert_eq!(&out[..len], &bytes[..]);
+
+        //empty label
+        assert_eq!(
+            Entry::try_from_bytes(&entry(b"", &tz1(0x42))),
+            Err(Error::DataInvalid)
+        );
+        //not printable
+        assert_eq!(
+            Entry::try_from_bytes(&entry(b"Cold\nstorage", &tz1(0x42))),
+            Err(Error::DataInvalid)
+        );
+        //label too long
+        assert_eq!(
+            Entry::try_from_bytes(&entry(&[b'a'; 25], &tz1(0x42))),
+            Err(Error::WrongLength)
+        );
+        //unknown address kind
+        let mut wrong_address = tz1(0x42);
+        wrong_address[0] = 0x02;
+        assert_eq!(
+            Entry::try_from_bytes(&entry(b"Cold storage", &wrong_address)),
+            Err(Error::DataInvalid)
+        );
+    }
+
+    #[test]
+    #[serial(address_book)]
+    fn lookup_render() {
+        let bytes = entry(b"Cold storage", &tz1(0x42));
+        Entry::try_from_bytes(&bytes).unwrap().store(0).unwrap();
+
+        //the same address, parsed from an operation
+        let known = ContractID::Implicit(Curve::Ed25519, &[0x42; 20]);
+        assert_eq!(
+            lookup(&known).map(|e| e.label().to_vec()),
+            Some(b"Cold storage".to_vec())
+        );
+
+        let mut message = [0; 64];
+        render_address(known, &mut message, 0).unwrap();
+        let (len, base58) = known.base58().unwrap();
+        let expected = [&b"Cold storage ("[..], &base58[..len], &b")"[..]].concat();
+        assert_eq!(&message[..expected.len()], &expected[..]);
+
+        //different kind of address with the same hash
+        let unknown = ContractID::Originated(&[0x42; 20]);
+        assert!(lookup(&unknown).is_none());
+        render_address(unknown, &mut message, 0).unwrap();
+        let (len, base58) = unknown.base58().unwrap();
+        assert_eq!(&message[..len], &base58[..len]);
+
+        Entry::remove(0).unwrap();
+        assert!(lookup(&known).is_none());
+    }
+
+    #[test]
+    #[serial(address_book)]
+    fn apdu_add_get_remove() {
+        let bytes = entry(b"Exchange", &tz1(0x13));
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        buffer[..5].copy_from_slice(&[CLA, INS_ADD_CONTACT, 0, 0, bytes.len() as u8]);
+        buffer[5..5 + bytes.len()].copy_from_slice(&bytes);
+        handle_apdu(&mut flags, &mut tx, 5 + bytes.len() as u32, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        //the mock UI accepts right away
+        let (len, out) = get_out().expect("contact confirmed");
+        assert_error_code!(len, out, Error::Success);
+
+        let index = position(&ContractID::Implicit(Curve::Bip32Ed25519, &[0x13; 20]))
+            .expect("contact added") as u8;
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_CONTACT, index, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(&buffer[..tx as usize - 2], &bytes[..]);
+
+        buffer[..5].copy_from_slice(&[CLA, INS_REMOVE_CONTACT, index, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_CONTACT, index, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::ApduCodeEmptyBuffer);
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_CONTACT, MAX_ENTRIES as u8, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::InvalidP1P2);
+    }
+}
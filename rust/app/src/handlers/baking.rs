@@ -27,10 +27,19 @@ use crate::{
     constants::{ApduError as Error, BIP32_MAX_LENGTH},
     crypto::Curve,
     dispatcher::ApduHandler,
-    handlers::{hwm::HWM, signing::Sign},
+    handlers::{
+        hwm::{WaterMark, HWM},
+        parser_common::ParserError,
+        rejection::Rejection,
+        sign_log::{self, Kind, Record},
+        signing::Sign,
+    },
     parser::{
         baking::{BlockData, EndorsementData, EndorsementType, Fitness, TenderbakeEndorsement},
-        operations::{Delegation, Reveal},
+        operations::{
+            Ballot, ContractID, Delegation, DrainDelegate, Entrypoint, Proposals, Reveal,
+            SetDepositsLimit, Transfer, UpdateConsensusKey,
+        },
         DisplayableItem, Preemble,
     },
     sys::{flash_slot::Wear, new_flash_slot},
@@ -188,6 +197,14 @@ impl Baking {
             .map(|sz| (sz, sig))
     }
 
+    /// Reads the watermark of `chain_id`, recording the rejection of unknown chains
+    fn read_watermark(chain_id: u32) -> Result<WaterMark, Error> {
+        match HWM::read_for(chain_id) {
+            Err(Error::UnknownChain) => Err(Rejection::UnknownChain.record(Error::UnknownChain)),
+            other => other,
+        }
+    }
+
     #[inline(never)]
     fn handle_endorsement(
         input: &'static [u8],
@@ -196,10 +213,12 @@ impl Baking {
         digest: [u8; 32],
         out: &mut [u8],
     ) -> Result<usize, Error> {
-        let hw = HWM::read().map_err(|_| Error::ExecutionError)?;
+        //no screens are shown while baking, so parsing failures are only recorded
+        let (_, endorsement) = EndorsementData::from_bytes(input)
+            .map_err(|e| Rejection::from(e).record(Error::DataInvalid))?;
 
-        let (_, endorsement) =
-            EndorsementData::from_bytes(input).map_err(|_| Error::DataInvalid)?;
+        let chain_id = endorsement.chain_id();
+        let hw = Self::read_watermark(chain_id)?;
 
         //parsed endorsement should match preemble
         match (preemble, &endorsement) {
@@ -225,9 +244,10 @@ impl Baking {
             _ => return Err(Error::DataInvalid),
         }
 
-        HWM::write(endorsement.derive_watermark()).map_err(|_| Error::ExecutionError)?;
+        HWM::write_for(chain_id, endorsement.derive_watermark())?;
 
         let (sz, sig) = Self::sign(&digest)?;
+        sign_log::append(endorsement.log_record());
 
         let mut tx = 0;
 
@@ -252,9 +272,21 @@ impl Baking {
         digest: [u8; 32],
         out: &mut [u8],
     ) -> Result<usize, Error> {
-        let hw = HWM::read().map_err(|_| Error::ExecutionError)?;
+        let (rem, blockdata) = BlockData::from_bytes(input)
+            .map_err(|e| Rejection::from(e).record(Error::DataInvalid))?;
+
+        //tenderbake blocks are parsed whole, nothing is signed unchecked
+        if blockdata.header.is_some() && !rem.is_empty() {
+            return Err(Rejection::Parser(ParserError::InvalidPayload).record(Error::DataInvalid));
+        }
+
+        if let Some(header) = &blockdata.header {
+            if !vote_policy::approves(&header.votes) {
+                return Err(Rejection::VoteNotAllowed.record(Error::DataInvalid));
+            }
+        }
 
-        let (_, blockdata) = BlockData::from_bytes(input).map_err(|_| Error::DataInvalid)?;
+        let hw = Self::read_watermark(blockdata.chain_id)?;
 
         //preemble should back block fitness
         match (preemble, &blockdata.fitness) {
@@ -267,9 +299,17 @@ impl Baking {
             _ => return Err(Error::DataInvalid),
         }
 
-        HWM::write(blockdata.derive_watermark()).map_err(|_| Error::ExecutionError)?;
+        HWM::write_for(blockdata.chain_id, blockdata.derive_watermark(&hw))?;
 
         let (sz, sig) = Self::sign(&digest)?;
+        let record = Record::new(
+            Kind::Block,
+            blockdata.chain_id,
+            blockdata.level,
+            blockdata.fitness.round(),
+            blockdata.payload_hash().map(|h| &h[..]).unwrap_or(&[]),
+        );
+        sign_log::append(record);
 
         let mut tx = 0;
 
@@ -297,26 +337,40 @@ impl Baking {
         use crate::parser::operations::{Operation, OperationType};
 
         let mut op = core::mem::MaybeUninit::uninit();
-        let mut operation = Operation::new(input).map_err(|_| Error::DataInvalid)?;
+        let mut operation =
+            Operation::new(input).map_err(|e| Rejection::from(e).record(Error::DataInvalid))?;
         operation
             .mut_ops()
             .parse_next_into(&mut op)
-            .map_err(|_| Error::DataInvalid)?
+            .map_err(|e| Rejection::from(e).record(Error::DataInvalid))?
             .ok_or(Error::DataInvalid)?;
 
-        let (data, branch) = match unsafe { op.assume_init() } {
-            OperationType::Delegation(deleg) => {
-                //verify that delegation.source == delegation.delegate
-                // and it matches the authorized key for baking
-                // (BAKINGPATH)
-                Ok((BakingTransactionType::Delegation(deleg), operation.branch()))
+        //everything in the operation is signed, so nothing can follow what's shown
+        match operation.mut_ops().parse_next() {
+            Ok(None) => {}
+            _ => return Err(Error::CommandNotAllowed),
+        }
+
+        let data = match unsafe { op.assume_init() } {
+            OperationType::Delegation(deleg) => BakingTransactionType::Delegation(deleg),
+            OperationType::Reveal(reveal) => BakingTransactionType::Reveal(reveal),
+            OperationType::Ballot(ballot) => BakingTransactionType::Ballot(ballot),
+            OperationType::Proposals(proposals) => BakingTransactionType::Proposals(proposals),
+            OperationType::UpdateConsensusKey(update) => {
+                BakingTransactionType::UpdateConsensusKey(update)
             }
-            OperationType::Reveal(reveal) => {
-                //what checks do we need here?
-                Ok((BakingTransactionType::Reveal(reveal), operation.branch()))
+            OperationType::DrainDelegate(drain) => BakingTransactionType::DrainDelegate(drain),
+            OperationType::SetDepositsLimit(limit) => {
+                BakingTransactionType::SetDepositsLimit(limit)
             }
-            _ => Err(Error::CommandNotAllowed),
-        }?;
+            OperationType::Transfer(tx) if Self::is_set_delegate_parameters(&tx) => {
+                BakingTransactionType::SetDelegateParameters(tx)
+            }
+            _ => return Err(Error::CommandNotAllowed),
+        };
+        Self::check_own_operation(&data)?;
+
+        let branch = operation.branch();
 
         let ui = BakingSignUI {
             send_hash,
@@ -328,6 +382,79 @@ impl Baking {
         unsafe { ui.show(flags).map(|_| 0).map_err(|_| Error::ExecutionError) }
     }
 
+    /// Checks that `tx` is a call to `set_delegate_parameters`,
+    /// which is a transaction of 0 to the source itself
+    fn is_set_delegate_parameters(tx: &Transfer<'_>) -> bool {
+        let (crv, source) = *tx.source();
+
+        let to_self = match *tx.destination() {
+            ContractID::Implicit(dest_crv, dest) => {
+                dest_crv.to_hash_prefix() == crv.to_hash_prefix() && dest == source
+            }
+            ContractID::Originated(_) => false,
+        };
+        let no_amount = tx.amount().read_as::<u64>() == Some((false, 0));
+        let entrypoint = matches!(
+            tx.parameters(),
+            Some(params) if *params.entrypoint() == Entrypoint::SetDelegateParameters
+        );
+
+        to_self && no_amount && entrypoint
+    }
+
+    /// Verifies that the operation is the baker's own:
+    /// a delegation of the baking key to itself, the reveal of the baking key,
+    /// a vote or a change of the delegate's settings made by the baking key
+    /// or the drain of a delegate for which the baking key is the consensus key
+    #[inline(never)]
+    fn check_own_operation(data: &BakingTransactionType<'_>) -> Result<(), Error> {
+        if let BakingTransactionType::Delegation(deleg) = data {
+            let (crv, source) = *deleg.source();
+            match *deleg.delegate() {
+                Some((delegate_crv, delegate))
+                    if delegate_crv.to_hash_prefix() == crv.to_hash_prefix()
+                        && delegate == source => {}
+                _ => return Err(Rejection::NotSelfDelegation.reject(Error::DataInvalid)),
+            }
+        }
+
+        let (curve, path) =
+            Self::read_baking_key()?.ok_or(Error::ApduCodeConditionsNotSatisfied)?;
+        let baker = Sign::signer_pkh(curve, &path)?;
+
+        //tz1 addresses can come from either ed25519 curve
+        // so we compare the address prefixes instead of the curves
+        let is_baker = |crv: Curve, pkh: &[u8; 20]| {
+            crv.to_hash_prefix() == curve.to_hash_prefix() && pkh == &baker
+        };
+
+        let (crv, source) = match data {
+            BakingTransactionType::Delegation(deleg) => *deleg.source(),
+            BakingTransactionType::Reveal(reveal) => {
+                //the revealed key must be the baking key
+                let (crv, pk) = *reveal.public_key();
+                let pkh: [u8; 20] = Blake2b::digest(pk).map_err(|_| Error::ExecutionError)?;
+                if !is_baker(crv, &pkh) {
+                    return Err(Rejection::WrongSource.reject(Error::DataInvalid));
+                }
+
+                *reveal.source()
+            }
+            BakingTransactionType::Ballot(ballot) => *ballot.source(),
+            BakingTransactionType::Proposals(proposals) => *proposals.source(),
+            BakingTransactionType::UpdateConsensusKey(update) => *update.source(),
+            BakingTransactionType::DrainDelegate(drain) => *drain.consensus_key(),
+            BakingTransactionType::SetDepositsLimit(limit) => *limit.source(),
+            BakingTransactionType::SetDelegateParameters(tx) => *tx.source(),
+        };
+
+        if !is_baker(crv, source) {
+            return Err(Rejection::WrongSource.reject(Error::DataInvalid));
+        }
+
+        Ok(())
+    }
+
     #[inline(never)]
     pub fn baker_sign(
         send_hash: bool,
@@ -350,7 +477,8 @@ impl Baking {
         let mut digest = [0; Sign::SIGN_HASH_SIZE];
         Self::blake2b_digest_into(cdata, &mut digest)?;
 
-        let (rem, preemble) = Preemble::from_bytes(cdata).map_err(|_| Error::DataInvalid)?;
+        let (rem, preemble) = Preemble::from_bytes(cdata)
+            .map_err(|e| Rejection::from(e).record(Error::DataInvalid))?;
 
         //endorses and bakes are automatically signed without any review
         match preemble {
@@ -371,6 +499,12 @@ impl Baking {
 enum BakingTransactionType<'b> {
     Delegation(Delegation<'b>),
     Reveal(Reveal<'b>),
+    Ballot(Ballot<'b>),
+    Proposals(Proposals<'b>),
+    UpdateConsensusKey(UpdateConsensusKey<'b>),
+    DrainDelegate(DrainDelegate<'b>),
+    SetDepositsLimit(SetDepositsLimit<'b>),
+    SetDelegateParameters(Transfer<'b>),
 }
 
 struct BakingSignUI {
@@ -381,19 +515,25 @@ struct BakingSignUI {
 }
 
 impl Viewable for BakingSignUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         let n = match self.data {
             BakingTransactionType::Delegation(data) => data.num_items(),
             BakingTransactionType::Reveal(data) => data.num_items(),
+            BakingTransactionType::Ballot(data) => data.num_items(),
+            BakingTransactionType::Proposals(data) => data.num_items(),
+            BakingTransactionType::UpdateConsensusKey(data) => data.num_items(),
+            BakingTransactionType::DrainDelegate(data) => data.num_items(),
+            BakingTransactionType::SetDepositsLimit(data) => data.num_items(),
+            BakingTransactionType::SetDelegateParameters(data) => data.num_items(),
         } + 1;
 
-        Ok(n as u8)
+        Ok(n as u16)
     }
 
     #[inline(never)]
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
@@ -412,12 +552,32 @@ impl Viewable for BakingSignUI {
 
             crate::handlers::handle_ui_message(&mex[..len], message, page)
         } else {
+            let item_n = u8::try_from(item_n - 1).map_err(|_| ViewError::NoData)?;
+
             match self.data {
                 BakingTransactionType::Delegation(data) => {
-                    data.render_item(item_n - 1, title, message, page)
+                    data.render_item(item_n, title, message, page)
                 }
                 BakingTransactionType::Reveal(data) => {
-                    data.render_item(item_n - 1, title, message, page)
+                    data.render_item(item_n, title, message, page)
+                }
+                BakingTransactionType::Ballot(data) => {
+                    data.render_item(item_n, title, message, page)
+                }
+                BakingTransactionType::Proposals(data) => {
+                    data.render_item(item_n, title, message, page)
+                }
+                BakingTransactionType::UpdateConsensusKey(data) => {
+                    data.render_item(item_n, title, message, page)
+                }
+                BakingTransactionType::DrainDelegate(data) => {
+                    data.render_item(item_n, title, message, page)
+                }
+                BakingTransactionType::SetDepositsLimit(data) => {
+                    data.render_item(item_n, title, message, page)
+                }
+                BakingTransactionType::SetDelegateParameters(data) => {
+                    data.render_item(item_n, title, message, page)
                 }
             }
         }
@@ -430,6 +590,19 @@ impl Viewable for BakingSignUI {
             Err(e) => return (0, e as _),
         };
 
+        //operations have no chain nor level, so the signed hash identifies them
+        let kind = match self.data {
+            BakingTransactionType::Delegation(_) => Kind::Delegation,
+            BakingTransactionType::Reveal(_) => Kind::Reveal,
+            BakingTransactionType::Ballot(_) => Kind::Ballot,
+            BakingTransactionType::Proposals(_) => Kind::Proposals,
+            BakingTransactionType::UpdateConsensusKey(_) => Kind::UpdateConsensusKey,
+            BakingTransactionType::DrainDelegate(_) => Kind::DrainDelegate,
+            BakingTransactionType::SetDepositsLimit(_) => Kind::SetDepositsLimit,
+            BakingTransactionType::SetDelegateParameters(_) => Kind::SetDelegateParameters,
+        };
+        sign_log::append(Record::new(kind, 0, 0, 0, &self.digest));
+
         let mut tx = 0;
 
         if self.send_hash {
@@ -459,6 +632,15 @@ pub use queryauth::{QueryAuthKey, QueryAuthKeyWithCurve};
 mod hmac;
 pub use hmac::HMAC;
 
+mod vote_policy;
+pub use vote_policy::{GetVotePolicy, SetVotePolicy, VotePolicy};
+
+mod setup;
+pub use setup::SetupBaking;
+
+mod watermark;
+pub use watermark::{QueryHWM, ResetHWM};
+
 impl ApduHandler for Baking {
     #[inline(never)]
     fn handle<'apdu>(
@@ -485,10 +667,11 @@ impl ApduHandler for Baking {
 
 #[cfg(test)]
 mod tests {
-    use crate::{crypto, utils::MaybeNullTerminatedToString};
+    use crate::{crypto, handlers::hwm::MAINNET_CHAIN_ID, utils::MaybeNullTerminatedToString};
     use bolos::crypto::bip32::BIP32Path;
 
     use arrayref::array_ref;
+    use serial_test::serial;
     use zuit::{MockDriver, Page};
 
     use super::*;
@@ -568,6 +751,138 @@ mod tests {
         assert_eq!(endorsement.endorsement_type(), b"Endorsement\x00");
     }
 
+    #[test]
+    fn test_tenderbake_endorsement_with_dal_data() {
+        let mut v = std::vec::Vec::with_capacity(1 + 4 + 32 + 1 + 2 + 4 + 4 + 32 + 2);
+        v.push(Preemble::TenderbakeEndorsement as _);
+        v.extend_from_slice(&1_u32.to_be_bytes());
+        v.extend_from_slice(&[0u8; 32]);
+        v.push(23); //tenderbake endorsement with DAL content
+        v.extend_from_slice(&0_u16.to_be_bytes()); //slot
+        v.extend_from_slice(&15_u32.to_be_bytes()); //level
+        v.extend_from_slice(&42_u32.to_be_bytes()); //round
+        v.extend_from_slice(&[0u8; 32]); //block payload hash
+
+        //the DAL content is required
+        assert!(EndorsementData::from_bytes(&v[1..]).is_err());
+        v.extend_from_slice(&[0x85, 0x01]); //DAL attestation bitset
+
+        let (rem, endorsement) = EndorsementData::from_bytes(&v[1..]).unwrap();
+        assert!(rem.is_empty());
+        assert_eq!(endorsement.level(), 15);
+        assert_eq!(endorsement.round(), Some(42));
+        assert_eq!(endorsement.endorsement_type(), b"Endorsement\x00");
+        match &endorsement {
+            EndorsementData::Tenderbake(TenderbakeEndorsement {
+                ty,
+                dal_attestation: Some(bitset),
+                ..
+            }) => {
+                assert_eq!(*ty, EndorsementType::Endorsement);
+                assert_eq!(bitset.read_as::<u64>(), Some((false, 0x85)));
+            }
+            _ => panic!("DAL content wasn't parsed"),
+        }
+
+        //watermark checks are the same as for plain endorsements
+        let hw = endorsement.derive_watermark();
+        assert!(!endorsement.validate_with_watermark(&hw));
+        v[1 + 4 + 32] = 21;
+        v.truncate(v.len() - 2);
+        let (_, plain) = EndorsementData::from_bytes(&v[1..]).unwrap();
+        assert_eq!(plain.derive_watermark(), hw);
+    }
+
+    #[test]
+    fn delegation_must_be_self_delegation() {
+        const PARTIAL_INPUT_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd\
+                                 904e\
+                                 01\
+                                 0a\
+                                 0a";
+
+        let mut other_delegate = hex::decode(PARTIAL_INPUT_HEX).expect("invalid input hex");
+        let mut withdrawal = other_delegate.clone();
+
+        other_delegate.extend_from_slice(&[0xff, 0x00]);
+        other_delegate.extend_from_slice(&[0x42; 20]);
+        withdrawal.push(0x00);
+
+        //refused before the baking key is even looked at
+        for input in [other_delegate, withdrawal] {
+            let (_, delegation) =
+                Delegation::from_bytes(&input).expect("couldn't parse delegation");
+
+            assert_eq!(
+                Baking::check_own_operation(&BakingTransactionType::Delegation(delegation)),
+                Err(Error::DataInvalid)
+            );
+        }
+    }
+
+    #[test]
+    fn set_delegate_parameters_to_self() {
+        const SOURCE_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd";
+        const PARAMETERS_HEX: &str = "ff0900000004020000000b";
+
+        let transfer = |amount: &str, destination: &str, parameters: &str| {
+            let input = std::format!(
+                "{}904e010a0a{}00{}{}",
+                SOURCE_HEX,
+                amount,
+                destination,
+                parameters
+            );
+            &*hex::decode(input).expect("invalid input hex").leak()
+        };
+
+        let check = |input: &'static [u8]| {
+            let (_, tx) = Transfer::from_bytes(input).expect("couldn't parse transfer");
+            Baking::is_set_delegate_parameters(&tx)
+        };
+
+        assert!(check(transfer("00", SOURCE_HEX, PARAMETERS_HEX)));
+
+        //with an amount
+        assert!(!check(transfer("01", SOURCE_HEX, PARAMETERS_HEX)));
+        //to someone else
+        assert!(!check(transfer(
+            "00",
+            "004242424242424242424242424242424242424242",
+            PARAMETERS_HEX
+        )));
+        //to another entrypoint
+        assert!(!check(transfer("00", SOURCE_HEX, "ff0300000004020000000b")));
+        //without parameters
+        assert!(!check(transfer("00", SOURCE_HEX, "00")));
+    }
+
+    #[test]
+    fn ballot_ui() {
+        const INPUT_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd\
+                                 fffffed4\
+                                 3e5e3a606afab74a59ca09e333633e2770b6492c5e594455b71e9a2f0ea92afb\
+                                 00";
+
+        let input = &*hex::decode(INPUT_HEX).expect("invalid input hex").leak();
+        let (_, ballot) = Ballot::from_bytes(input).expect("couldn't parse ballot");
+
+        let ui = BakingSignUI {
+            send_hash: false,
+            digest: [0; 32],
+            branch: &[0; 32],
+            data: BakingTransactionType::Ballot(ballot),
+        };
+        let mut driver = MockDriver::<_, 18, 4096>::new(ui);
+        driver.drive();
+
+        //the branch, then the ballot as in the wallet
+        let produced_ui = driver.out_ui();
+        assert_eq!(produced_ui.len(), 1 + ballot.num_items());
+        assert!(produced_ui[0][0].title.starts_with(b"Operation"));
+        assert!(produced_ui[1][0].title.starts_with(b"Type"));
+    }
+
     #[test]
     fn known_delegation() {
         const PARTIAL_INPUT_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd\
@@ -611,12 +926,50 @@ mod tests {
             })
             .expect("Couldn't find delegation item in UI");
 
-        let title = delegation_item[0]
-            .message
-            .to_string_with_check_null()
-            .expect("message was invalid UTF8");
+        let message: std::string::String = delegation_item
+            .iter()
+            .map(|Page { message, .. }| {
+                message
+                    .to_string_with_check_null()
+                    .expect("message was invalid UTF8")
+            })
+            .collect();
+
+        //verify that the message has both the name we expect in the test and the address
+        assert_eq!(
+            message,
+            std::format!("{} ({})", KNOWN_BAKER_NAME, KNOWN_BAKER_ADDR)
+        );
+    }
 
-        //verify that the message is the same as the name we expect in the test
-        assert_eq!(title, KNOWN_BAKER_NAME);
+    #[test]
+    #[serial(hwm)]
+    fn block_for_unknown_chain() {
+        HWM::format().expect("couldn't format");
+        HWM::reset(0).expect("couldn't reset");
+
+        //both chains that can be baked are taken
+        HWM::set_chains(&[MAINNET_CHAIN_ID, 0x1234_5678]).unwrap();
+
+        let mut block = std::vec::Vec::new();
+        block.extend_from_slice(&0x0BAD_C0DEu32.to_be_bytes()); //chain id
+        block.extend_from_slice(&10u32.to_be_bytes()); //level
+        block.push(42); //proto
+        block.extend_from_slice(&[0; 32]); //predecessor
+        block.extend_from_slice(&0u64.to_be_bytes()); //timestamp
+        block.push(0); //validation pass
+        block.extend_from_slice(&[0; 32]); //operation hash
+        block.extend_from_slice(&5u32.to_be_bytes()); //fitness size
+        block.extend_from_slice(&[0, 0, 0, 1, 1]); //emmy protocol
+
+        let err =
+            Baking::handle_blockdata(block.leak(), Preemble::Block, false, [0; 32], &mut [0; 260])
+                .unwrap_err();
+        assert_eq!(err, Error::UnknownChain);
+        //nothing was written for the other chains
+        assert_eq!(HWM::read_for(MAINNET_CHAIN_ID).map(|wm| wm.level()), Ok(0));
+        assert_eq!(HWM::read_for(0x1234_5678).map(|wm| wm.level()), Ok(0));
+
+        HWM::format().expect("couldn't format");
     }
 }
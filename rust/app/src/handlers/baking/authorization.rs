@@ -103,14 +103,14 @@ impl ApduHandler for AuthorizeBaking {
 }
 
 impl Viewable for AuthorizeUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(2)
     }
 
     #[inline(never)]
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
@@ -127,8 +127,7 @@ impl Viewable for AuthorizeUI {
                 let title_content = pic_str!(b"Address");
                 title[..title_content.len()].copy_from_slice(title_content);
 
-                let (len, mex) = self.addr.base58();
-                handle_ui_message(&mex[..len], message, page)
+                self.addr.render_named(message, page)
             }
             _ => Err(ViewError::NoData),
         }
@@ -217,13 +216,13 @@ impl ApduHandler for DeAuthorizeBaking {
 }
 
 impl Viewable for DeAuthorizeUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(2)
     }
 
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
@@ -239,8 +238,7 @@ impl Viewable for DeAuthorizeUI {
                 let title_content = pic_str!(b"Address");
                 title[..title_content.len()].copy_from_slice(title_content);
 
-                let (len, mex) = self.addr.base58();
-                handle_ui_message(&mex[..len], message, page)
+                self.addr.render_named(message, page)
             }
             _ => Err(ViewError::NoData),
         }
@@ -134,13 +134,13 @@ impl QueryAuthUI {
 }
 
 impl Viewable for QueryAuthUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(2)
     }
 
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
@@ -156,8 +156,7 @@ impl Viewable for QueryAuthUI {
                 let title_content = pic_str!(b"Address");
                 title[..title_content.len()].copy_from_slice(title_content);
 
-                let (len, mex) = self.addr.base58();
-                handle_ui_message(&mex[..len], message, page)
+                self.addr.render_named(message, page)
             }
             _ => Err(ViewError::NoData),
         }
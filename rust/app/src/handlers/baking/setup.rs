@@ -0,0 +1,230 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::mem::MaybeUninit;
+
+use crate::{
+    constants::{ApduError as Error, BIP32_MAX_LENGTH},
+    crypto::Curve,
+    dispatcher::ApduHandler,
+    handlers::{
+        handle_ui_message,
+        hwm::{ChainID, WaterMark, HWM},
+        public_key::{Addr, GetAddress},
+    },
+    sys::{self, crypto::bip32::BIP32Path},
+    utils::ApduBufferRead,
+};
+use arrayref::array_ref;
+use bolos::{pic_str, PIC};
+use core::convert::TryFrom;
+use zemu_sys::{Show, ViewError, Viewable};
+
+use super::Baking;
+
+pub struct SetupBaking;
+
+impl SetupBaking {
+    #[inline(never)]
+    pub fn setup(
+        curve: Curve,
+        path: BIP32Path<BIP32_MAX_LENGTH>,
+        chain_id: u32,
+        level: u32,
+        round: u32,
+        flags: &mut u32,
+    ) -> Result<u32, Error> {
+        sys::zemu_log_stack("SetupBaking::setup\x00");
+        let ui = SetupUI::new(curve, path, chain_id, level, round)?;
+
+        unsafe { ui.show(flags) }
+            .map_err(|_| Error::ExecutionError)
+            .map(|_| 0)
+    }
+}
+
+impl ApduHandler for SetupBaking {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("SetupBaking::handle\x00");
+        *tx = 0;
+
+        let req_confirmation = buffer.p1() >= 1;
+
+        //confirmation mandatory
+        if !req_confirmation {
+            return Err(Error::ApduCodeConditionsNotSatisfied);
+        }
+
+        let curve = Curve::try_from(buffer.p2()).map_err(|_| Error::InvalidP1P2)?;
+
+        let cdata = buffer.payload().map_err(|_| Error::DataInvalid)?;
+        if cdata.len() < 12 {
+            return Err(Error::WrongLength);
+        }
+
+        let chain_id = u32::from_be_bytes(*array_ref!(cdata, 0, 4));
+        let level = u32::from_be_bytes(*array_ref!(cdata, 4, 4));
+        let round = u32::from_be_bytes(*array_ref!(cdata, 8, 4));
+
+        if !WaterMark::is_valid_blocklevel(level) {
+            return Err(Error::DataInvalid);
+        }
+
+        let path =
+            BIP32Path::<BIP32_MAX_LENGTH>::read(&cdata[12..]).map_err(|_| Error::DataInvalid)?;
+
+        *tx = Self::setup(curve, path, chain_id, level, round, flags)?;
+
+        Ok(())
+    }
+}
+
+struct SetupUI {
+    curve: Curve,
+    path: BIP32Path<BIP32_MAX_LENGTH>,
+    //reduntant but makes ui faster
+    addr: Addr,
+    chain_id: ChainID,
+    level: u32,
+    round: u32,
+}
+
+impl SetupUI {
+    #[inline(never)]
+    pub fn new(
+        curve: Curve,
+        path: BIP32Path<BIP32_MAX_LENGTH>,
+        chain_id: u32,
+        level: u32,
+        round: u32,
+    ) -> Result<Self, Error> {
+        sys::zemu_log_stack("SetupUI::new\x00");
+
+        let mut addr = MaybeUninit::uninit();
+        GetAddress::new_addr_into(curve, &path, &mut addr).map_err(|_| Error::ExecutionError)?;
+
+        Ok(Self {
+            curve,
+            path,
+            //safe because we have initialized this above with `Addr::new_into`
+            addr: unsafe { addr.assume_init() },
+            chain_id: chain_id.into(),
+            level,
+            round,
+        })
+    }
+}
+
+impl Viewable for SetupUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        Ok(5)
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        let mut hwm_buf = [0; u32::FORMATTED_SIZE_DECIMAL];
+
+        match item_n {
+            0 => {
+                let title_content = pic_str!(b"Type");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(&pic_str!(b"Setup Baking")[..], message, page)
+            }
+            1 => {
+                let title_content = pic_str!(b"Address");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                self.addr.render_named(message, page)
+            }
+            2 => {
+                let title_content = pic_str!(b"Chain");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut mex = [0; ChainID::BASE58_LEN];
+                let len = self
+                    .chain_id
+                    .to_alias(&mut mex)
+                    .map_err(|_| ViewError::Unknown)?;
+
+                handle_ui_message(&mex[..len], message, page)
+            }
+            3 => {
+                let title_content = pic_str!(b"HWM Level");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(itoa(self.level, &mut hwm_buf), message, page)
+            }
+            4 => {
+                let title_content = pic_str!(b"HWM Round");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(itoa(self.round, &mut hwm_buf), message, page)
+            }
+            _ => Err(ViewError::NoData),
+        }
+    }
+
+    #[inline(never)]
+    fn accept(&mut self, out: &mut [u8]) -> (usize, u16) {
+        //get public key
+        let mut pk = MaybeUninit::uninit();
+        if GetAddress::new_key_into(self.curve, &self.path, &mut pk).is_err() {
+            return (0, Error::ExecutionError as _);
+        }
+
+        //store path & curve for key in memory
+        if Baking::store_baking_key(self.curve, self.path).is_err() {
+            return (0, Error::ExecutionError as _);
+        }
+
+        //only the given chain can be baked from now on
+        if HWM::set_chain_id(self.chain_id.into()).is_err() {
+            return (0, Error::Busy as _);
+        }
+
+        let wm = WaterMark::reset_at(self.level, self.round);
+        if HWM::write(wm).is_err() {
+            return (0, Error::Busy as _);
+        }
+
+        //write PK to out
+        // safe because it's initialized
+        let pk = unsafe { pk.assume_init() };
+        let key = pk.as_ref();
+        let len = key.len();
+        out[0] = len as u8;
+        out[1..1 + len].copy_from_slice(key);
+
+        (1 + len, Error::Success as _)
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
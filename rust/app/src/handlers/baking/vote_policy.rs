@@ -0,0 +1,358 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use bolos::{pic_str, PIC};
+use zemu_sys::{Show, ViewError, Viewable};
+
+use crate::{
+    constants::ApduError as Error,
+    dispatcher::ApduHandler,
+    handlers::handle_ui_message,
+    parser::baking::{PerBlockVotes, Vote},
+    sys::{self, flash_slot::Wear, new_flash_slot},
+    utils::{ApduBufferRead, ApduPanic},
+};
+
+const N_PAGES_VOTE_POLICY: usize = 1;
+
+#[bolos::lazy_static]
+static mut VOTE_POLICY: Wear<'static, N_PAGES_VOTE_POLICY> =
+    new_flash_slot!(N_PAGES_VOTE_POLICY).apdu_expect("NVM might be corrupted");
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+/// Toggle votes the baker is willing to sign in blocks, persisted in NVM
+///
+/// # Codec
+///
+/// [0] = version; a different version is read as no policy
+///
+/// [1] = allowed liquidity baking votes; bit 0 is on, bit 1 is off, bit 2 is pass
+///
+/// [2] = allowed adaptive issuance votes, as above
+pub struct VotePolicy {
+    liquidity_baking: u8,
+    adaptive_issuance: u8,
+}
+
+impl VotePolicy {
+    pub const VERSION: u8 = 1;
+    pub const SERIALIZED_LEN: usize = 3;
+
+    const ALL_VOTES: u8 = 0b111;
+
+    fn bit(vote: Vote) -> u8 {
+        1 << vote as u8
+    }
+
+    /// Reads the policy from NVM, if any was confirmed
+    pub fn load() -> Option<Self> {
+        let data = unsafe { VOTE_POLICY.read() }.ok()?;
+
+        Self::try_from_bytes(&data[..Self::SERIALIZED_LEN]).ok()
+    }
+
+    fn store(self) -> Result<(), Error> {
+        let mut data = [0; 52];
+        data[..Self::SERIALIZED_LEN].copy_from_slice(&self.to_bytes());
+
+        unsafe { VOTE_POLICY.write(data) }.map_err(|_| Error::ExecutionError)
+    }
+
+    fn remove() -> Result<(), Error> {
+        unsafe { VOTE_POLICY.write([0; 52]) }.map_err(|_| Error::ExecutionError)
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SERIALIZED_LEN] {
+        [Self::VERSION, self.liquidity_baking, self.adaptive_issuance]
+    }
+
+    /// Attempt to read the policy from some bytes
+    ///
+    /// Each toggle must allow at least a vote, otherwise no block could be signed
+    pub fn try_from_bytes(from: &[u8]) -> Result<Self, Error> {
+        if from.len() != Self::SERIALIZED_LEN {
+            return Err(Error::WrongLength);
+        }
+
+        if from[0] != Self::VERSION {
+            return Err(Error::DataInvalid);
+        }
+
+        let valid = |votes: u8| votes != 0 && votes & !Self::ALL_VOTES == 0;
+        if !valid(from[1]) || !valid(from[2]) {
+            return Err(Error::DataInvalid);
+        }
+
+        Ok(Self {
+            liquidity_baking: from[1],
+            adaptive_issuance: from[2],
+        })
+    }
+
+    pub fn allows(&self, votes: &PerBlockVotes) -> bool {
+        self.liquidity_baking & Self::bit(votes.liquidity_baking) != 0
+            && self.adaptive_issuance & Self::bit(votes.adaptive_issuance) != 0
+    }
+}
+
+/// Checks the votes of a block against the policy,
+/// any vote is allowed when no policy was confirmed
+pub fn approves(votes: &PerBlockVotes) -> bool {
+    VotePolicy::load()
+        .map(|policy| policy.allows(votes))
+        .unwrap_or(true)
+}
+
+pub struct GetVotePolicy;
+
+impl ApduHandler for GetVotePolicy {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("GetVotePolicy::handle\x00");
+
+        *tx = 0;
+
+        let policy = VotePolicy::load()
+            .ok_or(Error::ApduCodeEmptyBuffer)?
+            .to_bytes();
+
+        buffer.write()[..policy.len()].copy_from_slice(&policy[..]);
+        *tx = policy.len() as u32;
+
+        Ok(())
+    }
+}
+
+pub struct SetVotePolicy;
+
+impl ApduHandler for SetVotePolicy {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("SetVotePolicy::handle\x00");
+
+        *tx = 0;
+
+        let payload = buffer.payload().map_err(|_| Error::DataInvalid)?;
+
+        //an empty policy allows any vote
+        let policy = if payload.is_empty() {
+            None
+        } else {
+            Some(VotePolicy::try_from_bytes(payload)?)
+        };
+
+        let ui = VotePolicyUI { policy };
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+struct VotePolicyUI {
+    policy: Option<VotePolicy>,
+}
+
+impl VotePolicyUI {
+    /// Writes the names of the allowed votes in `out`
+    fn render_votes(votes: u8, out: &mut [u8; 16]) -> usize {
+        let mut len = 0;
+        for vote in [Vote::On, Vote::Off, Vote::Pass] {
+            if votes & VotePolicy::bit(vote) == 0 {
+                continue;
+            }
+
+            if len != 0 {
+                out[len..len + 2].copy_from_slice(b", ");
+                len += 2;
+            }
+            let name = vote.as_str().as_bytes();
+            out[len..len + name.len()].copy_from_slice(name);
+            len += name.len();
+        }
+
+        len
+    }
+}
+
+impl Viewable for VotePolicyUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        match self.policy {
+            None => Ok(1),
+            Some(_) => Ok(3),
+        }
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        let votes = match (item_n, &self.policy) {
+            (0, policy) => {
+                let title_content = pic_str!(b"Vote Policy");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let message_content = if policy.is_some() {
+                    &pic_str!(b"Enable")[..]
+                } else {
+                    &pic_str!(b"Disable")[..]
+                };
+                return handle_ui_message(message_content, message, page);
+            }
+            (1, Some(policy)) => {
+                let title_content = pic_str!(b"Liquidity Baking");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                policy.liquidity_baking
+            }
+            (2, Some(policy)) => {
+                let title_content = pic_str!(b"Adaptive Issuance");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                policy.adaptive_issuance
+            }
+            _ => return Err(ViewError::NoData),
+        };
+
+        let mut names = [0; 16];
+        let len = Self::render_votes(votes, &mut names);
+        handle_ui_message(&names[..len], message, page)
+    }
+
+    fn accept(&mut self, _: &mut [u8]) -> (usize, u16) {
+        let stored = match self.policy {
+            Some(policy) => policy.store(),
+            None => VotePolicy::remove(),
+        };
+
+        match stored {
+            Ok(_) => (0, Error::Success as _),
+            Err(e) => (0, e as _),
+        }
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        dispatcher::{handle_apdu, CLA, INS_GET_VOTE_POLICY, INS_SET_VOTE_POLICY},
+        sys::get_out,
+    };
+    use std::convert::TryInto;
+
+    use serial_test::serial;
+
+    fn votes(liquidity_baking: Vote, adaptive_issuance: Vote) -> PerBlockVotes {
+        PerBlockVotes {
+            liquidity_baking,
+            adaptive_issuance,
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let policy = VotePolicy {
+            liquidity_baking: 0b101,
+            adaptive_issuance: 0b010,
+        };
+
+        let bytes = policy.to_bytes();
+        assert_eq!(VotePolicy::try_from_bytes(&bytes), Ok(policy));
+
+        //nothing allowed
+        assert_eq!(
+            VotePolicy::try_from_bytes(&[VotePolicy::VERSION, 0, 1]),
+            Err(Error::DataInvalid)
+        );
+        //unknown vote
+        assert_eq!(
+            VotePolicy::try_from_bytes(&[VotePolicy::VERSION, 1, 0b1000]),
+            Err(Error::DataInvalid)
+        );
+        assert_eq!(
+            VotePolicy::try_from_bytes(&bytes[..2]),
+            Err(Error::WrongLength)
+        );
+    }
+
+    #[test]
+    fn allowed_votes() {
+        let policy = VotePolicy {
+            liquidity_baking: 0b101,
+            adaptive_issuance: 0b010,
+        };
+
+        assert!(policy.allows(&votes(Vote::On, Vote::Off)));
+        assert!(policy.allows(&votes(Vote::Pass, Vote::Off)));
+        assert!(!policy.allows(&votes(Vote::Off, Vote::Off)));
+        assert!(!policy.allows(&votes(Vote::On, Vote::Pass)));
+    }
+
+    #[test]
+    #[serial(vote_policy)]
+    fn apdu_set_get() {
+        let policy = [VotePolicy::VERSION, 0b100, 0b011];
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        buffer[..5].copy_from_slice(&[CLA, INS_SET_VOTE_POLICY, 0, 0, policy.len() as u8]);
+        buffer[5..5 + policy.len()].copy_from_slice(&policy);
+        handle_apdu(&mut flags, &mut tx, 5 + policy.len() as u32, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        //the mock UI accepts right away
+        let (len_out, out) = get_out().expect("vote policy confirmed");
+        assert_error_code!(len_out, out, Error::Success);
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_VOTE_POLICY, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(&buffer[..tx as usize - 2], &policy[..]);
+
+        assert!(approves(&votes(Vote::Pass, Vote::On)));
+        assert!(!approves(&votes(Vote::On, Vote::On)));
+
+        //remove
+        buffer[..5].copy_from_slice(&[CLA, INS_SET_VOTE_POLICY, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        let (len_out, out) = get_out().expect("vote policy removal confirmed");
+        assert_error_code!(len_out, out, Error::Success);
+
+        assert!(approves(&votes(Vote::On, Vote::On)));
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_VOTE_POLICY, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::ApduCodeEmptyBuffer);
+    }
+}
@@ -0,0 +1,287 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use crate::{
+    constants::ApduError as Error,
+    dispatcher::ApduHandler,
+    handlers::{
+        handle_ui_message,
+        hwm::{ChainID, WaterMark, WearError, HWM},
+    },
+    sys,
+    utils::ApduBufferRead,
+};
+use arrayref::array_ref;
+use bolos::{pic_str, PIC};
+use zemu_sys::{Show, ViewError, Viewable};
+
+/// Reads the chain id the request is about
+fn read_chain_id(buffer: &ApduBufferRead<'_>) -> Result<u32, Error> {
+    let cdata = buffer.payload().map_err(|_| Error::DataInvalid)?;
+    if cdata.len() < 4 {
+        return Err(Error::WrongLength);
+    }
+
+    Ok(u32::from_be_bytes(*array_ref!(cdata, 0, 4)))
+}
+
+/// Reads the watermark kept for `chain_id`
+///
+/// Fails with `ApduCodeEmptyBuffer` if baking was never set up
+fn read_watermark(chain_id: u32) -> Result<WaterMark, Error> {
+    match HWM::chains() {
+        Ok(_) => {}
+        Err(WearError::Uninitialized) => return Err(Error::ApduCodeEmptyBuffer),
+        Err(_) => return Err(Error::ExecutionError),
+    }
+
+    HWM::read_for(chain_id)
+}
+
+pub struct QueryHWM;
+
+impl ApduHandler for QueryHWM {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("QueryHWM::handle\x00");
+        *tx = 0;
+
+        let chain_id = read_chain_id(&buffer)?;
+        let hwm = read_watermark(chain_id)?.to_full_bytes();
+        let len = hwm.len();
+
+        let buffer = buffer.write();
+        if buffer.len() < len {
+            return Err(Error::OutputBufferTooSmall);
+        }
+
+        buffer[..len].copy_from_slice(&hwm[..]);
+        *tx = len as u32;
+
+        Ok(())
+    }
+}
+
+pub struct ResetHWM;
+
+impl ApduHandler for ResetHWM {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("ResetHWM::handle\x00");
+        *tx = 0;
+
+        let chain_id = read_chain_id(&buffer)?;
+
+        let cdata = buffer.payload().map_err(|_| Error::DataInvalid)?;
+        if cdata.len() < 12 {
+            return Err(Error::WrongLength);
+        }
+
+        let level = u32::from_be_bytes(*array_ref!(cdata, 4, 4));
+        let round = u32::from_be_bytes(*array_ref!(cdata, 8, 4));
+
+        if !WaterMark::is_valid_blocklevel(level) {
+            return Err(Error::DataInvalid);
+        }
+
+        let ui = ResetUI {
+            chain_id,
+            old: read_watermark(chain_id)?,
+            level,
+            round,
+        };
+
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+struct ResetUI {
+    chain_id: u32,
+    old: WaterMark,
+    level: u32,
+    round: u32,
+}
+
+impl Viewable for ResetUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        Ok(6)
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        let mut hwm_buf = [0; u32::FORMATTED_SIZE_DECIMAL];
+
+        let (title_content, value) = match item_n {
+            0 => {
+                let title_content = pic_str!(b"Type");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                return handle_ui_message(&pic_str!(b"Reset HWM")[..], message, page);
+            }
+            1 => {
+                let title_content = pic_str!(b"Chain");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut mex = [0; ChainID::BASE58_LEN];
+                let len = ChainID::from(self.chain_id)
+                    .to_alias(&mut mex)
+                    .map_err(|_| ViewError::Unknown)?;
+
+                return handle_ui_message(&mex[..len], message, page);
+            }
+            2 => (&pic_str!(b"Old Level")[..], self.old.level()),
+            3 => (&pic_str!(b"Old Round")[..], self.old.round()),
+            4 => (&pic_str!(b"New Level")[..], self.level),
+            5 => (&pic_str!(b"New Round")[..], self.round),
+            _ => return Err(ViewError::NoData),
+        };
+
+        title[..title_content.len()].copy_from_slice(title_content);
+        handle_ui_message(itoa(value, &mut hwm_buf), message, page)
+    }
+
+    fn accept(&mut self, _: &mut [u8]) -> (usize, u16) {
+        let wm = WaterMark::reset_at(self.level, self.round);
+
+        match HWM::write_for(self.chain_id, wm) {
+            Ok(_) => (0, Error::Success as _),
+            Err(e) => (0, e as _),
+        }
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        dispatcher::{handle_apdu, CLA, INS_QUERY_HWM, INS_RESET_HWM},
+        handlers::hwm::{FULL_HWM_LEN, MAINNET_CHAIN_ID},
+        sys::get_out,
+    };
+    use std::convert::TryInto;
+
+    use serial_test::serial;
+
+    fn query(chain_id: u32, buffer: &mut [u8; 260]) -> u32 {
+        let mut flags = 0;
+        let mut tx = 0;
+
+        buffer[..5].copy_from_slice(&[CLA, INS_QUERY_HWM, 0, 0, 4]);
+        buffer[5..9].copy_from_slice(&chain_id.to_be_bytes());
+        handle_apdu(&mut flags, &mut tx, 9, buffer);
+
+        tx
+    }
+
+    #[test]
+    fn full_watermark() {
+        let mut wm = WaterMark::reset_at(42, 3);
+        if let WaterMark::Tenderbake {
+            had_preendorsement, ..
+        } = &mut wm
+        {
+            *had_preendorsement = true;
+        }
+
+        let bytes = wm.to_full_bytes();
+        assert_eq!(&bytes[..12], &[1, 0, 0, 0, 42, 0, 0, 0, 3, 1, 0, 0]);
+        assert_eq!(&bytes[12..], &[0; 32]);
+
+        if let WaterMark::Tenderbake { payload_hash, .. } = &mut wm {
+            *payload_hash = Some([0xAB; 32]);
+        }
+        let bytes = wm.to_full_bytes();
+        assert_eq!(bytes[11], 1);
+        assert_eq!(&bytes[12..], &[0xAB; 32]);
+
+        let bytes = WaterMark::reset(7, false).to_full_bytes();
+        assert_eq!(&bytes[..12], &[0, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&bytes[12..], &[0; 32]);
+    }
+
+    fn reset(chain_id: u32, level: u32, round: u32, buffer: &mut [u8; 260]) -> u32 {
+        let mut flags = 0;
+        let mut tx = 0;
+
+        buffer[..5].copy_from_slice(&[CLA, INS_RESET_HWM, 0, 0, 12]);
+        buffer[5..9].copy_from_slice(&chain_id.to_be_bytes());
+        buffer[9..13].copy_from_slice(&level.to_be_bytes());
+        buffer[13..17].copy_from_slice(&round.to_be_bytes());
+        handle_apdu(&mut flags, &mut tx, 17, buffer);
+
+        tx
+    }
+
+    #[test]
+    #[serial(hwm)]
+    fn apdu_query_reset() {
+        let mut buffer = [0; 260];
+
+        HWM::format().expect("couldn't format");
+
+        //not set up yet
+        let tx = query(MAINNET_CHAIN_ID, &mut buffer);
+        assert_error_code!(tx, buffer, Error::ApduCodeEmptyBuffer);
+
+        HWM::reset(10).expect("couldn't reset");
+
+        let tx = query(MAINNET_CHAIN_ID, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(tx as usize, FULL_HWM_LEN + 2);
+        assert_eq!(&buffer[..12], &[0, 0, 0, 0, 10, 0, 0, 0, 0, 0, 0, 0]);
+
+        let tx = query(0x1234_5678, &mut buffer);
+        assert_error_code!(tx, buffer, Error::UnknownChain);
+
+        //resets are always to a tenderbake watermark
+        let tx = reset(MAINNET_CHAIN_ID, 20, 2, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        //the mock UI accepts right away
+        let (len_out, out) = get_out().expect("reset confirmed");
+        assert_error_code!(len_out, out, Error::Success);
+
+        let tx = query(MAINNET_CHAIN_ID, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(&buffer[..12], &[1, 0, 0, 0, 20, 0, 0, 0, 2, 0, 0, 0]);
+
+        //invalid level
+        let tx = reset(MAINNET_CHAIN_ID, u32::MAX, 0, &mut buffer);
+        assert_error_code!(tx, buffer, Error::DataInvalid);
+
+        HWM::format().expect("couldn't format");
+    }
+}
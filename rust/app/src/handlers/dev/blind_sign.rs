@@ -121,13 +121,13 @@ struct BlindSignUi {
 }
 
 impl Viewable for BlindSignUi {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(1)
     }
 
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
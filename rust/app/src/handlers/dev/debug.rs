@@ -74,13 +74,13 @@ impl Debug {
 }
 
 impl Viewable for Debug {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(1)
     }
 
     fn render_item(
         &mut self,
-        idx: u8,
+        idx: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
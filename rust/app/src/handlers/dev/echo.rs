@@ -62,13 +62,13 @@ impl ApduHandler for Echo {
 }
 
 impl Viewable for Echo {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(1)
     }
 
     fn render_item(
         &mut self,
-        idx: u8,
+        idx: u16,
         title: &mut [u8],
         message: &mut [u8],
         _: u8,
@@ -29,6 +29,12 @@ type WearLeveller = Wear<'static, N_PAGES>;
 
 pub const MAIN_HWM_LEN: usize = 4;
 pub const ALL_HWM_LEN: usize = 12;
+pub const FULL_HWM_LEN: usize = 11 + 1 + 32;
+
+/// Maximum number of chains that can be baked, each with its own watermark
+///
+/// The first is the main chain, the watermark of the second is the test one
+pub const MAX_CHAINS: usize = 2;
 
 // Mainnet Chain ID: NetXdQprcVkpaWU
 // types.h:61,0
@@ -40,6 +46,10 @@ static mut MAIN: WearLeveller = new_flash_slot!(N_PAGES).apdu_expect("NVM might
 #[bolos::lazy_static]
 static mut TEST: WearLeveller = new_flash_slot!(N_PAGES).apdu_expect("NVM might be corrupted");
 
+/// Chains that can be baked, `MAX_CHAINS` big endian ids
+///
+/// A main chain of 0 allows any chain, with the main watermark;
+/// other chains of 0 are unused
 #[bolos::lazy_static]
 static mut CHAIN_ID: WearLeveller = new_flash_slot!(N_PAGES).apdu_expect("NVM might be corrupted");
 
@@ -80,13 +90,52 @@ impl HWM {
         WaterMark::default().level().to_be_bytes()
     }
 
+    /// Sets the main chain, removing any other chain
     pub fn set_chain_id(id: u32) -> Result<(), Error> {
+        Self::set_chains(&[id])
+    }
+
+    /// Sets the chains that can be baked, the first being the main one
+    pub fn set_chains(chains: &[u32]) -> Result<(), Error> {
+        if chains.is_empty() || chains.len() > MAX_CHAINS {
+            return Err(Error::DataInvalid);
+        }
+
         let mut data = [0; 52];
-        data[..4].copy_from_slice(&id.to_be_bytes()[..]);
+        for (chunk, id) in data.chunks_exact_mut(4).zip(chains) {
+            chunk.copy_from_slice(&id.to_be_bytes()[..]);
+        }
 
         unsafe { CHAIN_ID.write(data) }.map_err(|_| Error::ExecutionError)
     }
 
+    pub fn chains() -> Result<[u32; MAX_CHAINS], WearError> {
+        let data = unsafe { CHAIN_ID.read() }?;
+
+        let mut chains = [0; MAX_CHAINS];
+        for (id, chunk) in chains.iter_mut().zip(data.chunks_exact(4)) {
+            *id = u32::from_be_bytes(*arrayref::array_ref!(chunk, 0, 4));
+        }
+
+        Ok(chains)
+    }
+
+    /// Finds the index of the watermark kept for `chain_id`
+    ///
+    /// Fails with `UnknownChain` if the chain can't be baked
+    fn chain_index(chain_id: u32) -> Result<usize, Error> {
+        let chains = Self::chains().map_err(|_| Error::ExecutionError)?;
+
+        if chains[0] == u32::from(ChainID::Any) {
+            return Ok(0);
+        }
+
+        chains
+            .iter()
+            .position(|&id| id != 0 && id == chain_id)
+            .ok_or(Error::UnknownChain)
+    }
+
     pub fn chain_id() -> Result<u32, WearError> {
         let data = unsafe { CHAIN_ID.read() }?;
 
@@ -156,9 +205,26 @@ impl HWM {
         unsafe { TEST.write(data) }.map_err(|_| Error::ExecutionError)
     }
 
-    pub fn read() -> Result<WaterMark, WearError> {
-        let main_wm: WaterMark = unsafe { MAIN.read() }?.into();
-        Ok(main_wm)
+    /// Reads the watermark of `chain_id`
+    ///
+    /// Fails with `UnknownChain` if the chain can't be baked
+    pub fn read_for(chain_id: u32) -> Result<WaterMark, Error> {
+        let data = match Self::chain_index(chain_id)? {
+            0 => unsafe { MAIN.read() },
+            _ => unsafe { TEST.read() },
+        };
+
+        data.map(WaterMark::from).map_err(|_| Error::ExecutionError)
+    }
+
+    /// Writes the watermark of `chain_id`
+    ///
+    /// Fails with `UnknownChain` if the chain can't be baked
+    pub fn write_for(chain_id: u32, wm: WaterMark) -> Result<(), Error> {
+        match Self::chain_index(chain_id)? {
+            0 => Self::write(wm),
+            _ => Self::write_test(wm),
+        }
     }
 }
 
@@ -174,6 +240,10 @@ pub enum WaterMark {
         had_endorsement: bool,
         round: u32,
         had_preendorsement: bool,
+        /// Payload hash of the last block or (pre)endorsement signed
+        payload_hash: Option<[u8; 32]>,
+        /// `payload_hash` was endorsed at this level, at this round or a lower one
+        payload_attested: bool,
     },
 }
 
@@ -184,11 +254,70 @@ impl WaterMark {
     const SERIALIZED_TAGS: PIC<&'static [u8]> =
         PIC::new(&[Self::SERIALIZED_EMMY_TAG, Self::SERIALIZED_TENDERBAKE_TAG]);
 
+    //flags of the serialized payload hash
+    const PAYLOAD_HASHED: u8 = 1;
+    const PAYLOAD_ATTESTED: u8 = 2;
+
     pub fn level(&self) -> u32 {
         match self {
             WaterMark::Emmy { level, .. } | WaterMark::Tenderbake { level, .. } => *level,
         }
     }
+
+    /// Round of the watermark, emmy watermarks are always at round 0
+    pub fn round(&self) -> u32 {
+        match self {
+            WaterMark::Emmy { .. } => 0,
+            WaterMark::Tenderbake { round, .. } => *round,
+        }
+    }
+
+    /// Serializes the watermark as returned by the new API
+    ///
+    /// [0] = type; 0 is emmy, 1 is tenderbake
+    ///
+    /// [1..5] = level, big endian
+    ///
+    /// [5..9] = round, big endian
+    ///
+    /// [9] = 1 if a preendorsement was signed at this level and round
+    ///
+    /// [10] = 1 if an endorsement was signed at this level and round
+    ///
+    /// [11] = 1 if the payload hash was recorded
+    ///
+    /// [12..44] = payload hash of the last block or (pre)endorsement signed, all 0s if not recorded
+    pub fn to_full_bytes(&self) -> [u8; FULL_HWM_LEN] {
+        let (ty, had_preendorsement, had_endorsement, payload_hash) = match self {
+            WaterMark::Emmy {
+                had_endorsement, ..
+            } => (Self::SERIALIZED_EMMY_TAG, false, *had_endorsement, None),
+            WaterMark::Tenderbake {
+                had_endorsement,
+                had_preendorsement,
+                payload_hash,
+                ..
+            } => (
+                Self::SERIALIZED_TENDERBAKE_TAG,
+                *had_preendorsement,
+                *had_endorsement,
+                payload_hash.as_ref(),
+            ),
+        };
+
+        let mut out = [0; FULL_HWM_LEN];
+        out[0] = ty;
+        out[1..5].copy_from_slice(&self.level().to_be_bytes());
+        out[5..9].copy_from_slice(&self.round().to_be_bytes());
+        out[9] = had_preendorsement as _;
+        out[10] = had_endorsement as _;
+        if let Some(hash) = payload_hash {
+            out[11] = 1;
+            out[12..].copy_from_slice(&hash[..]);
+        }
+
+        out
+    }
 }
 
 impl From<&[u8; 52]> for WaterMark {
@@ -222,12 +351,23 @@ impl From<&[u8; 52]> for WaterMark {
                 array.copy_from_slice(&from[read..read + 4]);
                 u32::from_be_bytes(array)
             };
+            read += 4;
+
+            //watermarks stored before the payload hash was kept have a 0 flag
+            let flags = from[read];
+            let payload_hash = if flags & Self::PAYLOAD_HASHED != 0 {
+                Some(*arrayref::array_ref!(from, read + 1, 32))
+            } else {
+                None
+            };
 
             this = Self::Tenderbake {
                 level,
                 round,
                 had_endorsement,
                 had_preendorsement,
+                payload_hash,
+                payload_attested: flags & Self::PAYLOAD_ATTESTED != 0,
             };
         } else {
             this = Self::Emmy {
@@ -271,12 +411,22 @@ impl From<WaterMark> for [u8; 52] {
                 had_endorsement,
                 round,
                 had_preendorsement,
+                payload_hash,
+                payload_attested,
             } => {
                 out[write] = WaterMark::SERIALIZED_TENDERBAKE_TAG;
                 write += 1;
 
                 write += serialize_data(&mut out[write..], level, had_endorsement);
-                serialize_data(&mut out[write..], round, had_preendorsement);
+                write += serialize_data(&mut out[write..], round, had_preendorsement);
+
+                if let Some(hash) = payload_hash {
+                    out[write] = WaterMark::PAYLOAD_HASHED;
+                    out[write + 1..write + 1 + 32].copy_from_slice(&hash[..]);
+                }
+                if payload_attested {
+                    out[write] |= WaterMark::PAYLOAD_ATTESTED;
+                }
             }
         }
 
@@ -292,6 +442,8 @@ impl WaterMark {
                 round: 0,
                 had_endorsement: false,
                 had_preendorsement: false,
+                payload_hash: None,
+                payload_attested: false,
             }
         } else {
             Self::Emmy {
@@ -301,6 +453,18 @@ impl WaterMark {
         }
     }
 
+    /// Tenderbake watermark at the given level and round, with nothing signed yet
+    pub fn reset_at(level: u32, round: u32) -> Self {
+        Self::Tenderbake {
+            level,
+            round,
+            had_endorsement: false,
+            had_preendorsement: false,
+            payload_hash: None,
+            payload_attested: false,
+        }
+    }
+
     //return !(lvl & 0xC0000000);
     #[inline(never)]
     pub fn is_valid_blocklevel(level: u32) -> bool {
@@ -412,3 +576,36 @@ impl ChainID {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serial_test::serial;
+
+    const TESTNET_CHAIN_ID: u32 = 0x1234_5678;
+
+    #[test]
+    #[serial(hwm)]
+    fn watermark_per_chain() {
+        HWM::format().expect("couldn't format");
+        HWM::reset(10).expect("couldn't reset");
+
+        //only mainnet after a reset
+        assert_eq!(HWM::read_for(MAINNET_CHAIN_ID).map(|wm| wm.level()), Ok(10));
+        assert_eq!(HWM::read_for(TESTNET_CHAIN_ID), Err(Error::UnknownChain));
+
+        HWM::set_chains(&[MAINNET_CHAIN_ID, TESTNET_CHAIN_ID]).unwrap();
+        HWM::write_for(TESTNET_CHAIN_ID, WaterMark::reset(42, false)).unwrap();
+        assert_eq!(HWM::read_for(TESTNET_CHAIN_ID).map(|wm| wm.level()), Ok(42));
+        assert_eq!(HWM::read_for(MAINNET_CHAIN_ID).map(|wm| wm.level()), Ok(10));
+
+        //any chain shares the main watermark
+        HWM::set_chain_id(ChainID::Any.into()).unwrap();
+        assert_eq!(HWM::read_for(TESTNET_CHAIN_ID).map(|wm| wm.level()), Ok(10));
+
+        assert_eq!(HWM::set_chains(&[1, 2, 3]), Err(Error::DataInvalid));
+
+        HWM::format().expect("couldn't format");
+    }
+}
@@ -138,14 +138,14 @@ impl SetupUI {
 }
 
 impl Viewable for SetupUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(5)
     }
 
     #[inline(never)]
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
@@ -37,7 +37,8 @@ impl ApduHandler for LegacySign {
         use crate::handlers::signing::Sign;
 
         if let Some(upload) = Uploader::new(Sign).upload(&buffer)? {
-            *tx = Sign::start_sign(false, upload.p2, upload.first, upload.data, flags)?;
+            *tx = Sign::start_sign(false, upload.p2, upload.first, upload.data, flags)
+                .map_err(Error::legacy)?;
         }
 
         Ok(())
@@ -64,7 +65,8 @@ impl ApduHandler for LegacySign {
                 upload.data,
                 buffer.write(),
                 flags,
-            )?;
+            )
+            .map_err(Error::legacy)?;
         }
 
         Ok(())
@@ -82,7 +84,8 @@ impl ApduHandler for LegacySignWithHash {
         use crate::handlers::signing::Sign;
 
         if let Some(upload) = Uploader::new(Sign).upload(&buffer)? {
-            *tx = Sign::start_sign(true, upload.p2, upload.first, upload.data, flags)?;
+            *tx = Sign::start_sign(true, upload.p2, upload.first, upload.data, flags)
+                .map_err(Error::legacy)?;
         }
 
         Ok(())
@@ -109,7 +112,8 @@ impl ApduHandler for LegacySignWithHash {
                 upload.data,
                 buffer.write(),
                 flags,
-            )?;
+            )
+            .map_err(Error::legacy)?;
         }
 
         Ok(())
@@ -127,7 +131,8 @@ impl ApduHandler for LegacySignUnsafe {
         use crate::handlers::signing::Sign;
 
         if let Some(upload) = Uploader::new(Sign).upload(&buffer)? {
-            *tx = Sign::start_sign(false, upload.p2, upload.first, upload.data, flags)?;
+            *tx = Sign::start_sign(false, upload.p2, upload.first, upload.data, flags)
+                .map_err(Error::legacy)?;
         }
 
         Ok(())
@@ -197,4 +202,39 @@ mod tests {
         let expected = Blake2b::<32>::digest(MSG).unwrap();
         assert_eq!(&expected, out_hash);
     }
+
+    #[test]
+    #[cfg(feature = "wallet")]
+    #[serial(ui)]
+    fn apdu_legacy_parser_error() {
+        use crate::dispatcher::INS_LEGACY_SIGN;
+
+        //transfer cut short after its tag
+        let mut msg = std::vec![0x03];
+        msg.extend_from_slice(&[0; 32]); //branch
+        msg.push(0x6C);
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        buffer[0] = CLA;
+        buffer[1] = INS_LEGACY_SIGN;
+        buffer[2] = LegacyPacketType::Init.into();
+        let len = prepare_buffer(&mut buffer, &[44, 1729, 0, 0], Curve::Ed25519);
+
+        handle_apdu(&mut flags, &mut tx, 5 + len as u32, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        buffer[0] = CLA;
+        buffer[1] = INS_LEGACY_SIGN;
+        buffer[2] = LegacyPacketType::AddAndLast.into();
+        buffer[3] = 0;
+        buffer[4] = msg.len() as u8;
+        buffer[5..5 + msg.len()].copy_from_slice(&msg);
+
+        //legacy clients don't know the parser status words
+        handle_apdu(&mut flags, &mut tx, 5 + msg.len() as u32, &mut buffer);
+        assert_error_code!(tx, buffer, Error::DataInvalid);
+    }
 }
@@ -13,7 +13,12 @@
 *  See the License for the specific language governing permissions and
 *  limitations under the License.
 ********************************************************************************/
+pub mod address_book;
+pub mod policy;
 pub mod public_key;
+pub mod rejection;
+pub mod session;
+pub mod settings;
 pub mod signing;
 pub mod version;
 
@@ -24,6 +29,9 @@ pub mod legacy;
 #[cfg(feature = "baking")]
 pub mod hwm;
 
+#[cfg(feature = "baking")]
+pub mod sign_log;
+
 #[cfg(feature = "dev")]
 pub mod dev;
 
@@ -1,70 +1,46 @@
-#![allow(non_camel_case_types, non_upper_case_globals, non_snake_case)]
 use nom::error::ErrorKind;
 
-#[repr(u32)]
-#[derive(Copy, Clone, PartialEq)]
-/// ParserError is the counterpart of
-/// the parse_error_t in c,
-/// we redeclare it here, just for interpolation
-/// purposes
+use crate::constants::ApduError;
 
+#[repr(u8)]
+#[derive(Copy, Clone, PartialEq, Eq)]
+/// Reasons a Tezos payload failed to parse
+///
+/// The discriminant is the code reported by `GET_LAST_ERROR`
+/// and the low byte of the status word (see `ApduError`)
 #[cfg_attr(test, derive(Debug))]
 pub enum ParserError {
-    // Generic errors
-    parser_no_data,
-    parser_init_context_empty,
-    parser_display_idx_out_of_range,
-    parser_display_page_out_of_range,
-    parser_unexpected_error,
-    parser_no_memory_for_state,
-    // Context related errors
-    parser_context_mismatch,
-    parser_context_unexpected_size,
-    parser_context_invalid_chars,
-    // Required fields
-    // Coin specific
-    parser_post_condition_failed,
-    parser_invalid_contract_name,
-    parser_invalid_asset_name,
-    parser_invalid_clarity_name,
-    parser_invalid_fungible_code,
-    parser_invalid_non_fungible_code,
-    parser_invalid_asset_info,
-    parser_invalid_post_condition,
-    parser_invalid_post_condition_principal,
-    parser_invalid_hash_mode,
-    parser_invalid_signature,
-    parser_invalid_pubkey_encoding,
-    parser_invalid_auth_type,
-    parser_invalid_argument_id,
-    parser_invalid_token_transfer_principal,
-    parser_invalid_transaction_payload,
-    parser_invalid_address_version,
-    parser_stacks_string_too_long,
-    parser_unexpected_type,
-    parser_unexpected_buffer_end,
-    parser_unexpected_value,
-    parser_unexpected_number_items,
-    parser_unexpected_characters,
-    parser_unexpected_field,
-    parser_value_out_of_range,
-    parser_invalid_address,
-    parser_invalid_token_transfer_type,
-    UnknownOperation,
-    UnimplementedOperation,
-    InvalidBallotVote,
-    ProposalsLengthInvalid,
-    InvalidEndorsementType,
-    InvalidProtocolVersion,
+    /// The data ended before it was expected to
+    UnexpectedEof = 0x01,
+    /// A number didn't fit or overflowed
+    ValueOutOfRange = 0x02,
+    /// Unknown kind of address or contract
+    InvalidAddress = 0x03,
+    /// Unknown kind of public key
+    InvalidPublicKey = 0x04,
+    /// Unknown kind of entrypoint
+    InvalidEntrypoint = 0x05,
+    /// Unknown magic byte
+    InvalidPreemble = 0x06,
+    /// Unknown operation tag
+    UnknownOperation = 0x07,
+    InvalidBallotVote = 0x08,
+    InvalidProposalsLength = 0x09,
+    InvalidEndorsementType = 0x0A,
+    /// The block is of neither of the supported protocols
+    InvalidProtocolVersion = 0x0B,
+    /// A field has an unexpected value (like a wrong tag)
+    InvalidPayload = 0x0C,
+    /// Any other failure
+    Unexpected = 0x0D,
 }
 
 impl From<ErrorKind> for ParserError {
     fn from(err: ErrorKind) -> Self {
         match err {
-            ErrorKind::Eof => ParserError::parser_unexpected_buffer_end,
-            ErrorKind::Permutation => ParserError::parser_unexpected_type,
-            ErrorKind::TooLarge => ParserError::parser_value_out_of_range,
-            _ => ParserError::parser_unexpected_error,
+            ErrorKind::Eof => ParserError::UnexpectedEof,
+            ErrorKind::TooLarge => ParserError::ValueOutOfRange,
+            _ => ParserError::Unexpected,
         }
     }
 }
@@ -86,3 +62,64 @@ impl From<ParserError> for nom::Err<ParserError> {
         nom::Err::Error(error)
     }
 }
+
+impl From<nom::Err<ParserError>> for ParserError {
+    fn from(error: nom::Err<ParserError>) -> Self {
+        match error {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e,
+            nom::Err::Incomplete(_) => ParserError::UnexpectedEof,
+        }
+    }
+}
+
+impl From<ParserError> for ApduError {
+    fn from(error: ParserError) -> Self {
+        match error {
+            ParserError::UnexpectedEof => Self::ParserUnexpectedEof,
+            ParserError::ValueOutOfRange => Self::ParserValueOutOfRange,
+            ParserError::InvalidAddress => Self::ParserInvalidAddress,
+            ParserError::InvalidPublicKey => Self::ParserInvalidPublicKey,
+            ParserError::InvalidEntrypoint => Self::ParserInvalidEntrypoint,
+            ParserError::InvalidPreemble => Self::ParserInvalidPreemble,
+            ParserError::UnknownOperation => Self::ParserUnknownOperation,
+            ParserError::InvalidBallotVote => Self::ParserInvalidBallotVote,
+            ParserError::InvalidProposalsLength => Self::ParserInvalidProposalsLength,
+            ParserError::InvalidEndorsementType => Self::ParserInvalidEndorsementType,
+            ParserError::InvalidProtocolVersion => Self::ParserInvalidProtocolVersion,
+            ParserError::InvalidPayload => Self::ParserInvalidPayload,
+            ParserError::Unexpected => Self::ParserUnexpected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn status_words() {
+        const ALL: &[ParserError] = &[
+            ParserError::UnexpectedEof,
+            ParserError::ValueOutOfRange,
+            ParserError::InvalidAddress,
+            ParserError::InvalidPublicKey,
+            ParserError::InvalidEntrypoint,
+            ParserError::InvalidPreemble,
+            ParserError::UnknownOperation,
+            ParserError::InvalidBallotVote,
+            ParserError::InvalidProposalsLength,
+            ParserError::InvalidEndorsementType,
+            ParserError::InvalidProtocolVersion,
+            ParserError::InvalidPayload,
+            ParserError::Unexpected,
+        ];
+
+        for &error in ALL {
+            let sw = ApduError::from(error) as u16;
+            assert_eq!(sw, 0xB000 | error as u16);
+            assert_eq!(ApduError::try_from(sw).unwrap(), ApduError::from(error));
+            assert_eq!(ApduError::from(error).legacy(), ApduError::DataInvalid);
+        }
+    }
+}
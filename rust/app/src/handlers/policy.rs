@@ -0,0 +1,725 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use core::convert::TryFrom;
+use std::mem::MaybeUninit;
+
+use bolos::{crypto::bip32::BIP32Path, pic_str, PIC};
+use zemu_sys::{Show, ViewError, Viewable};
+
+use crate::{
+    constants::{ApduError as Error, BIP32_MAX_LENGTH},
+    crypto::Curve,
+    dispatcher::ApduHandler,
+    handlers::{
+        handle_ui_message, intstr_to_fpstr_inplace,
+        public_key::{Addr, GetAddress},
+        settings::Settings,
+    },
+    parser::operations::{EncodedOperations, OperationType, Summary},
+    sys::{self, flash_slot::Wear, new_flash_slot},
+    utils::{ApduBufferRead, ApduPanic},
+};
+
+const N_PAGES_POLICY: usize = 1;
+//written on every unattended signature
+const N_PAGES_SPENDING: usize = 8;
+
+#[bolos::lazy_static]
+static mut POLICY: Wear<'static, N_PAGES_POLICY> =
+    new_flash_slot!(N_PAGES_POLICY).apdu_expect("NVM might be corrupted");
+
+#[bolos::lazy_static]
+static mut SPENDING: Wear<'static, N_PAGES_SPENDING> =
+    new_flash_slot!(N_PAGES_SPENDING).apdu_expect("NVM might be corrupted");
+
+#[derive(Clone, Copy, PartialEq, Eq, property::Property)]
+#[cfg_attr(test, derive(Debug))]
+#[property(mut(disable), get(public), set(disable))]
+/// Rules to sign operations without review, persisted in NVM
+///
+/// # Codec
+///
+/// [0] = version; a different version is read as no policy
+///
+/// [1] = curve of the allowed key
+///
+/// [2] = allowed operations; bit 0 is transfer, bit 1 is reveal
+///
+/// [3] = max transfers per request; 0 for no limit
+///
+/// [4..12] = max amount per transfer in mutez (big endian)
+///
+/// [12..20] = max amount, fees and storage burn signed until the policy is confirmed again,
+/// in mutez (big endian)
+///
+/// [20..28] = max fee per operation in mutez (big endian)
+///
+/// [28..] = BIP32 path of the allowed key, at most `MAX_PATH_LEN` components
+pub struct Policy {
+    curve: Curve,
+    path: BIP32Path<BIP32_MAX_LENGTH>,
+    kinds: u8,
+    max_transfers: u8,
+    max_amount: u64,
+    budget: u64,
+    max_fee: u64,
+}
+
+impl Policy {
+    /// Policies of previous versions had a daily limit, so they are dropped
+    pub const VERSION: u8 = 2;
+
+    pub const KIND_TRANSFER: u8 = 1 << 0;
+    pub const KIND_REVEAL: u8 = 1 << 1;
+
+    /// Limited by the space in NVM
+    pub const MAX_PATH_LEN: usize = 5;
+    pub const MAX_SERIALIZED_LEN: usize = 29 + 4 * Self::MAX_PATH_LEN;
+
+    /// Reads the policy from NVM, if any was confirmed
+    pub fn load() -> Option<Self> {
+        let data = unsafe { POLICY.read() }.ok()?;
+        //the path length tells how much of the slot is used
+        let len = 29 + 4 * data[28] as usize;
+
+        Self::try_from_bytes(data.get(..len)?).ok()
+    }
+
+    /// Writes the policy to NVM, resetting the amount spent
+    fn store(self) -> Result<(), Error> {
+        let (len, bytes) = self.to_bytes();
+
+        let mut data = [0; 52];
+        data[..len].copy_from_slice(&bytes[..len]);
+        unsafe { POLICY.write(data) }.map_err(|_| Error::ExecutionError)?;
+
+        Spending::default().store()
+    }
+
+    /// Removes the policy from NVM
+    fn remove() -> Result<(), Error> {
+        unsafe { POLICY.write([0; 52]) }.map_err(|_| Error::ExecutionError)
+    }
+
+    pub fn to_bytes(self) -> (usize, [u8; Self::MAX_SERIALIZED_LEN]) {
+        let mut out = [0; Self::MAX_SERIALIZED_LEN];
+        out[0] = Self::VERSION;
+        out[1] = self.curve.into();
+        out[2] = self.kinds;
+        out[3] = self.max_transfers;
+        out[4..12].copy_from_slice(&self.max_amount.to_be_bytes());
+        out[12..20].copy_from_slice(&self.budget.to_be_bytes());
+        out[20..28].copy_from_slice(&self.max_fee.to_be_bytes());
+
+        let components = self.path.components();
+        out[28] = components.len() as u8;
+        for (chunk, component) in out[29..].chunks_exact_mut(4).zip(components) {
+            chunk.copy_from_slice(&component.to_be_bytes());
+        }
+
+        (29 + 4 * components.len(), out)
+    }
+
+    /// Attempt to read the policy from some bytes
+    pub fn try_from_bytes(from: &[u8]) -> Result<Self, Error> {
+        if from.len() < 29 || from.len() > Self::MAX_SERIALIZED_LEN {
+            return Err(Error::WrongLength);
+        }
+
+        if from[0] != Self::VERSION {
+            return Err(Error::DataInvalid);
+        }
+
+        let kinds = from[2];
+        if kinds & !(Self::KIND_TRANSFER | Self::KIND_REVEAL) != 0 {
+            return Err(Error::DataInvalid);
+        }
+
+        let read_u64 = |at: usize| u64::from_be_bytes(*arrayref::array_ref!(from, at, 8));
+
+        Ok(Self {
+            curve: Curve::try_from(from[1]).map_err(|_| Error::DataInvalid)?,
+            path: BIP32Path::read(&from[28..]).map_err(|_| Error::DataInvalid)?,
+            kinds,
+            max_transfers: from[3],
+            max_amount: read_u64(4),
+            budget: read_u64(12),
+            max_fee: read_u64(20),
+        })
+    }
+
+    /// Checks the operations in `ops` against the policy,
+    /// returning the total amount, fees and storage burn spent
+    ///
+    /// The sources of the operations are expected to be verified already
+    fn check(&self, ops: EncodedOperations<'_>) -> Option<u64> {
+        let mut total = 0u64;
+        let mut transfers = 0usize;
+
+        for_each_spend(ops, self.max_amount, self.max_fee, |spend| {
+            let kind = if spend.transfer {
+                transfers += 1;
+                Self::KIND_TRANSFER
+            } else {
+                Self::KIND_REVEAL
+            };
+            if self.kinds & kind == 0 {
+                return None;
+            }
+
+            total = total.checked_add(spend.total()?)?;
+            Some(())
+        })?;
+
+        if self.max_transfers != 0 && transfers > self.max_transfers as usize {
+            return None;
+        }
+
+        Some(total)
+    }
+}
+
+/// What an operation signed without review spends, in mutez
+pub(crate) struct Spend {
+    pub transfer: bool,
+    pub amount: u64,
+    pub fee: u64,
+    pub burn: u64,
+}
+
+impl Spend {
+    pub fn total(&self) -> Option<u64> {
+        self.amount.checked_add(self.fee)?.checked_add(self.burn)
+    }
+}
+
+/// Calls `f` with what each operation in `ops` spends
+///
+/// Only transfers without parameters and reveals can be signed without review,
+/// so any other operation, an amount above `max_amount` or a fee above `max_fee`
+/// stops the walk and returns None, as does `f` returning None or an empty group
+pub(crate) fn for_each_spend(
+    mut ops: EncodedOperations<'_>,
+    max_amount: u64,
+    max_fee: u64,
+    mut f: impl FnMut(Spend) -> Option<()>,
+) -> Option<()> {
+    let mut n_ops = 0usize;
+
+    loop {
+        let op = match ops.parse_next() {
+            Ok(Some(op)) => op,
+            Ok(None) => break,
+            Err(_) => return None,
+        };
+        n_ops += 1;
+
+        let (amount, fee, storage_limit) = match op {
+            //contract calls can do more than just paying
+            OperationType::Transfer(tx) if tx.parameters().is_none() => {
+                (Some(*tx.amount()), *tx.fee(), *tx.storage_limit())
+            }
+            //needed before the first transfer of an account
+            OperationType::Reveal(rev) => (None, *rev.fee(), *rev.storage_limit()),
+            _ => return None,
+        };
+
+        let amount = match amount {
+            Some(amount) => amount.read_as::<u64>()?.1,
+            None => 0,
+        };
+        if amount > max_amount {
+            return None;
+        }
+
+        let (_, fee) = fee.read_as::<u64>()?;
+        if fee > max_fee {
+            return None;
+        }
+
+        let (_, storage_limit) = storage_limit.read_as::<u64>()?;
+        let burn = storage_limit.checked_mul(Summary::COST_PER_BYTE)?;
+
+        f(Spend {
+            transfer: matches!(op, OperationType::Transfer(_)),
+            amount,
+            fee,
+            burn,
+        })?;
+    }
+
+    if n_ops == 0 {
+        return None;
+    }
+
+    Some(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(test, derive(Debug))]
+/// What was signed without review since the policy was confirmed, persisted in NVM
+///
+/// # Codec
+///
+/// [0..8] = amount, fees and storage burn spent (big endian)
+struct Spending {
+    spent: u64,
+}
+
+impl Spending {
+    pub const SERIALIZED_LEN: usize = 8;
+
+    fn load() -> Self {
+        match unsafe { SPENDING.read() } {
+            Ok(data) => Self {
+                spent: u64::from_be_bytes(*arrayref::array_ref!(data, 0, 8)),
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn store(self) -> Result<(), Error> {
+        let mut data = [0; 52];
+        data[..Self::SERIALIZED_LEN].copy_from_slice(&self.to_bytes());
+
+        unsafe { SPENDING.write(data) }.map_err(|_| Error::ExecutionError)
+    }
+
+    fn to_bytes(self) -> [u8; Self::SERIALIZED_LEN] {
+        self.spent.to_be_bytes()
+    }
+}
+
+/// Checks if the operations in `ops` can be signed without review
+/// with the key of `curve` and `path`
+///
+/// When they can, the amount spent is recorded right away
+#[inline(never)]
+pub fn approves(
+    curve: Curve,
+    path: &BIP32Path<BIP32_MAX_LENGTH>,
+    ops: EncodedOperations<'_>,
+) -> bool {
+    sys::zemu_log_stack("policy::approves\x00");
+
+    let policy = match Policy::load() {
+        Some(policy) if policy.curve == curve && policy.path == *path => policy,
+        _ => return false,
+    };
+
+    //the budget only goes back up once the user confirms a policy again
+    let total = policy
+        .check(ops)
+        .and_then(|amount| Spending::load().spent.checked_add(amount))
+        .filter(|&total| total <= policy.budget);
+
+    match total {
+        Some(spent) => Spending { spent }.store().is_ok(),
+        None => false,
+    }
+}
+
+pub struct GetPolicy;
+
+impl ApduHandler for GetPolicy {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("GetPolicy::handle\x00");
+
+        *tx = 0;
+
+        let (len, policy) = Policy::load().ok_or(Error::ApduCodeEmptyBuffer)?.to_bytes();
+        let spending = Spending::load().to_bytes();
+
+        let buffer = buffer.write();
+        buffer[..spending.len()].copy_from_slice(&spending[..]);
+        buffer[spending.len()..spending.len() + len].copy_from_slice(&policy[..len]);
+        *tx = (spending.len() + len) as u32;
+
+        Ok(())
+    }
+}
+
+pub struct SetPolicy;
+
+impl ApduHandler for SetPolicy {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("SetPolicy::handle\x00");
+
+        *tx = 0;
+
+        let payload = buffer.payload().map_err(|_| Error::DataInvalid)?;
+
+        //an empty policy disables unattended signing
+        let ui = if payload.is_empty() {
+            PolicyUI { policy: None }
+        } else {
+            let policy = Policy::try_from_bytes(payload)?;
+
+            let mut addr = MaybeUninit::uninit();
+            GetAddress::new_addr_into(policy.curve, &policy.path, &mut addr)
+                .map_err(|_| Error::ExecutionError)?;
+
+            PolicyUI {
+                //safe because we have initialized this above with `new_addr_into`
+                policy: Some((policy, unsafe { addr.assume_init() })),
+            }
+        };
+
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+struct PolicyUI {
+    //the address is kept to speed up the UI
+    policy: Option<(Policy, Addr)>,
+}
+
+impl PolicyUI {
+    /// Writes the names of the allowed operations in `out`
+    fn render_kinds(kinds: u8, out: &mut [u8; 32]) -> usize {
+        let names: [(u8, &[u8]); 2] = [
+            (Policy::KIND_TRANSFER, pic_str!(b"Transfer")),
+            (Policy::KIND_REVEAL, pic_str!(b"Reveal")),
+        ];
+
+        let mut len = 0;
+        for (_, name) in names.iter().filter(|(kind, _)| kinds & kind != 0) {
+            //remove null terminator
+            let name = &name[..name.len() - 1];
+
+            if len != 0 {
+                out[len..len + 2].copy_from_slice(b", ");
+                len += 2;
+            }
+            out[len..len + name.len()].copy_from_slice(name);
+            len += name.len();
+        }
+
+        len
+    }
+
+    /// Renders an amount in mutez as tez
+    fn render_amount(amount: u64, message: &mut [u8], page: u8) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        //+2 for decimal formatting
+        let mut amount_buf = [0; u64::FORMATTED_SIZE_DECIMAL + 2];
+        itoa(amount, &mut amount_buf);
+
+        handle_ui_message(
+            intstr_to_fpstr_inplace(&mut amount_buf, Settings::load().unit().decimals())
+                .map_err(|_| ViewError::Unknown)?,
+            message,
+            page,
+        )
+    }
+}
+
+impl Viewable for PolicyUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        match self.policy {
+            None => Ok(1),
+            Some(_) => Ok(7),
+        }
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        let (policy, addr) = match (item_n, &self.policy) {
+            (0, policy) => {
+                let title_content = pic_str!(b"Unattended");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let message_content = if policy.is_some() {
+                    &pic_str!(b"Enable")[..]
+                } else {
+                    &pic_str!(b"Disable")[..]
+                };
+                return handle_ui_message(message_content, message, page);
+            }
+            (_, Some(policy)) => policy,
+            (_, None) => return Err(ViewError::NoData),
+        };
+
+        match item_n {
+            1 => {
+                let title_content = pic_str!(b"Source");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (len, mex) = addr.base58();
+                handle_ui_message(&mex[..len], message, page)
+            }
+            2 => {
+                let title_content = pic_str!(b"Operations");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut kinds = [0; 32];
+                let len = Self::render_kinds(policy.kinds, &mut kinds);
+                handle_ui_message(&kinds[..len], message, page)
+            }
+            3 => {
+                let title_content = pic_str!(b"Max transfers");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                if policy.max_transfers == 0 {
+                    return handle_ui_message(&pic_str!(b"no limit")[..], message, page);
+                }
+
+                let mut itoa_buf = [0; u8::FORMATTED_SIZE_DECIMAL];
+                handle_ui_message(itoa(policy.max_transfers, &mut itoa_buf), message, page)
+            }
+            4 => {
+                let title_content = pic_str!(b"Max amount");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(policy.max_amount, message, page)
+            }
+            5 => {
+                let title_content = pic_str!(b"Budget");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(policy.budget, message, page)
+            }
+            6 => {
+                let title_content = pic_str!(b"Max fee");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(policy.max_fee, message, page)
+            }
+            _ => Err(ViewError::NoData),
+        }
+    }
+
+    fn accept(&mut self, _: &mut [u8]) -> (usize, u16) {
+        let stored = match self.policy {
+            Some((policy, _)) => policy.store(),
+            None => Policy::remove(),
+        };
+
+        match stored {
+            Ok(_) => (0, Error::Success as _),
+            Err(e) => (0, e as _),
+        }
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        dispatcher::{handle_apdu, CLA, INS_GET_POLICY, INS_SET_POLICY},
+        sys::get_out,
+    };
+    use std::{convert::TryInto, vec, vec::Vec};
+
+    use serial_test::serial;
+
+    fn policy() -> Policy {
+        Policy {
+            curve: Curve::Ed25519,
+            path: BIP32Path::new([44, 1729, 0, 0].iter().map(|n| 0x8000_0000 + n)).unwrap(),
+            kinds: Policy::KIND_TRANSFER | Policy::KIND_REVEAL,
+            max_transfers: 2,
+            max_amount: 1_000,
+            budget: 2_500,
+            max_fee: 100,
+        }
+    }
+
+    //fee, counter, gas_limit and storage_limit are 1
+    fn transfer(amount: u8, with_params: bool) -> Vec<u8> {
+        let mut op = vec![0x6C, 0x00];
+        op.extend_from_slice(&[0x42; 20]);
+        op.extend_from_slice(&[0x01; 4]);
+        op.extend_from_slice(&[0x80 | amount, 0x07]);
+        //implicit destination
+        op.extend_from_slice(&[0x00, 0x00]);
+        op.extend_from_slice(&[0xAA; 20]);
+        if with_params {
+            //default entrypoint, empty michelson
+            op.extend_from_slice(&[0xFF, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        } else {
+            op.push(0x00);
+        }
+
+        op
+    }
+
+    fn delegation() -> Vec<u8> {
+        let mut op = vec![0x6E, 0x00];
+        op.extend_from_slice(&[0x42; 20]);
+        op.extend_from_slice(&[0x01; 4]);
+        //no delegate
+        op.push(0x00);
+
+        op
+    }
+
+    #[test]
+    fn roundtrip() {
+        let policy = policy();
+
+        let (len, bytes) = policy.to_bytes();
+        assert_eq!(len, 29 + 4 * 4);
+        assert_eq!(Policy::try_from_bytes(&bytes[..len]), Ok(policy));
+
+        let mut unknown_kind = bytes;
+        unknown_kind[2] |= 1 << 7;
+        assert_eq!(
+            Policy::try_from_bytes(&unknown_kind[..len]),
+            Err(Error::DataInvalid)
+        );
+
+        //delegations are never signed without review
+        let mut delegation = bytes;
+        delegation[2] |= 1 << 2;
+        assert_eq!(
+            Policy::try_from_bytes(&delegation[..len]),
+            Err(Error::DataInvalid)
+        );
+
+        let mut long_path = vec![0; 29 + 4 * 6];
+        long_path[..29].copy_from_slice(&bytes[..29]);
+        long_path[28] = 6;
+        assert_eq!(Policy::try_from_bytes(&long_path), Err(Error::WrongLength));
+    }
+
+    #[test]
+    fn check_operations() {
+        let policy = policy();
+        let check = |ops: &[Vec<u8>]| {
+            let input = ops.concat();
+            policy.check(EncodedOperations::new(&input))
+        };
+
+        //each transfer is 7 * 128 + amount mutez, plus 1 mutez of fees
+        //and 1 byte of storage burn
+        let burn = Summary::COST_PER_BYTE;
+        assert_eq!(check(&[transfer(0, false)]), Some(896 + 1 + burn));
+        assert_eq!(
+            check(&[transfer(0, false), transfer(1, false)]),
+            Some(896 + 1 + burn + 897 + 1 + burn)
+        );
+
+        //above the max amount
+        assert_eq!(check(&[transfer(0x7F, false)]), None);
+        //too many transfers
+        assert_eq!(
+            check(&[transfer(0, false), transfer(0, false), transfer(0, false)]),
+            None
+        );
+        //contract call
+        assert_eq!(check(&[transfer(0, true)]), None);
+        //operation not allowed
+        assert_eq!(check(&[transfer(0, false), delegation()]), None);
+        //nothing to sign
+        assert_eq!(check(&[]), None);
+    }
+
+    #[test]
+    #[serial(policy)]
+    fn budget() {
+        let policy = policy();
+        policy.store().unwrap();
+
+        let input = transfer(0, false);
+        let approves = |path: &BIP32Path<BIP32_MAX_LENGTH>| {
+            super::approves(Curve::Ed25519, path, EncodedOperations::new(&input))
+        };
+
+        assert!(approves(&policy.path));
+        assert!(approves(&policy.path));
+        //above the budget
+        assert!(!approves(&policy.path));
+        assert_eq!(Spending::load().spent, 2 * (897 + Summary::COST_PER_BYTE));
+
+        //another key
+        let other = BIP32Path::new([44, 1729, 1, 0].iter().map(|n| 0x8000_0000 + n)).unwrap();
+        assert!(!approves(&other));
+
+        //confirming the policy again resets the budget
+        policy.store().unwrap();
+        assert!(approves(&policy.path));
+
+        Policy::remove().unwrap();
+        assert!(!approves(&policy.path));
+    }
+
+    #[test]
+    #[serial(policy)]
+    fn apdu_set_get() {
+        let (len, policy) = policy().to_bytes();
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        let mut apdu = Vec::with_capacity(5 + len);
+        apdu.extend_from_slice(&[CLA, INS_SET_POLICY, 0, 0, len as u8]);
+        apdu.extend_from_slice(&policy[..len]);
+        buffer[..apdu.len()].copy_from_slice(&apdu);
+
+        handle_apdu(&mut flags, &mut tx, apdu.len() as u32, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        //the mock UI accepts right away
+        let (len_out, out) = get_out().expect("policy confirmed");
+        assert_error_code!(len_out, out, Error::Success);
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_POLICY, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(tx as usize, Spending::SERIALIZED_LEN + len + 2);
+        //confirming resets the spending
+        assert_eq!(&buffer[..Spending::SERIALIZED_LEN], &[0; 8][..]);
+        assert_eq!(
+            &buffer[Spending::SERIALIZED_LEN..tx as usize - 2],
+            &policy[..len]
+        );
+
+        //disable
+        buffer[..5].copy_from_slice(&[CLA, INS_SET_POLICY, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_POLICY, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::ApduCodeEmptyBuffer);
+    }
+}
@@ -230,6 +230,19 @@ impl Addr {
         (len, out)
     }
 
+    /// Renders the address, along with the name of its baker when it's a known one
+    #[cfg(feature = "baking")]
+    pub fn render_named(&self, message: &mut [u8], page: u8) -> Result<u8, ViewError> {
+        use crate::{handlers::handle_ui_named, parser::baker_lookup};
+
+        let (len, mex) = self.base58();
+
+        match baker_lookup(&self.prefix, &self.hash) {
+            Ok(name) => handle_ui_named(name.as_bytes(), &mex[..len], message, page),
+            Err(_) => handle_ui_message(&mex[..len], message, page),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn into_ui(self, pkey: crypto::PublicKey, with_addr: bool) -> AddrUI {
         AddrUI {
@@ -262,13 +275,13 @@ pub struct AddrUI {
 }
 
 impl Viewable for AddrUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         Ok(1)
     }
 
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
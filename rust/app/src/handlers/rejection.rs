@@ -0,0 +1,301 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use core::convert::TryFrom;
+
+use arrayvec::ArrayString;
+use bolos::{pic_str, PIC};
+
+use crate::{
+    constants::ApduError as Error, dispatcher::ApduHandler, handlers::parser_common::ParserError,
+    sys, utils::ApduBufferRead,
+};
+
+#[bolos::lazy_static]
+static mut LAST_ERROR: Option<(Rejection, u16)> = None;
+
+/// Why a request was refused
+///
+/// Shown on the device before returning the error,
+/// so the user isn't left wondering why nothing happened
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Rejection {
+    /// The data couldn't be parsed
+    Parser(ParserError),
+    /// The operation at `index` (starting at byte `offset`) couldn't be parsed
+    Operation {
+        index: u16,
+        offset: u16,
+        error: ParserError,
+    },
+    /// Operation with the given tag is not supported (without blind signing)
+    UnsupportedOperation(u8),
+    /// The data doesn't fit in the buffer
+    PayloadTooLarge,
+    /// The data doesn't start with a known (or allowed) preamble
+    WrongPreemble,
+    /// The curve requested is not supported
+    WrongCurve,
+    /// The derivation path is invalid
+    WrongPath,
+    /// An operation is not meant to be signed by the requested key
+    WrongSource,
+    /// The operations have too many items to be shown
+    TooManyItems,
+    /// The operations are not within the signing session in progress
+    OutsideSession,
+    /// The payload is for a chain that can't be baked
+    UnknownChain,
+    /// The delegation doesn't register the source as delegate
+    NotSelfDelegation,
+    /// The block votes aren't allowed by the vote policy
+    VoteNotAllowed,
+}
+
+impl From<nom::Err<ParserError>> for Rejection {
+    fn from(e: nom::Err<ParserError>) -> Self {
+        Self::Parser(e.into())
+    }
+}
+
+impl From<ParserError> for Rejection {
+    fn from(e: ParserError) -> Self {
+        Self::Parser(e)
+    }
+}
+
+impl Rejection {
+    /// Big enough for any of the messages
+    const MESSAGE_SIZE: usize = 32;
+
+    fn parser_message(e: ParserError) -> &'static str {
+        match e {
+            ParserError::UnexpectedEof => pic_str!("Unexpected end of data"!),
+            ParserError::ValueOutOfRange => pic_str!("Value out of range"!),
+            ParserError::InvalidAddress => pic_str!("Invalid address"!),
+            ParserError::InvalidPublicKey => pic_str!("Invalid public key"!),
+            ParserError::InvalidEntrypoint => pic_str!("Invalid entrypoint"!),
+            ParserError::InvalidPreemble => pic_str!("Wrong preamble"!),
+            ParserError::UnknownOperation => pic_str!("Unknown operation"!),
+            ParserError::InvalidBallotVote => pic_str!("Invalid ballot vote"!),
+            ParserError::InvalidProposalsLength => pic_str!("Invalid proposals"!),
+            ParserError::InvalidEndorsementType => pic_str!("Invalid endorsement"!),
+            ParserError::InvalidProtocolVersion => pic_str!("Invalid protocol"!),
+            ParserError::InvalidPayload | ParserError::Unexpected => pic_str!("Invalid data"!),
+        }
+    }
+
+    fn parser_error(&self) -> Option<ParserError> {
+        match *self {
+            Self::Parser(error) | Self::Operation { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Writes the message describing the rejection
+    pub fn message(&self) -> ArrayString<{ Self::MESSAGE_SIZE }> {
+        let mut out = ArrayString::new();
+
+        //all the messages fit
+        let text = match *self {
+            Self::Parser(e) | Self::Operation { error: e, .. } => Self::parser_message(e),
+            Self::UnsupportedOperation(tag) => {
+                let mut hex_buf = [b'0'; 2];
+                //this is impossible that will error since the sizes are all checked
+                let _ = hex::encode_to_slice([tag], &mut hex_buf);
+                hex_buf.make_ascii_uppercase();
+
+                let _ = out.try_push_str(pic_str!("Unsupported operation 0x"!));
+                let _ = out.try_push_str(core::str::from_utf8(&hex_buf).unwrap_or_default());
+                return out;
+            }
+            Self::PayloadTooLarge => pic_str!("Payload too large"!),
+            Self::WrongPreemble => pic_str!("Wrong preamble"!),
+            Self::WrongCurve => pic_str!("Unsupported curve"!),
+            Self::WrongPath => pic_str!("Invalid path"!),
+            Self::WrongSource => pic_str!("Source is not this key"!),
+            Self::TooManyItems => pic_str!("Too many items to show"!),
+            Self::OutsideSession => pic_str!("Outside of signing session"!),
+            Self::UnknownChain => pic_str!("Chain not allowed"!),
+            Self::NotSelfDelegation => pic_str!("Not a self delegation"!),
+            Self::VoteNotAllowed => pic_str!("Block vote not allowed"!),
+        };
+        let _ = out.try_push_str(text);
+
+        out
+    }
+
+    /// Remembers the rejection for `GET_LAST_ERROR`, returning the error to be sent back
+    ///
+    /// Parsing failures are sent back with their own status word,
+    /// otherwise `error` is used
+    #[inline(never)]
+    pub fn record(self, error: Error) -> Error {
+        let error = self.parser_error().map(Error::from).unwrap_or(error);
+
+        let status = error as u16;
+        unsafe {
+            LAST_ERROR.replace((self, status));
+        }
+
+        //status was taken from an error so it's always valid
+        Error::try_from(status).unwrap_or(Error::ExecutionError)
+    }
+
+    /// Shows the rejection on the device and records it (see `record`)
+    #[inline(never)]
+    pub fn reject(self, error: Error) -> Error {
+        sys::show_message(pic_str!("Rejected"!), &self.message());
+
+        self.record(error)
+    }
+
+    /// Serializes the rejection and its status word,
+    /// as returned by `GET_LAST_ERROR`
+    ///
+    /// Fields that don't apply to the rejection are all 1s
+    fn to_bytes(self, status: u16) -> [u8; GetLastError::RESPONSE_LEN] {
+        let (error, index, offset) = match self {
+            Self::Parser(error) => (error as u8, u16::MAX, u16::MAX),
+            Self::Operation {
+                index,
+                offset,
+                error,
+            } => (error as u8, index, offset),
+            _ => (u8::MAX, u16::MAX, u16::MAX),
+        };
+
+        let mut out = [0; GetLastError::RESPONSE_LEN];
+        out[..2].copy_from_slice(&status.to_be_bytes());
+        out[2] = error;
+        out[3..5].copy_from_slice(&index.to_be_bytes());
+        out[5..].copy_from_slice(&offset.to_be_bytes());
+        out
+    }
+}
+
+pub struct GetLastError;
+
+impl GetLastError {
+    /// status word, parsing error, operation index and offset
+    pub const RESPONSE_LEN: usize = 2 + 1 + 2 + 2;
+}
+
+impl ApduHandler for GetLastError {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("GetLastError::handle\x00");
+
+        *tx = 0;
+
+        //nothing was rejected yet
+        let (rejection, status) = unsafe { *LAST_ERROR }.ok_or(Error::ApduCodeEmptyBuffer)?;
+        let out = rejection.to_bytes(status);
+
+        let buffer = buffer.write();
+        buffer[..out.len()].copy_from_slice(&out[..]);
+        *tx = out.len() as u32;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            Rejection::UnsupportedOperation(0x9E).message().as_str(),
+            "Unsupported operation 0x9E"
+        );
+
+        let parse_error: nom::Err<ParserError> = ParserError::InvalidBallotVote.into();
+        assert_eq!(
+            Rejection::from(parse_error).message().as_str(),
+            "Invalid ballot vote"
+        );
+        assert_eq!(
+            Rejection::from(ParserError::Unexpected).message().as_str(),
+            "Invalid data"
+        );
+    }
+
+    #[test]
+    fn reject_shows_message() {
+        assert_eq!(
+            Rejection::PayloadTooLarge.reject(Error::DataInvalid),
+            Error::DataInvalid
+        );
+
+        //other tests might have shown their own rejection in the meantime
+        let (title, _) = sys::get_message().expect("message shown");
+        assert_eq!(title.as_str(), "Rejected");
+    }
+
+    #[test]
+    fn parser_status_word() {
+        let rejection = Rejection::Operation {
+            index: 2,
+            offset: 0x1234,
+            error: ParserError::InvalidBallotVote,
+        };
+
+        assert_eq!(
+            rejection.record(Error::DataInvalid),
+            Error::ParserInvalidBallotVote
+        );
+        assert_eq!(
+            rejection.to_bytes(Error::ParserInvalidBallotVote as u16),
+            [0xB0, 0x08, 0x08, 0x00, 0x02, 0x12, 0x34]
+        );
+
+        assert_eq!(
+            Rejection::WrongSource.to_bytes(Error::DataInvalid as u16),
+            [0x69, 0x84, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]
+        );
+    }
+
+    #[test]
+    fn apdu_last_error() {
+        use crate::{
+            assert_error_code,
+            dispatcher::{handle_apdu, CLA, INS_GET_LAST_ERROR},
+        };
+
+        //other tests might record their own rejection in the meantime,
+        //so the content is checked in `parser_status_word` instead
+        Rejection::Parser(ParserError::UnexpectedEof).record(Error::DataInvalid);
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_LAST_ERROR, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        assert_eq!(tx as usize, GetLastError::RESPONSE_LEN + 2);
+        let status: Result<Error, _> = (&buffer[..2]).try_into();
+        assert!(status.is_ok());
+    }
+}
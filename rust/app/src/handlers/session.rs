@@ -0,0 +1,518 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use bolos::{pic_str, PIC};
+use zemu_sys::{Show, ViewError, Viewable};
+
+use crate::{
+    constants::ApduError as Error,
+    crypto::Curve,
+    dispatcher::ApduHandler,
+    handlers::{
+        handle_ui_message, intstr_to_fpstr_inplace, policy::for_each_spend, public_key::Addr,
+        settings::Settings,
+    },
+    parser::{operations::EncodedOperations, public_key_hash},
+    sys,
+    utils::ApduBufferRead,
+};
+
+/// Kept in RAM only, so the session ends when the app is closed
+#[bolos::lazy_static]
+static mut SESSION: Option<Session> = None;
+
+/// Maximum number of sources allowed in a session
+pub const MAX_SOURCES: usize = 4;
+
+/// The session ends after this many ticks (of 100ms) without signing
+const TIMEOUT_TICKS: u16 = 5 * 60 * 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+/// Limits of a signing session, as reviewed by the user
+///
+/// # Codec
+///
+/// [0] = number of operation groups that can be signed
+///
+/// [1..9] = total spent by all the groups in mutez (big endian),
+/// counting amounts, fees and storage burn
+///
+/// [9..17] = max amount per transfer in mutez (big endian)
+///
+/// [17..25] = max fee per operation in mutez (big endian)
+///
+/// [25..33] = total fees of all the groups in mutez (big endian)
+///
+/// [33] = number of sources, at most `MAX_SOURCES`
+///
+/// [34..] = sources, encoded as public key hashes (tag and hash)
+pub struct Session {
+    groups: u8,
+    amount: u64,
+    max_amount: u64,
+    max_fee: u64,
+    fees: u64,
+    sources: [(Curve, [u8; 20]); MAX_SOURCES],
+    n_sources: u8,
+    ticks: u16,
+}
+
+impl Session {
+    /// Attempt to read the session limits from some bytes
+    pub fn try_from_bytes(from: &[u8]) -> Result<Self, Error> {
+        if from.len() < 34 {
+            return Err(Error::WrongLength);
+        }
+
+        let n_sources = from[33];
+        if from.len() != 34 + 21 * n_sources as usize {
+            return Err(Error::WrongLength);
+        }
+        if from[0] == 0 || n_sources == 0 || n_sources as usize > MAX_SOURCES {
+            return Err(Error::DataInvalid);
+        }
+
+        let mut sources = [(Curve::Bip32Ed25519, [0; 20]); MAX_SOURCES];
+        for (source, encoded) in sources.iter_mut().zip(from[34..].chunks_exact(21)) {
+            let (_, (crv, hash)) = public_key_hash(encoded).map_err(|_| Error::DataInvalid)?;
+            *source = (crv, *hash);
+        }
+
+        Ok(Self {
+            groups: from[0],
+            amount: u64::from_be_bytes(*arrayref::array_ref!(from, 1, 8)),
+            max_amount: u64::from_be_bytes(*arrayref::array_ref!(from, 9, 8)),
+            max_fee: u64::from_be_bytes(*arrayref::array_ref!(from, 17, 8)),
+            fees: u64::from_be_bytes(*arrayref::array_ref!(from, 25, 8)),
+            sources,
+            n_sources,
+            ticks: TIMEOUT_TICKS,
+        })
+    }
+
+    fn sources(&self) -> &[(Curve, [u8; 20])] {
+        &self.sources[..self.n_sources as usize]
+    }
+
+    /// Checks if the operations in `ops` signed by `signer` are within the session,
+    /// returning the total spent (amounts, fees and burn) and the fees alone
+    ///
+    /// The sources of the operations are expected to be verified already
+    fn check(
+        &self,
+        (curve, signer): (Curve, &[u8; 20]),
+        ops: EncodedOperations<'_>,
+    ) -> Option<(u64, u64)> {
+        //tz1 addresses can come from either ed25519 curve
+        let allowed = self
+            .sources()
+            .iter()
+            .any(|(crv, pkh)| crv.to_hash_prefix() == curve.to_hash_prefix() && pkh == signer);
+        if !allowed {
+            return None;
+        }
+
+        let mut total = 0u64;
+        let mut fees = 0u64;
+        for_each_spend(ops, self.max_amount, self.max_fee, |spend| {
+            fees = fees.checked_add(spend.fee)?;
+            total = total.checked_add(spend.total()?)?;
+            Some(())
+        })?;
+
+        if total > self.amount || fees > self.fees {
+            return None;
+        }
+
+        Some((total, fees))
+    }
+}
+
+/// Returns true if a signing session is in progress
+pub fn active() -> bool {
+    unsafe { SESSION.is_some() }
+}
+
+/// Checks if the operations in `ops` signed by `signer` are within the session
+///
+/// When they are, the group, its total and its fees are deducted from the session
+#[inline(never)]
+pub fn approves(signer: (Curve, &[u8; 20]), ops: EncodedOperations<'_>) -> bool {
+    sys::zemu_log_stack("session::approves\x00");
+
+    let session = match unsafe { SESSION.as_mut() } {
+        Some(session) => session,
+        None => return false,
+    };
+
+    let (total, fees) = match session.check(signer, ops) {
+        Some(spent) => spent,
+        None => return false,
+    };
+
+    session.amount -= total;
+    session.fees -= fees;
+    session.groups -= 1;
+    session.ticks = TIMEOUT_TICKS;
+
+    if session.groups == 0 {
+        end();
+    }
+
+    true
+}
+
+/// Ends the session in progress, if any
+pub fn end() {
+    unsafe {
+        SESSION.take();
+    }
+}
+
+/// Advances the session timeout by a tick, ending the session when it expires
+fn tick() {
+    let expired = match unsafe { SESSION.as_mut() } {
+        Some(session) => {
+            session.ticks = session.ticks.saturating_sub(1);
+            session.ticks == 0
+        }
+        None => false,
+    };
+
+    if expired {
+        end();
+    }
+}
+
+pub struct StartSession;
+
+impl ApduHandler for StartSession {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("StartSession::handle\x00");
+
+        *tx = 0;
+
+        let payload = buffer.payload().map_err(|_| Error::DataInvalid)?;
+        let ui = SessionUI {
+            session: Session::try_from_bytes(payload)?,
+        };
+
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+pub struct EndSession;
+
+impl ApduHandler for EndSession {
+    #[inline(never)]
+    fn handle<'apdu>(_: &mut u32, tx: &mut u32, _: ApduBufferRead<'apdu>) -> Result<(), Error> {
+        sys::zemu_log_stack("EndSession::handle\x00");
+
+        *tx = 0;
+        end();
+
+        Ok(())
+    }
+}
+
+struct SessionUI {
+    session: Session,
+}
+
+impl SessionUI {
+    /// Renders an amount in mutez as tez
+    fn render_amount(amount: u64, message: &mut [u8], page: u8) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        //+2 for decimal formatting
+        let mut amount_buf = [0; u64::FORMATTED_SIZE_DECIMAL + 2];
+        itoa(amount, &mut amount_buf);
+
+        handle_ui_message(
+            intstr_to_fpstr_inplace(&mut amount_buf, Settings::load().unit().decimals())
+                .map_err(|_| ViewError::Unknown)?,
+            message,
+            page,
+        )
+    }
+}
+
+impl Viewable for SessionUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        Ok(6 + self.session.n_sources as u16)
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        match item_n {
+            0 => {
+                let title_content = pic_str!(b"Type");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(&pic_str!(b"Signing session")[..], message, page)
+            }
+            1 => {
+                let title_content = pic_str!(b"Groups");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut itoa_buf = [0; u8::FORMATTED_SIZE_DECIMAL];
+                handle_ui_message(itoa(self.session.groups, &mut itoa_buf), message, page)
+            }
+            2 => {
+                let title_content = pic_str!(b"Total spent");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(self.session.amount, message, page)
+            }
+            3 => {
+                let title_content = pic_str!(b"Max amount");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(self.session.max_amount, message, page)
+            }
+            4 => {
+                let title_content = pic_str!(b"Max fee");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(self.session.max_fee, message, page)
+            }
+            5 => {
+                let title_content = pic_str!(b"Total fees");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                Self::render_amount(self.session.fees, message, page)
+            }
+            n => {
+                let (crv, hash) = self
+                    .session
+                    .sources()
+                    .get(n as usize - 6)
+                    .ok_or(ViewError::NoData)?;
+
+                let title_content = pic_str!(b"Source");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let addr = Addr::from_hash(hash, *crv).map_err(|_| ViewError::Unknown)?;
+                let (len, mex) = addr.base58();
+                handle_ui_message(&mex[..len], message, page)
+            }
+        }
+    }
+
+    fn accept(&mut self, _: &mut [u8]) -> (usize, u16) {
+        unsafe {
+            SESSION.replace(self.session);
+        }
+
+        (0, Error::Success as _)
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
+
+mod cabi {
+    use super::*;
+
+    /// Advances the session timeout
+    ///
+    /// # Safety
+    ///
+    /// Meant to be called only by the C stub on every ticker event
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_session_tick() {
+        tick();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        dispatcher::{handle_apdu, CLA, INS_END_SESSION, INS_START_SESSION},
+        sys::get_out,
+    };
+    use std::{convert::TryInto, vec, vec::Vec};
+
+    use serial_test::serial;
+
+    const SIGNER: [u8; 20] = [0x42; 20];
+
+    fn limits(groups: u8) -> Vec<u8> {
+        let mut limits = vec![groups];
+        limits.extend_from_slice(&2_000u64.to_be_bytes());
+        limits.extend_from_slice(&1_000u64.to_be_bytes());
+        //max fee and total fees
+        limits.extend_from_slice(&10u64.to_be_bytes());
+        limits.extend_from_slice(&3u64.to_be_bytes());
+        //tz1 and tz2 sources
+        limits.push(2);
+        limits.push(0x00);
+        limits.extend_from_slice(&SIGNER);
+        limits.push(0x01);
+        limits.extend_from_slice(&[0x43; 20]);
+
+        limits
+    }
+
+    //counter and gas_limit are 1
+    fn transfer_with(fee: u8, storage_limit: u8, amount: u8) -> Vec<u8> {
+        let mut op = vec![0x6C, 0x00];
+        op.extend_from_slice(&SIGNER);
+        op.extend_from_slice(&[fee, 0x01, 0x01, storage_limit]);
+        op.extend_from_slice(&[0x80 | amount, 0x07]);
+        //implicit destination
+        op.extend_from_slice(&[0x00, 0x00]);
+        op.extend_from_slice(&[0xAA; 20]);
+        //no parameters
+        op.push(0x00);
+
+        op
+    }
+
+    //fee is 1, without storage
+    fn transfer(amount: u8) -> Vec<u8> {
+        transfer_with(1, 0, amount)
+    }
+
+    #[test]
+    fn limits_bytes() {
+        let session = Session::try_from_bytes(&limits(3)).unwrap();
+        assert_eq!(session.groups, 3);
+        assert_eq!(session.amount, 2_000);
+        assert_eq!(session.max_amount, 1_000);
+        assert_eq!(session.max_fee, 10);
+        assert_eq!(session.fees, 3);
+        assert_eq!(
+            session.sources(),
+            &[
+                (Curve::Bip32Ed25519, SIGNER),
+                (Curve::Secp256K1, [0x43; 20])
+            ]
+        );
+
+        assert_eq!(Session::try_from_bytes(&limits(0)), Err(Error::DataInvalid));
+
+        let mut truncated = limits(1);
+        truncated.pop();
+        assert_eq!(Session::try_from_bytes(&truncated), Err(Error::WrongLength));
+    }
+
+    #[test]
+    fn check_operations() {
+        let session = Session::try_from_bytes(&limits(3)).unwrap();
+        let check = |signer: (Curve, &[u8; 20]), ops: &[Vec<u8>]| {
+            let input = ops.concat();
+            session.check(signer, EncodedOperations::new(&input))
+        };
+
+        //each transfer is 7 * 128 + amount mutez, plus 1 mutez of fee
+        let tz1 = (Curve::Ed25519, &SIGNER);
+        assert_eq!(check(tz1, &[transfer(0)]), Some((897, 1)));
+        assert_eq!(
+            check(tz1, &[transfer(0), transfer(1)]),
+            Some((897 + 898, 2))
+        );
+        //storage burn counts towards the total
+        assert_eq!(check(tz1, &[transfer_with(1, 2, 0)]), Some((897 + 500, 1)));
+
+        //above the max fee
+        assert_eq!(check(tz1, &[transfer_with(11, 0, 0)]), None);
+        //above the total fees
+        assert_eq!(
+            check(tz1, &[transfer_with(2, 0, 0), transfer_with(2, 0, 0)]),
+            None
+        );
+        //the burn puts it above the total
+        assert_eq!(check(tz1, &[transfer_with(1, 5, 0)]), None);
+
+        //above the max amount
+        assert_eq!(check(tz1, &[transfer(0x7F)]), None);
+        //above the total amount
+        assert_eq!(check(tz1, &[transfer(0), transfer(0), transfer(0)]), None);
+        //source not in the session
+        assert_eq!(check((Curve::Secp256R1, &SIGNER), &[transfer(0)]), None);
+        //nothing to sign
+        assert_eq!(check(tz1, &[]), None);
+    }
+
+    #[test]
+    #[serial(session)]
+    fn groups_and_timeout() {
+        let input = transfer(0);
+        let approves =
+            || super::approves((Curve::Ed25519, &SIGNER), EncodedOperations::new(&input));
+
+        unsafe { SESSION.replace(Session::try_from_bytes(&limits(3)).unwrap()) };
+        assert!(approves());
+        assert!(approves());
+        //total amount used up
+        assert!(!approves());
+        assert!(active());
+
+        unsafe { SESSION.replace(Session::try_from_bytes(&limits(1)).unwrap()) };
+        assert!(approves());
+        //no groups left
+        assert!(!active());
+
+        unsafe { SESSION.replace(Session::try_from_bytes(&limits(1)).unwrap()) };
+        (0..TIMEOUT_TICKS - 1).for_each(|_| tick());
+        assert!(active());
+        tick();
+        assert!(!active());
+        assert!(!approves());
+    }
+
+    #[test]
+    #[serial(session)]
+    fn apdu_start_end() {
+        let limits = limits(2);
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        buffer[..5].copy_from_slice(&[CLA, INS_START_SESSION, 0, 0, limits.len() as u8]);
+        buffer[5..5 + limits.len()].copy_from_slice(&limits);
+        handle_apdu(&mut flags, &mut tx, 5 + limits.len() as u32, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        //the mock UI accepts right away
+        let (len, out) = get_out().expect("session confirmed");
+        assert_error_code!(len, out, Error::Success);
+        assert!(active());
+
+        buffer[..5].copy_from_slice(&[CLA, INS_END_SESSION, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert!(!active());
+    }
+}
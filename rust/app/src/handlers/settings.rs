@@ -0,0 +1,579 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use core::convert::TryFrom;
+
+use bolos::{pic_str, PIC};
+use zemu_sys::{Show, ViewError, Viewable};
+
+use crate::{
+    constants::ApduError as Error,
+    dispatcher::ApduHandler,
+    handlers::{handle_ui_message, intstr_to_fpstr_inplace},
+    sys::{self, flash_slot::Wear, new_flash_slot},
+    utils::{ApduBufferRead, ApduPanic},
+};
+
+const N_PAGES: usize = 1;
+
+type WearLeveller = Wear<'static, N_PAGES>;
+
+#[bolos::lazy_static]
+static mut SETTINGS: WearLeveller = new_flash_slot!(N_PAGES).apdu_expect("NVM might be corrupted");
+
+/// Unit used to display amounts
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[repr(u8)]
+pub enum AmountUnit {
+    Tez = 0,
+    Mutez = 1,
+}
+
+impl AmountUnit {
+    /// Decimals to show an amount in mutez with
+    pub fn decimals(self) -> usize {
+        match self {
+            Self::Tez => 6,
+            Self::Mutez => 0,
+        }
+    }
+
+    fn toggle(self) -> Self {
+        match self {
+            Self::Tez => Self::Mutez,
+            Self::Mutez => Self::Tez,
+        }
+    }
+}
+
+impl TryFrom<u8> for AmountUnit {
+    type Error = ();
+
+    fn try_from(from: u8) -> Result<Self, ()> {
+        match from {
+            0 => Ok(Self::Tez),
+            1 => Ok(Self::Mutez),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, property::Property)]
+#[cfg_attr(test, derive(Debug))]
+#[property(mut(disable), get(public), set(disable))]
+/// User settings, persisted in NVM
+///
+/// # Codec
+///
+/// [0] = version; records of previous versions are migrated,
+/// an unknown version is read as the default settings
+///
+/// [1] = flags; bit 0 is expert mode, bit 1 is blind signing
+///
+/// [2] = `AmountUnit`
+///
+/// [3..11] = fee cap in mutez (big endian); 0 for no cap
+///
+/// [11] = fee ratio; 0 to disable the check
+pub struct Settings {
+    expert: bool,
+    blind_signing: bool,
+    unit: AmountUnit,
+    /// Fees above this amount (in mutez) are flagged
+    fee_cap: u64,
+    /// Fees above this many times the minimal fee are flagged
+    fee_ratio: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            expert: false,
+            blind_signing: false,
+            unit: AmountUnit::Tez,
+            fee_cap: Self::DEFAULT_FEE_CAP,
+            fee_ratio: Self::DEFAULT_FEE_RATIO,
+        }
+    }
+}
+
+impl Settings {
+    pub const VERSION: u8 = 3;
+    pub const SERIALIZED_LEN: usize = 12;
+
+    /// 1 tez
+    pub const DEFAULT_FEE_CAP: u64 = 1_000_000;
+    pub const DEFAULT_FEE_RATIO: u8 = 10;
+
+    const FLAG_EXPERT: u8 = 1 << 0;
+    const FLAG_BLIND_SIGNING: u8 = 1 << 1;
+
+    /// Reads the settings from NVM
+    ///
+    /// If the settings were never written the defaults are returned
+    pub fn load() -> Self {
+        match unsafe { SETTINGS.read() } {
+            Ok(data) => data.into(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// Writes the settings to NVM
+    pub fn store(self) -> Result<(), Error> {
+        unsafe { SETTINGS.write(self.into()) }.map_err(|_| Error::ExecutionError)
+    }
+
+    /// Updates the UI with the stored settings
+    pub fn sync_ui(&self) {
+        zemu_sys::set_expert(self.expert);
+    }
+
+    pub fn toggle_expert(&mut self) {
+        self.expert = !self.expert;
+    }
+
+    pub fn toggle_blind_signing(&mut self) {
+        self.blind_signing = !self.blind_signing;
+    }
+
+    pub fn toggle_unit(&mut self) {
+        self.unit = self.unit.toggle();
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SERIALIZED_LEN] {
+        let mut flags = 0;
+        if self.expert {
+            flags |= Self::FLAG_EXPERT;
+        }
+        if self.blind_signing {
+            flags |= Self::FLAG_BLIND_SIGNING;
+        }
+
+        let mut out = [0; Self::SERIALIZED_LEN];
+        out[..3].copy_from_slice(&[Self::VERSION, flags, self.unit as u8]);
+        out[3..11].copy_from_slice(&self.fee_cap.to_be_bytes());
+        out[11] = self.fee_ratio;
+
+        out
+    }
+
+    /// Converts a record of a previous version to the current layout
+    ///
+    /// Versions 1 and 2 also had toggle votes for baking at [3..5], which were never enforced
+    /// and are replaced by the vote policy of the baking app, so they are dropped
+    fn migrate(from: &[u8; 52]) -> [u8; Self::SERIALIZED_LEN] {
+        let mut out = [0; Self::SERIALIZED_LEN];
+        out[..3].copy_from_slice(&[Self::VERSION, from[1], from[2]]);
+
+        match from[0] {
+            //no fee checks yet
+            1 => {
+                out[3..11].copy_from_slice(&Self::DEFAULT_FEE_CAP.to_be_bytes());
+                out[11] = Self::DEFAULT_FEE_RATIO;
+            }
+            //fee cap and ratio after the toggle votes
+            2 => out[3..].copy_from_slice(&from[5..14]),
+            _ => out.copy_from_slice(&from[..Self::SERIALIZED_LEN]),
+        }
+
+        out
+    }
+
+    /// Attempt to read the settings from some bytes
+    ///
+    /// Unlike reading from NVM, an unknown version or value is an error
+    pub fn try_from_bytes(from: &[u8]) -> Result<Self, Error> {
+        if from.len() != Self::SERIALIZED_LEN {
+            return Err(Error::WrongLength);
+        }
+
+        if from[0] != Self::VERSION {
+            return Err(Error::DataInvalid);
+        }
+
+        let flags = from[1];
+        if flags & !(Self::FLAG_EXPERT | Self::FLAG_BLIND_SIGNING) != 0 {
+            return Err(Error::DataInvalid);
+        }
+
+        Ok(Self {
+            expert: flags & Self::FLAG_EXPERT != 0,
+            blind_signing: flags & Self::FLAG_BLIND_SIGNING != 0,
+            unit: AmountUnit::try_from(from[2]).map_err(|_| Error::DataInvalid)?,
+            fee_cap: u64::from_be_bytes(*arrayref::array_ref!(from, 3, 8)),
+            fee_ratio: from[11],
+        })
+    }
+}
+
+impl From<&[u8; 52]> for Settings {
+    fn from(from: &[u8; 52]) -> Self {
+        //if the serialization is invalid just return a default
+        Self::try_from_bytes(&Self::migrate(from)).unwrap_or_default()
+    }
+}
+
+impl From<Settings> for [u8; 52] {
+    fn from(from: Settings) -> Self {
+        let mut out = [0; 52];
+        out[..Settings::SERIALIZED_LEN].copy_from_slice(&from.to_bytes());
+
+        out
+    }
+}
+
+pub struct GetSettings;
+
+impl ApduHandler for GetSettings {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("GetSettings::handle\x00");
+
+        *tx = 0;
+
+        let settings = Settings::load().to_bytes();
+
+        let buffer = buffer.write();
+        buffer[..settings.len()].copy_from_slice(&settings[..]);
+        *tx = settings.len() as u32;
+
+        Ok(())
+    }
+}
+
+pub struct SetSettings;
+
+impl ApduHandler for SetSettings {
+    #[inline(never)]
+    fn handle<'apdu>(
+        flags: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("SetSettings::handle\x00");
+
+        *tx = 0;
+
+        let payload = buffer.payload().map_err(|_| Error::DataInvalid)?;
+        let ui = SettingsUI {
+            settings: Settings::try_from_bytes(payload)?,
+        };
+
+        unsafe { ui.show(flags) }.map_err(|_| Error::ExecutionError)
+    }
+}
+
+struct SettingsUI {
+    settings: Settings,
+}
+
+impl Viewable for SettingsUI {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
+        Ok(5)
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use lexical_core::{write as itoa, Number};
+
+        let enabled = |flag: bool| {
+            if flag {
+                &pic_str!(b"enabled")[..]
+            } else {
+                &pic_str!(b"disabled")[..]
+            }
+        };
+
+        let (title_content, message_content) = match item_n {
+            0 => (&pic_str!(b"Expert mode")[..], enabled(self.settings.expert)),
+            1 => (
+                &pic_str!(b"Blind signing")[..],
+                enabled(self.settings.blind_signing),
+            ),
+            2 => (
+                &pic_str!(b"Amount unit")[..],
+                match self.settings.unit {
+                    AmountUnit::Tez => &pic_str!(b"tez")[..],
+                    AmountUnit::Mutez => &pic_str!(b"mutez")[..],
+                },
+            ),
+            3 => {
+                let title_content = pic_str!(b"Fee cap");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                if self.settings.fee_cap == 0 {
+                    return handle_ui_message(&pic_str!(b"none")[..], message, page);
+                }
+
+                //+2 for decimal formatting
+                let mut fee_buf = [0; u64::FORMATTED_SIZE_DECIMAL + 2];
+                itoa(self.settings.fee_cap, &mut fee_buf);
+                return handle_ui_message(
+                    intstr_to_fpstr_inplace(&mut fee_buf, self.settings.unit.decimals())
+                        .map_err(|_| ViewError::Unknown)?,
+                    message,
+                    page,
+                );
+            }
+            4 => {
+                let title_content = pic_str!(b"Fee ratio");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                if self.settings.fee_ratio == 0 {
+                    return handle_ui_message(enabled(false), message, page);
+                }
+
+                let mut ratio_buf = [0; u8::FORMATTED_SIZE_DECIMAL];
+                let ratio = itoa(self.settings.fee_ratio, &mut ratio_buf);
+                return handle_ui_message(ratio, message, page);
+            }
+            _ => return Err(ViewError::NoData),
+        };
+
+        title[..title_content.len()].copy_from_slice(title_content);
+        handle_ui_message(message_content, message, page)
+    }
+
+    fn accept(&mut self, out: &mut [u8]) -> (usize, u16) {
+        if let Err(e) = self.settings.store() {
+            return (0, e as _);
+        }
+        self.settings.sync_ui();
+
+        let settings = self.settings.to_bytes();
+        out[..settings.len()].copy_from_slice(&settings[..]);
+
+        (settings.len(), Error::Success as _)
+    }
+
+    fn reject(&mut self, _: &mut [u8]) -> (usize, u16) {
+        (0, Error::CommandNotAllowed as _)
+    }
+}
+
+mod cabi {
+    use super::*;
+
+    /// Loads the stored settings in the UI
+    ///
+    /// # Safety
+    ///
+    /// Meant to be called once by the C stub when the app starts
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_settings_init() {
+        Settings::load().sync_ui();
+    }
+
+    /// Handler of the "Expert mode" menu item
+    ///
+    /// # Safety
+    ///
+    /// Meant to be called only by the C UI
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_settings_toggle_expert() {
+        let mut settings = Settings::load();
+        settings.toggle_expert();
+
+        //only update the UI if the change was persisted
+        if settings.store().is_ok() {
+            zemu_sys::toggle_expert();
+        }
+    }
+
+    /// Handler of the "Blind signing" menu item
+    ///
+    /// # Safety
+    ///
+    /// Meant to be called only by the C UI
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_settings_toggle_blind_signing() {
+        let mut settings = Settings::load();
+        settings.toggle_blind_signing();
+
+        let _ = settings.store();
+    }
+
+    /// # Safety
+    ///
+    /// Meant to be called only by the C UI
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_settings_blind_signing() -> bool {
+        Settings::load().blind_signing
+    }
+
+    /// Handler of the "Amount unit" menu item
+    ///
+    /// # Safety
+    ///
+    /// Meant to be called only by the C UI
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_settings_toggle_unit() {
+        let mut settings = Settings::load();
+        settings.toggle_unit();
+
+        let _ = settings.store();
+    }
+
+    /// Returns true if amounts are shown in mutez
+    ///
+    /// # Safety
+    ///
+    /// Meant to be called only by the C UI
+    #[no_mangle]
+    pub unsafe extern "C" fn rs_settings_unit_mutez() -> bool {
+        Settings::load().unit == AmountUnit::Mutez
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        dispatcher::{handle_apdu, CLA, INS_GET_SETTINGS, INS_SET_SETTINGS},
+        sys::get_out,
+    };
+    use std::{convert::TryInto, vec::Vec};
+
+    use serial_test::serial;
+
+    #[test]
+    fn roundtrip() {
+        let settings = Settings {
+            expert: true,
+            blind_signing: false,
+            unit: AmountUnit::Mutez,
+            fee_cap: 0x0102_0304_0506_0708,
+            fee_ratio: 0,
+        };
+
+        let slot: [u8; 52] = settings.into();
+        assert_eq!(Settings::from(&slot), settings);
+        assert_eq!(Settings::try_from_bytes(&settings.to_bytes()), Ok(settings));
+    }
+
+    #[test]
+    fn unit_decimals() {
+        use crate::handlers::utils::intstr_to_fpstr_inplace;
+
+        let mut buf = *b"1234567\0\0\0\0\0\0\0\0\0";
+        let tez = intstr_to_fpstr_inplace(&mut buf, AmountUnit::Tez.decimals()).unwrap();
+        assert_eq!(tez, b"1.234567");
+
+        let mut buf = *b"1234567\0\0\0\0\0\0\0\0\0";
+        let mutez = intstr_to_fpstr_inplace(&mut buf, AmountUnit::Mutez.decimals()).unwrap();
+        assert_eq!(mutez, b"1234567");
+    }
+
+    #[test]
+    fn unknown_version_is_default() {
+        let mut slot: [u8; 52] = Settings::default().into();
+        slot[0] = Settings::VERSION + 1;
+        slot[1] = Settings::FLAG_EXPERT;
+
+        assert_eq!(Settings::from(&slot), Settings::default());
+
+        let uninit = [0; 52];
+        assert_eq!(Settings::from(&uninit), Settings::default());
+    }
+
+    #[test]
+    fn migrate_previous_versions() {
+        //expert mode, mutez and the toggle votes
+        let mut v1 = [0; 52];
+        v1[..5].copy_from_slice(&[1, Settings::FLAG_EXPERT, AmountUnit::Mutez as u8, 1, 2]);
+
+        let expected = Settings {
+            expert: true,
+            unit: AmountUnit::Mutez,
+            ..Settings::default()
+        };
+        assert_eq!(Settings::from(&v1), expected);
+
+        //as version 1, then a fee cap of 2 tez and a fee ratio of 3
+        let mut v2 = v1;
+        v2[0] = 2;
+        v2[5..13].copy_from_slice(&2_000_000u64.to_be_bytes());
+        v2[13] = 3;
+
+        let expected = Settings {
+            fee_cap: 2_000_000,
+            fee_ratio: 3,
+            ..expected
+        };
+        assert_eq!(Settings::from(&v2), expected);
+    }
+
+    #[test]
+    fn invalid_bytes() {
+        let mut bytes = Settings::default().to_bytes();
+        bytes[2] = 0xFF;
+        assert_eq!(Settings::try_from_bytes(&bytes), Err(Error::DataInvalid));
+
+        let bytes = Settings::default().to_bytes();
+        assert_eq!(
+            Settings::try_from_bytes(&bytes[..2]),
+            Err(Error::WrongLength)
+        );
+    }
+
+    #[test]
+    #[serial(settings)]
+    fn apdu_set_get() {
+        let settings = Settings {
+            expert: true,
+            blind_signing: true,
+            unit: AmountUnit::Tez,
+            fee_cap: 5_000_000,
+            fee_ratio: 3,
+        }
+        .to_bytes();
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        let mut apdu = Vec::with_capacity(5 + settings.len());
+        apdu.extend_from_slice(&[CLA, INS_SET_SETTINGS, 0, 0, settings.len() as u8]);
+        apdu.extend_from_slice(&settings);
+        buffer[..apdu.len()].copy_from_slice(&apdu);
+
+        handle_apdu(&mut flags, &mut tx, apdu.len() as u32, &mut buffer);
+        assert_eq!(tx, 2);
+        assert_error_code!(tx, buffer, Error::Success);
+
+        //the mock UI accepts right away
+        let (len, out) = get_out().expect("settings confirmed");
+        assert_eq!(&out[..len - 2], &settings[..]);
+        assert!(zemu_sys::expert());
+
+        buffer[..5].copy_from_slice(&[CLA, INS_GET_SETTINGS, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_eq!(tx as usize, settings.len() + 2);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(&buffer[..settings.len()], &settings[..]);
+    }
+}
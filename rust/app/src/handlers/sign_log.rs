@@ -0,0 +1,298 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use bolos::PIC;
+
+use crate::{
+    constants::ApduError as Error,
+    dispatcher::ApduHandler,
+    sys::{self, flash_slot::Wear, new_flash_slot},
+    utils::{ApduBufferRead, ApduPanic},
+};
+
+/// Number of signatures kept in the log
+///
+/// A tenderbake baker signs at least a preendorsement and an endorsement every level,
+/// so this covers the last 60 levels or so
+pub const MAX_RECORDS: usize = 128;
+
+/// Number of records sent in each page of `QUERY_SIGN_LOG`
+pub const RECORDS_PER_PAGE: usize = 4;
+
+//the log is written on every signature, like the watermark,
+// so the records are spread on a wear levelled ring and each slot
+// is written once every `MAX_RECORDS`
+#[bolos::lazy_static]
+static mut LOG: Wear<'static, MAX_RECORDS> =
+    new_flash_slot!(MAX_RECORDS).apdu_expect("NVM might be corrupted");
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+pub enum Kind {
+    Block = 1,
+    Preendorsement = 2,
+    Endorsement = 3,
+    Delegation = 4,
+    Reveal = 5,
+    Ballot = 6,
+    Proposals = 7,
+    UpdateConsensusKey = 8,
+    DrainDelegate = 9,
+    SetDepositsLimit = 10,
+    SetDelegateParameters = 11,
+}
+
+impl Kind {
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(Self::Block),
+            2 => Some(Self::Preendorsement),
+            3 => Some(Self::Endorsement),
+            4 => Some(Self::Delegation),
+            5 => Some(Self::Reveal),
+            6 => Some(Self::Ballot),
+            7 => Some(Self::Proposals),
+            8 => Some(Self::UpdateConsensusKey),
+            9 => Some(Self::DrainDelegate),
+            10 => Some(Self::SetDepositsLimit),
+            11 => Some(Self::SetDelegateParameters),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+/// Signature made by the device, as kept in the log
+///
+/// # Codec
+///
+/// [0] = `Kind`
+///
+/// [1..5] = chain id, big endian; 0 for operations
+///
+/// [5..9] = level, big endian; 0 for operations
+///
+/// [9..13] = round, big endian; 0 for emmy and operations
+///
+/// [13..21] = prefix of the block payload hash; of the signed hash for operations
+pub struct Record {
+    pub kind: Kind,
+    pub chain_id: u32,
+    pub level: u32,
+    pub round: u32,
+    pub payload_hash: [u8; Self::PAYLOAD_PREFIX_LEN],
+}
+
+impl Record {
+    pub const PAYLOAD_PREFIX_LEN: usize = 8;
+    pub const SERIALIZED_LEN: usize = 1 + 4 + 4 + 4 + Self::PAYLOAD_PREFIX_LEN;
+
+    pub fn new(kind: Kind, chain_id: u32, level: u32, round: u32, payload_hash: &[u8]) -> Self {
+        let mut prefix = [0; Self::PAYLOAD_PREFIX_LEN];
+        let len = payload_hash.len().min(Self::PAYLOAD_PREFIX_LEN);
+        prefix[..len].copy_from_slice(&payload_hash[..len]);
+
+        Self {
+            kind,
+            chain_id,
+            level,
+            round,
+            payload_hash: prefix,
+        }
+    }
+
+    pub fn to_bytes(self) -> [u8; Self::SERIALIZED_LEN] {
+        let mut out = [0; Self::SERIALIZED_LEN];
+        out[0] = self.kind as u8;
+        out[1..5].copy_from_slice(&self.chain_id.to_be_bytes());
+        out[5..9].copy_from_slice(&self.level.to_be_bytes());
+        out[9..13].copy_from_slice(&self.round.to_be_bytes());
+        out[13..].copy_from_slice(&self.payload_hash);
+
+        out
+    }
+
+    pub fn from_bytes(from: &[u8; Self::SERIALIZED_LEN]) -> Option<Self> {
+        use arrayref::array_ref;
+
+        Some(Self {
+            kind: Kind::from_u8(from[0])?,
+            chain_id: u32::from_be_bytes(*array_ref!(from, 1, 4)),
+            level: u32::from_be_bytes(*array_ref!(from, 5, 4)),
+            round: u32::from_be_bytes(*array_ref!(from, 9, 4)),
+            payload_hash: *array_ref!(from, 13, Record::PAYLOAD_PREFIX_LEN),
+        })
+    }
+}
+
+/// Retrieves the record of the signature made `n` signatures ago, 0 being the most recent
+pub fn record(n: usize) -> Option<Record> {
+    let data = unsafe { LOG.read_nth(n) }.ok()?;
+
+    Record::from_bytes(arrayref::array_ref!(data, 0, Record::SERIALIZED_LEN))
+}
+
+/// Number of records in the log
+pub fn len() -> usize {
+    (0..MAX_RECORDS)
+        .take_while(|&n| record(n).is_some())
+        .count()
+}
+
+/// Adds `record` to the log, overwriting the oldest one when full
+///
+/// The log is written after signing, so a failure is ignored
+/// instead of withholding a signature the watermark already accounts for
+#[inline(never)]
+pub fn append(record: Record) {
+    let mut data = [0; 52];
+    data[..Record::SERIALIZED_LEN].copy_from_slice(&record.to_bytes());
+
+    let _ = unsafe { LOG.write(data) };
+}
+
+pub struct QuerySignLog;
+
+impl ApduHandler for QuerySignLog {
+    #[inline(never)]
+    fn handle<'apdu>(
+        _: &mut u32,
+        tx: &mut u32,
+        buffer: ApduBufferRead<'apdu>,
+    ) -> Result<(), Error> {
+        sys::zemu_log_stack("QuerySignLog::handle\x00");
+
+        *tx = 0;
+
+        let page = buffer.p1() as usize;
+        if page * RECORDS_PER_PAGE >= MAX_RECORDS {
+            return Err(Error::InvalidP1P2);
+        }
+
+        let first = page * RECORDS_PER_PAGE;
+        let count = len();
+
+        let out = buffer.write();
+        out[0] = count as u8;
+        let mut len = 1;
+        for record in (first..count.min(first + RECORDS_PER_PAGE)).filter_map(record) {
+            out[len..len + Record::SERIALIZED_LEN].copy_from_slice(&record.to_bytes());
+            len += Record::SERIALIZED_LEN;
+        }
+
+        *tx = len as u32;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assert_error_code,
+        dispatcher::{handle_apdu, CLA, INS_QUERY_SIGN_LOG},
+    };
+    use std::convert::TryInto;
+
+    use serial_test::serial;
+
+    /// Removes all records from the log
+    fn clear() -> Result<(), Error> {
+        unsafe { LOG.format() }.map_err(|_| Error::ExecutionError)
+    }
+
+    #[test]
+    fn record_roundtrip() {
+        let record = Record::new(Kind::Endorsement, 0x7A06A770, 42, 3, &[0xAB; 32]);
+        let bytes = record.to_bytes();
+
+        assert_eq!(Record::from_bytes(&bytes), Some(record));
+        assert_eq!(&bytes[13..], &[0xAB; Record::PAYLOAD_PREFIX_LEN]);
+
+        let mut unknown = bytes;
+        unknown[0] = 0;
+        assert_eq!(Record::from_bytes(&unknown), None);
+    }
+
+    #[test]
+    #[serial(sign_log)]
+    fn ring_buffer() {
+        clear().unwrap();
+        assert_eq!(len(), 0);
+
+        for level in 0..MAX_RECORDS as u32 + 3 {
+            append(Record::new(Kind::Block, 1, level, 0, &[]));
+        }
+
+        //only the last ones are kept, the most recent first
+        assert_eq!(len(), MAX_RECORDS);
+        let levels: std::vec::Vec<_> = (0..MAX_RECORDS)
+            .filter_map(record)
+            .map(|r| r.level)
+            .collect();
+        let expected: std::vec::Vec<_> = (3..MAX_RECORDS as u32 + 3).rev().collect();
+        assert_eq!(levels, expected);
+
+        clear().unwrap();
+    }
+
+    #[test]
+    #[serial(sign_log)]
+    fn apdu_query_pages() {
+        clear().unwrap();
+
+        for level in 0..RECORDS_PER_PAGE as u32 + 1 {
+            append(Record::new(Kind::Preendorsement, 1, level, 2, &[7; 32]));
+        }
+
+        let mut flags = 0;
+        let mut tx = 0;
+        let mut buffer = [0; 260];
+
+        buffer[..5].copy_from_slice(&[CLA, INS_QUERY_SIGN_LOG, 0, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(
+            tx as usize,
+            1 + RECORDS_PER_PAGE * Record::SERIALIZED_LEN + 2
+        );
+        assert_eq!(buffer[0] as usize, RECORDS_PER_PAGE + 1);
+        let newest = Record::from_bytes(arrayref::array_ref!(buffer, 1, Record::SERIALIZED_LEN));
+        assert_eq!(newest.map(|r| r.level), Some(RECORDS_PER_PAGE as u32));
+
+        buffer[..5].copy_from_slice(&[CLA, INS_QUERY_SIGN_LOG, 1, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(tx as usize, 1 + Record::SERIALIZED_LEN + 2);
+        let oldest = Record::from_bytes(arrayref::array_ref!(buffer, 1, Record::SERIALIZED_LEN));
+        assert_eq!(oldest.map(|r| r.level), Some(0));
+
+        //pages past the records are empty
+        buffer[..5].copy_from_slice(&[CLA, INS_QUERY_SIGN_LOG, 2, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::Success);
+        assert_eq!(tx as usize, 1 + 2);
+
+        let last_page = (MAX_RECORDS / RECORDS_PER_PAGE) as u8;
+        buffer[..5].copy_from_slice(&[CLA, INS_QUERY_SIGN_LOG, last_page, 0, 0]);
+        handle_apdu(&mut flags, &mut tx, 5, &mut buffer);
+        assert_error_code!(tx, buffer, Error::InvalidP1P2);
+
+        clear().unwrap();
+    }
+}
@@ -15,28 +15,34 @@
 ********************************************************************************/
 use {std::convert::TryFrom, std::mem::MaybeUninit};
 
-use core::hint::unreachable_unchecked;
-
+use arrayvec::ArrayVec;
 use bolos::{
     crypto::bip32::BIP32Path,
     hash::{Blake2b, Hasher},
     pic_str, PIC,
 };
-use zemu_sys::{Show, ViewError, Viewable};
+use zemu_sys::{Show, ViewError, Viewable, Warning, MAX_WARNINGS};
 
 use crate::{
     constants::{ApduError as Error, BIP32_MAX_LENGTH},
     crypto::Curve,
     dispatcher::ApduHandler,
-    handlers::handle_ui_message,
+    handlers::{
+        handle_ui_message, policy, public_key::GetAddress, rejection::Rejection, session,
+        settings::Settings,
+    },
     parser::{
-        operations::{Operation, OperationType},
+        operations::{Operation, OperationType, Summary},
         DisplayableItem, Preemble,
     },
     sys,
-    utils::{ApduBufferRead, ApduPanic, Uploader},
+    utils::{ApduBufferRead, ApduPanic, Uploader, UploaderError},
 };
 
+mod fee;
+mod index;
+use index::OpsIndex;
+
 #[bolos::lazy_static]
 static mut PATH: Option<(BIP32Path<BIP32_MAX_LENGTH>, Curve)> = None;
 
@@ -45,6 +51,9 @@ pub struct Sign;
 impl Sign {
     pub const SIGN_HASH_SIZE: usize = 32;
 
+    /// Where the operations start in the data to sign (magic byte and branch)
+    const OPS_OFFSET: usize = 1 + 32;
+
     fn get_derivation_info() -> Result<&'static (BIP32Path<BIP32_MAX_LENGTH>, Curve), Error> {
         match unsafe { &*PATH } {
             None => Err(Error::ApduCodeConditionsNotSatisfied),
@@ -74,6 +83,66 @@ impl Sign {
         Blake2b::digest(buffer).map_err(|_| Error::ExecutionError)
     }
 
+    /// Computes the public key hash of the key identified by `curve` and `path`
+    #[inline(never)]
+    pub(crate) fn signer_pkh(
+        curve: Curve,
+        path: &BIP32Path<BIP32_MAX_LENGTH>,
+    ) -> Result<[u8; 20], Error> {
+        let mut pkey = MaybeUninit::uninit();
+        GetAddress::new_key_into(curve, path, &mut pkey).map_err(|_| Error::ExecutionError)?;
+
+        //safe because we initialized it above
+        let pkey = unsafe { pkey.assume_init() };
+
+        let mut pkh = [0; 20];
+        pkey.hash(&mut pkh).map_err(|_| Error::ExecutionError)?;
+        Ok(pkh)
+    }
+
+    /// Verifies that `op` is meant to be signed by `signer`
+    ///
+    /// This means that the source of the operation (when present) has to
+    /// be the signer's pkh, and that a revealed public key hashes to it
+    fn verify_source(
+        (curve, signer): (Curve, &[u8; 20]),
+        op: &OperationType<'_>,
+    ) -> Result<(), Error> {
+        //tz1 addresses can come from either ed25519 curve
+        // so we compare the address prefixes instead of the curves
+        let is_signer = |crv: Curve, pkh: &[u8; 20]| {
+            crv.to_hash_prefix() == curve.to_hash_prefix() && pkh == signer
+        };
+
+        if let Some((crv, pkh)) = op.source() {
+            if !is_signer(crv, pkh) {
+                return Err(Rejection::WrongSource.reject(Error::DataInvalid));
+            }
+        }
+
+        if let OperationType::Reveal(reveal) = op {
+            let (crv, pk) = *reveal.public_key();
+            let pkh: [u8; 20] = Blake2b::digest(pk).map_err(|_| Error::ExecutionError)?;
+
+            if !is_signer(crv, &pkh) {
+                return Err(Rejection::WrongSource.reject(Error::DataInvalid));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Signs without showing anything, writing the signature to `out`
+    #[inline(never)]
+    fn sign_unattended(mut ui: SignUI, out: &mut [u8]) -> Result<u32, Error> {
+        sys::zemu_log_stack("Sign::sign_unattended\x00");
+
+        match ui.accept(out) {
+            (tx, code) if code == Error::Success as u16 => Ok(tx as u32),
+            (_, code) => Err(Error::try_from(code).unwrap_or(Error::ExecutionError)),
+        }
+    }
+
     #[inline(never)]
     pub fn start_sign(
         send_hash: bool,
@@ -82,32 +151,107 @@ impl Sign {
         data: &'static [u8],
         flags: &mut u32,
     ) -> Result<u32, Error> {
-        let curve = Curve::try_from(p2).map_err(|_| Error::InvalidP1P2)?;
-        let path =
-            BIP32Path::<BIP32_MAX_LENGTH>::read(init_data).map_err(|_| Error::DataInvalid)?;
+        Self::start_sign_automated(send_hash, p2, init_data, data, None, flags)
+    }
+
+    /// Like `start_sign`, but when `out` is present operations can be signed
+    /// right away (in `out`) without review
+    ///
+    /// That's the case for operations within the signing session in progress,
+    /// or within the unattended signing policy
+    #[inline(never)]
+    fn start_sign_automated(
+        send_hash: bool,
+        p2: u8,
+        init_data: &[u8],
+        data: &'static [u8],
+        out: Option<&mut [u8]>,
+        flags: &mut u32,
+    ) -> Result<u32, Error> {
+        let curve =
+            Curve::try_from(p2).map_err(|_| Rejection::WrongCurve.reject(Error::InvalidP1P2))?;
+        let path = BIP32Path::<BIP32_MAX_LENGTH>::read(init_data)
+            .map_err(|_| Rejection::WrongPath.reject(Error::DataInvalid))?;
 
         unsafe {
             PATH.replace((path, curve));
         }
 
         let unsigned_hash = Self::blake2b_digest(data)?;
-        let (rem, preemble) = Preemble::from_bytes(data).map_err(|_| Error::DataInvalid)?;
+        let (rem, preemble) = Preemble::from_bytes(data)
+            .map_err(|_| Rejection::WrongPreemble.reject(Error::DataInvalid))?;
 
-        let mut ui = SignUI {
-            hash: unsigned_hash,
-            send_hash,
-            parsed: None,
-        };
+        let settings = Settings::load();
+        let mut ui = SignUI::new(unsigned_hash, send_hash, settings.expert());
+        let mut warnings = ArrayVec::<Warning, MAX_WARNINGS>::new();
 
         match preemble {
             Preemble::Operation => {
-                ui.parsed = Some(Operation::new(rem).map_err(|_| Error::DataInvalid)?)
+                let parsed = Operation::new(rem)
+                    .map_err(|e| Rejection::from(e).reject(Error::DataInvalid))?;
+
+                let signer = Self::signer_pkh(curve, &path)?;
+
+                let mut high_fee = false;
+                ui.set_operation(parsed, |op, size| {
+                    Self::verify_source((curve, &signer), op)?;
+                    high_fee |= fee::is_high_fee(op, size, &settings);
+
+                    Ok(())
+                })?;
+
+                if high_fee {
+                    warnings.push(Warning {
+                        title: pic_str!(b"High fee"),
+                        message: pic_str!(b"Fee looks too high, check it carefully"),
+                    });
+                }
+
+                //unknown operations can't be reviewed properly
+                // so they are only signed if the user opted in
+                match ui.unknown_tag {
+                    Some(tag) if !settings.blind_signing() => {
+                        return Err(Rejection::UnsupportedOperation(tag)
+                            .reject(Error::ApduCodeConditionsNotSatisfied));
+                    }
+                    _ => {}
+                }
+
+                if let Some(out) = out {
+                    //anything that would need the user's attention is never signed right away
+                    let automatic = warnings.is_empty();
+
+                    //while a session is in progress nothing else is signed
+                    if session::active() {
+                        if automatic && session::approves((curve, &signer), *parsed.ops()) {
+                            return Self::sign_unattended(ui, out);
+                        }
+
+                        return Err(
+                            Rejection::OutsideSession.reject(Error::ApduCodeConditionsNotSatisfied)
+                        );
+                    }
+
+                    if automatic && policy::approves(curve, &path, *parsed.ops()) {
+                        return Self::sign_unattended(ui, out);
+                    }
+                }
+            }
+            Preemble::Michelson if out.is_some() && session::active() => {
+                return Err(Rejection::OutsideSession.reject(Error::ApduCodeConditionsNotSatisfied));
             }
             Preemble::Michelson => {}
-            _ => return Err(Error::CommandNotAllowed),
+            _ => return Err(Rejection::WrongPreemble.reject(Error::CommandNotAllowed)),
         };
 
-        unsafe { ui.show(flags) }
+        if ui.unknown_tag.is_some() {
+            warnings.push(Warning {
+                title: pic_str!(b"Blind Signing"),
+                message: pic_str!(b"Unknown operations, sign at your own risk"),
+            });
+        }
+
+        unsafe { ui.show_with_warnings(&warnings, flags) }
             .map_err(|_| Error::ExecutionError)
             .map(|_| 0)
     }
@@ -124,8 +268,20 @@ impl ApduHandler for Sign {
 
         *tx = 0;
 
-        if let Some(upload) = Uploader::new(Self).upload(&buffer)? {
-            *tx = Self::start_sign(true, upload.p2, upload.first, upload.data, flags)?;
+        let upload = Uploader::new(Self).upload(&buffer).map_err(|e| match e {
+            UploaderError::Nvm(_) => Rejection::PayloadTooLarge.reject(e.into()),
+            e => e.into(),
+        })?;
+
+        if let Some(upload) = upload {
+            *tx = Self::start_sign_automated(
+                true,
+                upload.p2,
+                upload.first,
+                upload.data,
+                Some(buffer.write()),
+                flags,
+            )?;
         }
 
         Ok(())
@@ -135,123 +291,320 @@ impl ApduHandler for Sign {
 pub(crate) struct SignUI {
     hash: [u8; Sign::SIGN_HASH_SIZE],
     send_hash: bool,
+    //show the advanced fields too
+    expert: bool,
+    //tag of the first unknown operation in the group
+    unknown_tag: Option<u8>,
     parsed: Option<Operation<'static>>,
+    //only present for groups of multiple operations
+    summary: Option<Summary>,
+    index: OpsIndex,
+    cache: RenderCache,
 }
 
 #[cfg(test)]
 impl Operation<'static> {
+    /// Test vectors show all the fields, so this is in expert mode
     pub(crate) fn to_sign_ui(self) -> SignUI {
-        SignUI {
-            hash: [0; Sign::SIGN_HASH_SIZE],
-            send_hash: false,
-            parsed: Some(self),
+        self.to_sign_ui_with_mode(true)
+    }
+
+    pub(crate) fn to_sign_ui_with_mode(self, expert: bool) -> SignUI {
+        let mut ui = SignUI::new([0; Sign::SIGN_HASH_SIZE], false, expert);
+
+        ui.set_operation(self, |_, _| Ok(())).unwrap();
+        ui
+    }
+}
+
+/// Last item rendered, so we don't have to render it again
+/// for each of its pages
+struct RenderCache {
+    item_n: Option<u16>,
+    title: [u8; Self::TITLE_SIZE],
+    message: [u8; Self::MESSAGE_SIZE],
+    len: u8,
+}
+
+impl RenderCache {
+    const TITLE_SIZE: usize = 17 + 1;
+    const MESSAGE_SIZE: usize = 63 + 1;
+
+    const fn new() -> Self {
+        Self {
+            item_n: None,
+            title: [0; Self::TITLE_SIZE],
+            message: [0; Self::MESSAGE_SIZE],
+            len: 0,
+        }
+    }
+
+    /// Writes the cached item, if it's `item_n`
+    fn get(
+        &self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Option<Result<u8, ViewError>> {
+        if self.item_n != Some(item_n) {
+            return None;
         }
+
+        let len = core::cmp::min(title.len(), Self::TITLE_SIZE);
+        title[..len].copy_from_slice(&self.title[..len]);
+
+        Some(handle_ui_message(
+            &self.message[..self.len as usize],
+            message,
+            page,
+        ))
+    }
+
+    /// Renders the item with `render` and caches it if it fits
+    ///
+    /// Otherwise `render` is called with the output buffers directly
+    fn render(
+        &mut self,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+        mut render: impl FnMut(&mut [u8], &mut [u8], u8) -> Result<u8, ViewError>,
+    ) -> Result<u8, ViewError> {
+        self.item_n = None;
+        self.title = [0; Self::TITLE_SIZE];
+
+        //a single page means the item fit in the cache
+        if let Ok(1) = render(&mut self.title, &mut self.message, 0) {
+            let title_len = self.title.iter().position(|&c| c == 0);
+            let len = self.message.iter().position(|&c| c == 0);
+
+            if let (Some(_), Some(len)) = (title_len, len) {
+                self.item_n = Some(item_n);
+                self.len = len as u8;
+
+                return self
+                    .get(item_n, title, message, page)
+                    .ok_or(ViewError::Unknown)?;
+            }
+        }
+
+        render(title, message, page)
     }
 }
 
 impl SignUI {
+    fn new(hash: [u8; Sign::SIGN_HASH_SIZE], send_hash: bool, expert: bool) -> Self {
+        Self {
+            hash,
+            send_hash,
+            expert,
+            unknown_tag: None,
+            parsed: None,
+            summary: None,
+            index: OpsIndex::empty(),
+            cache: RenderCache::new(),
+        }
+    }
+
+    /// Sets the operations to review
+    ///
+    /// The group is walked only once: to index it, to compute its summary,
+    /// to look for unknown operations and to `check` every operation (with its size)
+    #[inline(never)]
+    fn set_operation(
+        &mut self,
+        parsed: Operation<'static>,
+        mut check: impl FnMut(&OperationType<'_>, usize) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut summary = Summary::default();
+        let mut unknown_tag = None;
+
+        let index = OpsIndex::new(*parsed.ops(), self.expert, |op, size| {
+            summary
+                .add(op)
+                .map_err(|e| Rejection::Parser(e).reject(Error::DataInvalid))?;
+
+            if let (None, OperationType::UnknownOp(tag, _)) = (unknown_tag, op) {
+                unknown_tag = Some(*tag);
+            }
+
+            check(op, size)
+        })?;
+
+        //make sure the burn cap is representable too
+        summary
+            .burn_cap()
+            .map_err(|e| Rejection::Parser(e).reject(Error::DataInvalid))?;
+
+        self.unknown_tag = unknown_tag;
+        self.summary = if index.n_ops() > 1 {
+            Some(summary)
+        } else {
+            None
+        };
+        self.index = index;
+        self.parsed = Some(parsed);
+        self.cache = RenderCache::new();
+
+        //make sure all the items can be addressed
+        self.num_items()
+            .map_err(|_| Rejection::TooManyItems.reject(Error::DataInvalid))?;
+
+        Ok(())
+    }
+
+    fn summary_items(&self) -> u16 {
+        self.summary
+            .as_ref()
+            .map(|s| s.num_items() as u16)
+            .unwrap_or_default()
+    }
+
+    //the branch is only shown in expert mode
+    fn branch_items(&self) -> u16 {
+        self.expert as u16
+    }
+
     // Will find the operation that contains said item, as well as
     // return the index of the item in the operation
+    //
+    // `item_idx` is relative to the first item of the first operation
     fn find_op_with_item(
         &self,
-        mut item_idx: u8,
+        item_idx: u16,
         op: &mut MaybeUninit<OperationType<'static>>,
     ) -> Result<Option<u8>, ViewError> {
-        item_idx -= 1; //remove branch idx
-
         //we shouldn't be here if parsed is None
         let mut parsed = self.parsed.ok_or(ViewError::Unknown)?;
         let ops = parsed.mut_ops();
 
+        //start from the closest operation we know of
+        let (offset, first_item) = match self.index.checkpoint(item_idx) {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(None),
+        };
+        //safe because the index only has offsets of the start of operations
+        unsafe { ops.set_source_index(offset) };
+        let mut item_idx = item_idx - first_item;
+
         //we don't call this if we haven't verified all info first
         while ops
             .parse_next_into(op)
             .map_err(|_| ViewError::Unknown)?
             .is_some()
         {
-            let op = op.as_mut_ptr();
             //safe because the pointer is valid and we have initialized this
-            // also, we are the only ones with access at this point
-            let op_ref = unsafe {
-                match op.as_mut() {
-                    Some(ptr) => ptr,
-                    None => unreachable_unchecked(),
-                }
-            };
-            let n = op_ref.ui_items() as u8;
+            let n = unsafe { &*op.as_ptr() }.ui_items(self.expert) as u16;
 
             if n > item_idx {
                 //we return the remaining item_idx so we can navigate to it
                 // we don't want to drop `op` here so it can be used by the caller!!!
-                return Ok(Some(item_idx));
-            } else {
-                //decrease item_idx by n items and check next operation
-                item_idx -= n;
+                //a single operation never has more than u8::MAX items
+                return u8::try_from(item_idx)
+                    .map(Some)
+                    .map_err(|_| ViewError::Unknown);
             }
 
-            //pointer is valid, aligned
-            // and initialized
-            // we only drop if we are looping again
-            // since it's not what we are looking for
-            unsafe { op.drop_in_place() }
+            //decrease item_idx by n items and check next operation
+            item_idx -= n;
         }
 
         Ok(None)
     }
+
+    #[inline(never)]
+    fn render_op_item(
+        &self,
+        parsed: Operation<'static>,
+        item_n: u16,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        let mut op = MaybeUninit::uninit();
+
+        let summary_items = self.summary_items();
+        if item_n < summary_items {
+            //summary is present since we have items for it
+            let summary = self.summary.as_ref().ok_or(ViewError::Unknown)?;
+            return summary.render_item(item_n as u8, title, message, page);
+        }
+        let item_n = item_n - summary_items;
+
+        if item_n < self.branch_items() {
+            let title_content = pic_str!(b"Operation");
+            title[..title_content.len()].copy_from_slice(title_content);
+
+            let (len, mex) = parsed.get_base58_branch().map_err(|_| ViewError::Unknown)?;
+            handle_ui_message(&mex[..len], message, page)
+        } else if let Some(item_n) =
+            self.find_op_with_item(item_n - self.branch_items(), &mut op)?
+        {
+            //this is safe as we have initialized `op` in `self.find_op_with_item`
+            let op = unsafe { op.assume_init() };
+            match op {
+                OperationType::Transfer(tx) => tx.render_item(item_n, title, message, page),
+                OperationType::Delegation(delegation) => {
+                    delegation.render_item(item_n, title, message, page)
+                }
+                OperationType::Endorsement(endorsement) => {
+                    endorsement.render_item(item_n, title, message, page)
+                }
+                OperationType::EndorsementWithSlot(endorsement) => {
+                    endorsement.render_item(item_n, title, message, page)
+                }
+                OperationType::Ballot(vote) => vote.render_item(item_n, title, message, page),
+                OperationType::Reveal(rev) => rev.render_item(item_n, title, message, page),
+                OperationType::Proposals(props) => props.render_item(item_n, title, message, page),
+                OperationType::Origination(orig) => orig.render_item(item_n, title, message, page),
+                OperationType::ActivateAccount(act) => {
+                    act.render_item(item_n, title, message, page)
+                }
+                OperationType::FailingNoop(fail) => fail.render_item(item_n, title, message, page),
+                OperationType::UpdateConsensusKey(upd) => {
+                    upd.render_item(item_n, title, message, page)
+                }
+                OperationType::DrainDelegate(drain) => {
+                    drain.render_item(item_n, title, message, page)
+                }
+                OperationType::SetDepositsLimit(set) => {
+                    set.render_item(item_n, title, message, page)
+                }
+                OperationType::UnknownOp(tag, unk) => {
+                    OperationType::render_unknown(tag, unk, item_n, title, message, page)
+                }
+                OperationType::AnonymousOp(_) => Err(ViewError::Reject),
+            }
+        } else {
+            Err(ViewError::NoData)
+        }
+    }
 }
 
 impl Viewable for SignUI {
-    fn num_items(&mut self) -> Result<u8, ViewError> {
+    fn num_items(&mut self) -> Result<u16, ViewError> {
         match self.parsed {
             None => Ok(1),
-            Some(mut parsed) => {
-                let ops = parsed.mut_ops();
-
-                let mut items_counter = 1; //start with branch
-                let mut op = MaybeUninit::uninit();
-
-                while ops
-                    .parse_next_into(&mut op)
-                    .map_err(|_| ViewError::Unknown)?
-                    .is_some()
-                {
-                    let op = op.as_mut_ptr();
-                    //safe because the pointer is valid and we have initialized this
-                    // also, we are the only ones with access at this point
-                    let op_ref = unsafe { op.as_mut().unwrap() };
-                    items_counter += op_ref.ui_items();
-
-                    //this is safe to drop because
-                    // pointer is valid, aligned
-                    // and initialized
-                    //we will be writing to this location before reading again
-                    unsafe { op.drop_in_place() }
-                }
-
-                if items_counter > 1 {
-                    //this means we have parsed at least once and have reached the end
-                    // so we need to drop this manually
-                    //This IS initialized (from the last loop)
-                    // the pointer is valid and aligned
-                    // it also won't be used anymore
-                    unsafe { op.as_mut_ptr().drop_in_place() }
-                }
-
-                Ok(items_counter as u8)
-            }
+            //refuse to show more items than we can address
+            Some(_) => self
+                .index
+                .items()
+                .checked_add(self.branch_items())
+                .and_then(|n| n.checked_add(self.summary_items()))
+                .ok_or(ViewError::Unknown),
         }
     }
 
     #[inline(never)]
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
     ) -> Result<u8, ViewError> {
-        let mut op = MaybeUninit::uninit();
-
         match self.parsed {
             None => match item_n {
                 0 => {
@@ -267,50 +620,17 @@ impl Viewable for SignUI {
                 _ => Err(ViewError::NoData),
             },
             Some(parsed) => {
-                if let 0 = item_n {
-                    let title_content = pic_str!(b"Operation");
-                    title[..title_content.len()].copy_from_slice(title_content);
-
-                    let (len, mex) = parsed.get_base58_branch().map_err(|_| ViewError::Unknown)?;
-                    handle_ui_message(&mex[..len], message, page)
-                } else if let Some(item_n) = self.find_op_with_item(item_n, &mut op)? {
-                    //this is safe as we have initialized `op` in `self.find_op_with_item`
-                    let op = unsafe { op.assume_init() };
-                    match op {
-                        OperationType::Transfer(tx) => tx.render_item(item_n, title, message, page),
-                        OperationType::Delegation(delegation) => {
-                            delegation.render_item(item_n, title, message, page)
-                        }
-                        OperationType::Endorsement(endorsement) => {
-                            endorsement.render_item(item_n, title, message, page)
-                        }
-                        OperationType::EndorsementWithSlot(endorsement) => {
-                            endorsement.render_item(item_n, title, message, page)
-                        }
-                        OperationType::Ballot(vote) => {
-                            vote.render_item(item_n, title, message, page)
-                        }
-                        OperationType::Reveal(rev) => rev.render_item(item_n, title, message, page),
-                        OperationType::Proposals(props) => {
-                            props.render_item(item_n, title, message, page)
-                        }
-                        OperationType::Origination(orig) => {
-                            orig.render_item(item_n, title, message, page)
-                        }
-                        OperationType::ActivateAccount(act) => {
-                            act.render_item(item_n, title, message, page)
-                        }
-                        OperationType::FailingNoop(fail) => {
-                            fail.render_item(item_n, title, message, page)
-                        }
-                        OperationType::UnknownOp(unk) => {
-                            OperationType::render_unknown(unk, item_n, title, message, page)
-                        }
-                        OperationType::AnonymousOp(_) => Err(ViewError::Reject),
-                    }
-                } else {
-                    Err(ViewError::NoData)
+                if let Some(result) = self.cache.get(item_n, title, message, page) {
+                    return result;
                 }
+
+                let mut cache = core::mem::replace(&mut self.cache, RenderCache::new());
+                let result = cache.render(item_n, title, message, page, |title, message, page| {
+                    self.render_op_item(parsed, item_n, title, message, page)
+                });
+                self.cache = cache;
+
+                result
             }
         }
     }
@@ -365,9 +685,10 @@ mod tests {
         assert_error_code,
         dispatcher::{handle_apdu, CLA, INS_SIGN},
         handlers::ZPacketType,
+        parser::operations::Transfer,
         sys::get_out,
     };
-    use std::convert::TryInto;
+    use std::{convert::TryInto, vec, vec::Vec};
 
     use serial_test::serial;
 
@@ -384,6 +705,295 @@ mod tests {
         path.len()
     }
 
+    fn transfer_from(source: &[u8; 20]) -> Vec<u8> {
+        let mut op = vec![0x6C, 0x00];
+        op.extend_from_slice(&source[..]);
+        //fee, counter, gas_limit, storage_limit, amount
+        op.extend_from_slice(&[0x01; 5]);
+        //implicit destination
+        op.extend_from_slice(&[0x00, 0x00]);
+        op.extend_from_slice(&[0xAA; 20]);
+        //no parameters
+        op.push(0x00);
+
+        op
+    }
+
+    fn contract_call_from(source: &[u8; 20], entrypoint: &[u8]) -> Vec<u8> {
+        let mut op = vec![0x6C, 0x00];
+        op.extend_from_slice(&source[..]);
+        //fee, counter, gas_limit, storage_limit, amount
+        op.extend_from_slice(&[0x01; 5]);
+        //originated destination
+        op.push(0x01);
+        op.extend_from_slice(&[0xAA; 20]);
+        op.push(0x00);
+        //custom entrypoint with a unit parameter
+        op.extend_from_slice(&[0xFF, 0xFF, entrypoint.len() as u8]);
+        op.extend_from_slice(entrypoint);
+        op.extend_from_slice(&[0, 0, 0, 2, 0x03, 0x0B]);
+
+        op
+    }
+
+    fn reveal_from(source: &[u8; 20], pk: &[u8; 32]) -> Vec<u8> {
+        let mut op = vec![0x6B, 0x00];
+        op.extend_from_slice(&source[..]);
+        //fee, counter, gas_limit, storage_limit
+        op.extend_from_slice(&[0x01; 4]);
+        //ed25519 public key
+        op.push(0x00);
+        op.extend_from_slice(&pk[..]);
+
+        op
+    }
+
+    fn to_operation(ops: &[&[u8]]) -> Operation<'static> {
+        let mut input = vec![0; 32]; //branch
+        ops.iter().for_each(|op| input.extend_from_slice(op));
+
+        Operation::new(input.leak()).unwrap()
+    }
+
+    fn verify_sources(signer: (Curve, &[u8; 20]), op: Operation<'static>) -> Result<(), Error> {
+        let mut ui = SignUI::new([0; Sign::SIGN_HASH_SIZE], false, true);
+        ui.set_operation(op, |op, _| Sign::verify_source(signer, op))
+    }
+
+    #[test]
+    fn sources_match_signer() {
+        let signer = [0x42; 20];
+
+        let op = to_operation(&[&transfer_from(&signer), &transfer_from(&signer)]);
+        verify_sources((Curve::Ed25519, &signer), op).unwrap();
+
+        //Bip32Ed25519 has the same address kind
+        let op = to_operation(&[&transfer_from(&signer)]);
+        verify_sources((Curve::Bip32Ed25519, &signer), op).unwrap();
+    }
+
+    #[test]
+    fn sources_mismatch_signer() {
+        let signer = [0x42; 20];
+
+        let op = to_operation(&[&transfer_from(&signer), &transfer_from(&[0xBB; 20])]);
+        assert_eq!(
+            verify_sources((Curve::Ed25519, &signer), op).unwrap_err(),
+            Error::DataInvalid
+        );
+
+        //same hash but different address kind
+        let op = to_operation(&[&transfer_from(&signer)]);
+        assert_eq!(
+            verify_sources((Curve::Secp256K1, &signer), op).unwrap_err(),
+            Error::DataInvalid
+        );
+    }
+
+    #[test]
+    fn reveal_signer_key() {
+        const PK: [u8; 32] = [0xCC; 32];
+        let signer = Blake2b::<20>::digest(&PK).unwrap();
+
+        let op = to_operation(&[&reveal_from(&signer, &PK)]);
+        verify_sources((Curve::Ed25519, &signer), op).unwrap();
+
+        //source is the signer, but the revealed key is not
+        let op = to_operation(&[&reveal_from(&signer, &[0xDD; 32])]);
+        assert_eq!(
+            verify_sources((Curve::Ed25519, &signer), op).unwrap_err(),
+            Error::DataInvalid
+        );
+    }
+
+    #[test]
+    fn summary_leads_batch() {
+        use zuit::{MockDriver, Page};
+
+        let single = to_operation(&[&transfer_from(&[0x42; 20])]);
+        let mut driver = MockDriver::<_, 18, 4096>::new(single.to_sign_ui());
+        driver.drive();
+        //branch + transfer items
+        assert_eq!(driver.out_ui().len(), 1 + 10);
+
+        let batch = to_operation(&[&transfer_from(&[0x42; 20]), &transfer_from(&[0x42; 20])]);
+        let mut driver = MockDriver::<_, 18, 4096>::new(batch.to_sign_ui());
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        //summary + branch + 2 transfers
+        assert_eq!(produced_ui.len(), 4 + 1 + 10 * 2);
+
+        let expected = [
+            ("Operations", "2"),
+            ("Total Amount", "0.000002"),
+            ("Total Fees", "0.000002"),
+            ("Total Burn Cap", "0.0005"),
+        ];
+        for (item, (title, message)) in produced_ui.iter().zip(expected.iter()) {
+            let Page {
+                title: produced_title,
+                message: produced_message,
+            } = &item[0];
+
+            assert!(produced_title.starts_with(title.as_bytes()));
+            assert!(produced_message.starts_with(message.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn large_batch() {
+        use zuit::MockDriver;
+
+        const N_OPS: usize = 60;
+
+        let transfer = transfer_from(&[0x42; 20]);
+        let op = to_operation(&[&transfer[..]; N_OPS]);
+
+        let mut driver = MockDriver::<_, 18, 4096>::new(op.to_sign_ui());
+        driver.with_print(false);
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        //summary + branch + transfers, more than u8::MAX
+        assert_eq!(produced_ui.len(), 4 + 1 + 10 * N_OPS);
+
+        //the last item is the entrypoint of the last transfer
+        let last = &produced_ui.last().unwrap()[0];
+        assert!(last.title.starts_with(b"Entrypoint"));
+    }
+
+    #[test]
+    fn normal_mode_hides_advanced() {
+        use zuit::MockDriver;
+
+        let batch = to_operation(&[&transfer_from(&[0x42; 20]), &transfer_from(&[0x42; 20])]);
+        let mut driver = MockDriver::<_, 18, 4096>::new(batch.to_sign_ui_with_mode(false));
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        //summary + 2 transfers, without branch
+        assert_eq!(produced_ui.len(), 4 + Transfer::BASIC_ITEMS * 2);
+
+        let titles: Vec<&[u8]> = produced_ui[4..]
+            .iter()
+            .map(|item| {
+                let title = &item[0].title[..];
+                &title[..title.iter().position(|&c| c == 0).unwrap_or(title.len())]
+            })
+            .collect();
+
+        let expected: [&[u8]; 5] = [b"Type", b"Source", b"Destination", b"Amount", b"Fee"];
+        assert_eq!(&titles[..5], &expected[..]);
+        assert_eq!(&titles[5..], &expected[..]);
+    }
+
+    #[test]
+    fn contract_call_items() {
+        use zuit::MockDriver;
+
+        let call = to_operation(&[&contract_call_from(&[0x42; 20], b"payout")]);
+
+        //parameters are never hidden
+        let mut driver = MockDriver::<_, 18, 4096>::new(call.to_sign_ui_with_mode(false));
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        assert_eq!(produced_ui.len(), Transfer::BASIC_ITEMS + 1);
+        let params = &produced_ui.last().unwrap()[0];
+        assert!(params.title.starts_with(b"Parameters"));
+
+        //expert mode also shows the raw entrypoint
+        let mut driver = MockDriver::<_, 18, 4096>::new(call.to_sign_ui());
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        //branch + transfer items
+        assert_eq!(produced_ui.len(), 1 + 10);
+
+        let entrypoint = produced_ui.last().unwrap();
+        let entrypoint = &entrypoint[0];
+        assert!(entrypoint.title.starts_with(b"Entrypoint"));
+        assert!(entrypoint.message.starts_with(b"payout\0"));
+    }
+
+    #[test]
+    fn unknown_op_items() {
+        use zuit::MockDriver;
+
+        const UNKNOWN: &[u8] = &[0x99, 0xDE, 0xAD, 0xBE, 0xEF];
+
+        let batch = to_operation(&[&transfer_from(&[0x42; 20]), UNKNOWN]);
+        let mut driver = MockDriver::<_, 18, 4096>::new(batch.to_sign_ui_with_mode(false));
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        //summary + transfer + unknown
+        assert_eq!(produced_ui.len(), 4 + Transfer::BASIC_ITEMS + 2);
+
+        let mut driver = MockDriver::<_, 18, 4096>::new(batch.to_sign_ui());
+        driver.drive();
+
+        let produced_ui = driver.out_ui();
+        //summary + branch + transfer + unknown
+        assert_eq!(produced_ui.len(), 4 + 1 + 10 + 4);
+
+        let tag = &produced_ui[produced_ui.len() - 2][0];
+        assert!(tag.title.starts_with(b"Tag"));
+        assert!(tag.message.starts_with(b"99\0"));
+
+        let raw = &produced_ui[produced_ui.len() - 1][0];
+        assert!(raw.title.starts_with(b"Raw Data"));
+        assert!(raw.message.starts_with(b"deadbeef\0"));
+    }
+
+    #[test]
+    #[serial(settings)]
+    fn unknown_op_requires_blind_signing() {
+        let path = BIP32Path::<10>::new([44, 1729, 0, 0].iter().map(|n| 0x8000_0000 + n))
+            .unwrap()
+            .serialize();
+
+        let mut data = vec![0x03];
+        data.extend_from_slice(&[0; 32]); //branch
+        data.extend_from_slice(&[0x99, 0xDE, 0xAD, 0xBE, 0xEF]);
+        let data: &'static [u8] = data.leak();
+
+        let mut settings = Settings::default();
+        settings.store().unwrap();
+
+        let mut flags = 0;
+        assert_eq!(
+            Sign::start_sign(
+                false,
+                Curve::Ed25519.into(),
+                path.as_slice(),
+                data,
+                &mut flags
+            ),
+            Err(Error::ApduCodeConditionsNotSatisfied)
+        );
+
+        settings.toggle_blind_signing();
+        settings.store().unwrap();
+        assert_eq!(
+            Sign::start_sign(
+                false,
+                Curve::Ed25519.into(),
+                path.as_slice(),
+                data,
+                &mut flags
+            ),
+            Ok(0)
+        );
+
+        let warnings = sys::get_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].title.starts_with(b"Blind Signing"));
+
+        Settings::default().store().unwrap();
+    }
+
     #[test]
     #[ignore]
     #[serial(ui)]
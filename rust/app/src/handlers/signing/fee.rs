@@ -0,0 +1,137 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use crate::{handlers::settings::Settings, parser::operations::OperationType};
+
+/// Fixed part of the minimal fee, in mutez
+const MINIMAL_FEE: u64 = 100;
+/// Part of the minimal fee for each byte of the operation, in nanotez
+const MINIMAL_NANOTEZ_PER_BYTE: u64 = 1000;
+/// Part of the minimal fee for each unit of gas, in nanotez
+const MINIMAL_NANOTEZ_PER_GAS_UNIT: u64 = 100;
+
+/// Computes the minimal fee (in mutez) bakers accept by default
+/// for an operation of `size` bytes consuming up to `gas_limit`
+pub fn minimal_fee(size: u64, gas_limit: u64) -> u64 {
+    let nanotez = size
+        .saturating_mul(MINIMAL_NANOTEZ_PER_BYTE)
+        .saturating_add(gas_limit.saturating_mul(MINIMAL_NANOTEZ_PER_GAS_UNIT));
+
+    //round up, like the protocol does
+    MINIMAL_FEE.saturating_add(nanotez.saturating_add(999) / 1000)
+}
+
+/// Checks if the fee of `op` (encoded in `size` bytes) looks abnormal
+///
+/// The fee is abnormal when it's above the cap in `settings`
+/// or more than `fee_ratio` times the minimal fee
+pub fn is_high_fee(op: &OperationType<'_>, size: usize, settings: &Settings) -> bool {
+    let (fee, gas_limit) = match op.fee_and_gas_limit() {
+        Some(manager) => manager,
+        None => return false,
+    };
+
+    //a fee that doesn't even fit is definitely too high
+    let fee = match fee.read_as::<u64>() {
+        Some((_, fee)) => fee,
+        None => return true,
+    };
+    let gas_limit = gas_limit
+        .read_as::<u64>()
+        .map(|(_, gas)| gas)
+        .unwrap_or(u64::MAX);
+
+    let cap = settings.fee_cap();
+    if cap != 0 && fee > cap {
+        return true;
+    }
+
+    let ratio = settings.fee_ratio();
+    ratio != 0 && fee > minimal_fee(size as u64, gas_limit).saturating_mul(ratio as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{vec, vec::Vec};
+
+    use super::*;
+    use crate::parser::operations::EncodedOperations;
+
+    /// Checks if any of the operations in `ops` has an abnormal fee
+    fn has_high_fee(mut ops: EncodedOperations<'_>, settings: &Settings) -> bool {
+        let mut start = 0;
+        while let Ok(Some(op)) = ops.parse_next() {
+            let size = ops.source_index() - start;
+            start = ops.source_index();
+
+            if is_high_fee(&op, size, settings) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn delegation(fee: &[u8], gas_limit: &[u8]) -> Vec<u8> {
+        let mut op = vec![0x6E, 0x00];
+        op.extend_from_slice(&[0x42; 20]);
+        op.extend_from_slice(fee);
+        //counter
+        op.push(0x01);
+        op.extend_from_slice(gas_limit);
+        //storage_limit
+        op.push(0x00);
+        //no delegate
+        op.push(0x00);
+
+        op
+    }
+
+    #[test]
+    fn minimal() {
+        assert_eq!(minimal_fee(0, 0), 100);
+        assert_eq!(minimal_fee(100, 1000), 100 + 100 + 100);
+        //rounded up
+        assert_eq!(minimal_fee(1, 1), 102);
+        //saturates instead of overflowing
+        assert_eq!(minimal_fee(u64::MAX, u64::MAX), 100 + u64::MAX / 1000);
+    }
+
+    #[test]
+    fn high_fees() {
+        let settings = Settings::default();
+
+        //300 mutez, 1000 gas
+        let normal = delegation(&[0xAC, 0x02], &[0xE8, 0x07]);
+        assert!(!has_high_fee(EncodedOperations::new(&normal), &settings));
+
+        //20_000 mutez is way above 10 times the minimal fee
+        let ratio = delegation(&[0xA0, 0x9C, 0x01], &[0xE8, 0x07]);
+        assert!(has_high_fee(EncodedOperations::new(&ratio), &settings));
+
+        //2 tez is above the default cap, even with lots of gas
+        let cap = delegation(&[0x80, 0x89, 0x7A], &[0x80, 0x89, 0x7A]);
+        assert!(has_high_fee(EncodedOperations::new(&cap), &settings));
+
+        //doesn't fit in 64 bits
+        let huge = delegation(&[0xFF; 10], &[0x01, 0x01]);
+        assert!(has_high_fee(EncodedOperations::new(&huge), &settings));
+
+        //only flagged once any of the operations is
+        let mut group = normal.clone();
+        group.extend_from_slice(&ratio);
+        assert!(has_high_fee(EncodedOperations::new(&group), &settings));
+    }
+}
@@ -0,0 +1,237 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use std::convert::TryFrom;
+
+use crate::{
+    constants::ApduError as Error,
+    handlers::rejection::Rejection,
+    parser::operations::{EncodedOperations, OperationType},
+};
+
+use super::Sign;
+
+/// Checkpoints into a group of encoded operations
+///
+/// Every `stride` operations the offset of the operation and the index
+/// of its first UI item are recorded, so an item can be located
+/// by parsing at most `stride` operations instead of the whole group
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(Debug))]
+pub struct OpsIndex {
+    //(offset of the operation, first item of the operation)
+    checkpoints: [(u16, u16); Self::CAPACITY],
+    len: u8,
+    stride: u16,
+    n_ops: u16,
+    //total number of items of all the operations
+    items: u16,
+}
+
+impl OpsIndex {
+    /// Maximum number of checkpoints
+    ///
+    /// Kept small since the index is stored with the UI
+    pub const CAPACITY: usize = 8;
+
+    pub const fn empty() -> Self {
+        Self {
+            checkpoints: [(0, 0); Self::CAPACITY],
+            len: 0,
+            stride: 1,
+            n_ops: 0,
+            items: 0,
+        }
+    }
+
+    /// Walks the operations in `ops` once, recording the checkpoints
+    ///
+    /// Items are counted according to `expert` mode.
+    /// Every operation (and its size in bytes) is also passed to `visit`,
+    /// so other checks don't need to walk the group again.
+    ///
+    /// Fails if an operation can't be parsed or if the group
+    /// has more operations (or items, or bytes) than we can address
+    #[inline(never)]
+    pub fn new(
+        mut ops: EncodedOperations<'_>,
+        expert: bool,
+        mut visit: impl FnMut(&OperationType<'_>, usize) -> Result<(), Error>,
+    ) -> Result<Self, Error> {
+        crate::sys::zemu_log_stack("OpsIndex::new\x00");
+
+        let mut this = Self::empty();
+        let too_many = || Rejection::TooManyItems.reject(Error::DataInvalid);
+
+        loop {
+            let offset = ops.source_index();
+            let op = match ops.parse_next() {
+                Ok(Some(op)) => op,
+                Ok(None) => break,
+                Err(e) => {
+                    let rejection = Rejection::Operation {
+                        index: this.n_ops,
+                        //offset of the operation in the signed data
+                        offset: u16::try_from(Sign::OPS_OFFSET + offset).unwrap_or(u16::MAX),
+                        error: e.into(),
+                    };
+                    return Err(rejection.reject(Error::DataInvalid));
+                }
+            };
+
+            visit(&op, ops.source_index() - offset)?;
+
+            let offset = u16::try_from(offset).map_err(|_| too_many())?;
+            this.record(offset).ok_or_else(too_many)?;
+
+            this.n_ops = this.n_ops.checked_add(1).ok_or_else(too_many)?;
+            this.items = u16::try_from(op.ui_items(expert))
+                .ok()
+                .and_then(|n| this.items.checked_add(n))
+                .ok_or_else(too_many)?;
+        }
+
+        Ok(this)
+    }
+
+    /// Records the operation at `offset` if it's due for a checkpoint
+    ///
+    /// Once all checkpoints are taken every other one is dropped
+    /// and the stride is doubled, so any number of operations can be indexed
+    fn record(&mut self, offset: u16) -> Option<()> {
+        if self.n_ops % self.stride != 0 {
+            return Some(());
+        }
+
+        if self.len as usize == Self::CAPACITY {
+            for i in 0..Self::CAPACITY / 2 {
+                self.checkpoints[i] = self.checkpoints[i * 2];
+            }
+            self.len = (Self::CAPACITY / 2) as u8;
+            self.stride = self.stride.checked_mul(2)?;
+
+            //the capacity is even, so this operation is still due
+            debug_assert_eq!(self.n_ops % self.stride, 0);
+        }
+
+        self.checkpoints[self.len as usize] = (offset, self.items);
+        self.len += 1;
+
+        Some(())
+    }
+
+    /// Returns the number of operations
+    pub fn n_ops(&self) -> usize {
+        self.n_ops as usize
+    }
+
+    /// Returns the total number of items of all the operations
+    pub fn items(&self) -> u16 {
+        self.items
+    }
+
+    /// Returns the closest checkpoint preceding `item_idx`
+    ///
+    /// The checkpoint is in the form (offset of the operation, first item of the operation)
+    pub fn checkpoint(&self, item_idx: u16) -> Option<(usize, u16)> {
+        if item_idx >= self.items {
+            return None;
+        }
+
+        self.checkpoints[..self.len as usize]
+            .iter()
+            .rev()
+            .find(|(_, first)| *first <= item_idx)
+            .map(|&(offset, first)| (offset as usize, first))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{vec, vec::Vec};
+
+    use super::*;
+
+    fn ballot() -> Vec<u8> {
+        let mut op = vec![0x06, 0x00];
+        op.extend_from_slice(&[0x42; 20]);
+        //period
+        op.extend_from_slice(&[0x00; 4]);
+        op.extend_from_slice(&[0x11; 32]);
+        //vote
+        op.push(0x00);
+
+        op
+    }
+
+    #[test]
+    fn checkpoints() {
+        let single = ballot();
+        let n_items = {
+            let mut ops = EncodedOperations::new(&single);
+            ops.parse_next().unwrap().unwrap().ui_items(true) as u16
+        };
+
+        let n_ops = OpsIndex::CAPACITY * 2 + 1;
+        let input = single.repeat(n_ops);
+
+        let mut visited = 0;
+        let index = OpsIndex::new(EncodedOperations::new(&input), true, |_, size| {
+            assert_eq!(size, single.len());
+            visited += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(visited, n_ops);
+        assert_eq!(index.n_ops(), n_ops);
+        assert_eq!(index.items(), n_items * n_ops as u16);
+        assert_eq!(index.stride, 4);
+        assert_eq!(index.len, 5);
+
+        assert_eq!(index.checkpoint(0), Some((0, 0)));
+        assert_eq!(index.checkpoint(n_items * 4 - 1), Some((0, 0)));
+        assert_eq!(
+            index.checkpoint(n_items * 4),
+            Some((single.len() * 4, n_items * 4))
+        );
+        assert_eq!(
+            index.checkpoint(index.items() - 1),
+            Some((single.len() * 16, n_items * 16))
+        );
+        assert_eq!(index.checkpoint(index.items()), None);
+    }
+
+    #[test]
+    fn many_ops() {
+        let n_ops = 500;
+        let input = ballot().repeat(n_ops);
+
+        let index = OpsIndex::new(EncodedOperations::new(&input), true, |_, _| Ok(())).unwrap();
+        assert_eq!(index.n_ops(), n_ops);
+        assert!(index.len as usize <= OpsIndex::CAPACITY);
+        assert!(index.stride as usize * OpsIndex::CAPACITY >= n_ops);
+    }
+
+    #[test]
+    fn visit_error() {
+        let input = ballot().repeat(2);
+
+        let err = OpsIndex::new(EncodedOperations::new(&input), true, |_, _| {
+            Err(Error::ExecutionError)
+        })
+        .unwrap_err();
+        assert_eq!(err, Error::ExecutionError);
+    }
+}
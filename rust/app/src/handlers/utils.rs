@@ -196,6 +196,35 @@ pub fn sha256x2(pieces: &[&[u8]], out: &mut [u8; 4]) -> Result<(), bolos::Error>
     Ok(())
 }
 
+/// Maximum length of a name shown by `handle_ui_named`, longer ones are cut
+pub const MAX_UI_NAME_LEN: usize = 32;
+
+#[inline(never)]
+/// Like `handle_ui_message`, but shows `address` along with the `name` it's known by,
+/// as "name (address)"
+pub fn handle_ui_named(
+    name: &[u8],
+    address: &[u8],
+    out: &mut [u8],
+    page: u8,
+) -> Result<u8, ViewError> {
+    let name = &name[..name.len().min(MAX_UI_NAME_LEN)];
+
+    //" (" and ")" around the address
+    let mut item = [0; MAX_UI_NAME_LEN + 3 + 37];
+    let len = name.len() + 3 + address.len();
+    if len > item.len() {
+        return Err(ViewError::Unknown);
+    }
+
+    item[..name.len()].copy_from_slice(name);
+    item[name.len()..name.len() + 2].copy_from_slice(b" (");
+    item[name.len() + 2..len - 1].copy_from_slice(address);
+    item[len - 1] = b')';
+
+    handle_ui_message(&item[..len], out, page)
+}
+
 #[inline(never)]
 pub fn handle_ui_message(item: &[u8], out: &mut [u8], page: u8) -> Result<u8, ViewError> {
     crate::sys::zemu_log_stack("handle_ui_message\x00");
@@ -218,6 +247,36 @@ pub fn handle_ui_message(item: &[u8], out: &mut [u8], page: u8) -> Result<u8, Vi
     }
 }
 
+#[inline(never)]
+/// Like `handle_ui_message`, but shows `item` hex encoded
+///
+/// Each page is encoded on the fly, so `item` can be of any length
+/// as long as it fits in u8::MAX pages
+pub fn handle_ui_hex(item: &[u8], out: &mut [u8], page: u8) -> Result<u8, ViewError> {
+    crate::sys::zemu_log_stack("handle_ui_hex\x00");
+    //2 characters per byte, and the null byte terminator
+    let per_page = (out.len() - 1) / 2;
+    if per_page == 0 {
+        return Err(ViewError::Unknown);
+    }
+
+    //at least one page, even if empty
+    let n_pages = 1 + item.len().saturating_sub(1) / per_page;
+    let n_pages = u8::try_from(n_pages).map_err(|_| ViewError::Unknown)?;
+
+    if page >= n_pages {
+        return Err(ViewError::Unknown);
+    }
+    //empty if there's nothing to show
+    let chunk = item.chunks(per_page).nth(page as usize).unwrap_or(&[]);
+
+    let hex_len = chunk.len() * 2;
+    hex::encode_to_slice(chunk, &mut out[..hex_len]).map_err(|_| ViewError::Unknown)?;
+    out[hex_len] = 0; //null terminate
+
+    Ok(n_pages)
+}
+
 #[cfg_attr(any(test, feature = "derive-debug"), derive(Debug))]
 pub enum IntStrToFpStrError {
     BufferFull,
@@ -336,7 +395,7 @@ pub fn intstr_to_fpstr_inplace(
 
 #[cfg(test)]
 mod tests {
-    use super::intstr_to_fpstr_inplace;
+    use super::{handle_ui_hex, handle_ui_named, intstr_to_fpstr_inplace, MAX_UI_NAME_LEN};
 
     const SUITE: &[(&[u8], usize, &str)] = &[
         //NORMAL
@@ -390,4 +449,37 @@ mod tests {
             assert_eq!(out, expected_output)
         }
     }
+
+    #[test]
+    fn handle_ui_hex_pages() {
+        let mut out = [0; 8];
+
+        //3 bytes per page
+        let item = [0xAB, 0xCD, 0xEF, 0x01, 0x23];
+        assert_eq!(handle_ui_hex(&item, &mut out, 0).ok(), Some(2));
+        assert_eq!(&out[..7], b"abcdef\0");
+        assert_eq!(handle_ui_hex(&item, &mut out, 1).ok(), Some(2));
+        assert_eq!(&out[..5], b"0123\0");
+        assert!(handle_ui_hex(&item, &mut out, 2).is_err());
+
+        //still a single (empty) page
+        assert_eq!(handle_ui_hex(&[], &mut out, 0).ok(), Some(1));
+        assert_eq!(out[0], 0);
+    }
+
+    #[test]
+    fn handle_ui_named_cut() {
+        let mut out = [0; 128];
+
+        assert_eq!(
+            handle_ui_named(b"Baker", b"tz1abc", &mut out, 0).ok(),
+            Some(1)
+        );
+        assert_eq!(&out[..15], b"Baker (tz1abc)\0");
+
+        //long names are cut
+        let name = [b'a'; MAX_UI_NAME_LEN + 10];
+        assert_eq!(handle_ui_named(&name, b"tz1", &mut out, 0).ok(), Some(1));
+        assert_eq!(&out[MAX_UI_NAME_LEN..MAX_UI_NAME_LEN + 7], b" (tz1)\0");
+    }
 }
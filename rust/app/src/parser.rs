@@ -21,6 +21,14 @@ use crate::{crypto::Curve, handlers::parser_common::ParserError};
 
 pub mod operations;
 
+mod known_bakers {
+    use bolos::PIC;
+    use zemu_sys::zemu_log_stack;
+
+    ledger_tezos_derive::unroll!("vendor/BakersRegistryCoreUnfilteredData.json");
+}
+pub use known_bakers::baker_lookup;
+
 #[cfg(feature = "baking")]
 pub mod baking;
 
@@ -192,7 +200,7 @@ pub fn public_key_hash(input: &[u8]) -> IResult<&[u8], (Curve, &[u8; 20]), Parse
         0x00 => Curve::Bip32Ed25519,
         0x01 => Curve::Secp256K1,
         0x02 => Curve::Secp256R1,
-        _ => return Err(ParserError::parser_invalid_pubkey_encoding.into()),
+        _ => return Err(ParserError::InvalidPublicKey.into()),
     };
 
     let out = array_ref!(hash, 0, 20);
@@ -207,7 +215,7 @@ pub fn public_key(input: &[u8]) -> IResult<&[u8], (Curve, &[u8]), ParserError> {
         0x00 => (Curve::Bip32Ed25519, take(32usize)),
         0x01 => (Curve::Secp256K1, take(33usize)),
         0x02 => (Curve::Secp256R1, take(33usize)),
-        _ => return Err(ParserError::parser_invalid_pubkey_encoding.into()),
+        _ => return Err(ParserError::InvalidPublicKey.into()),
     };
 
     let (rem, pk) = take_pk(rem)?;
@@ -263,7 +271,7 @@ impl Preemble {
             0x11 => Ok((rem, Self::TenderbakeBlock)),
             0x12 => Ok((rem, Self::TenderbakePreendorsement)),
             0x13 => Ok((rem, Self::TenderbakeEndorsement)),
-            _ => Err(ParserError::parser_unexpected_type.into()),
+            _ => Err(ParserError::InvalidPreemble.into()),
         }
     }
 }
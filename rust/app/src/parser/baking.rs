@@ -14,13 +14,20 @@
 *  limitations under the License.
 ********************************************************************************/
 mod tenderbake;
-pub use tenderbake::{EndorsementType, TenderbakeEndorsement};
+pub use tenderbake::{
+    EndorsementType, PerBlockVotes, TenderbakeBlockHeader, TenderbakeEndorsement, Vote,
+};
 
 mod emmy;
 pub use emmy::EmmyEndorsement;
 
 use crate::{
-    handlers::{handle_ui_message, hwm::WaterMark, parser_common::ParserError},
+    handlers::{
+        handle_ui_message,
+        hwm::WaterMark,
+        parser_common::ParserError,
+        sign_log::{Kind, Record},
+    },
     utils::ApduPanic,
 };
 use bolos::{pic_str, PIC};
@@ -47,6 +54,8 @@ pub struct BlockData<'b> {
     pub validation_pass: u8,
     pub operation_hash: &'b [u8; 32],
     pub fitness: Fitness<'b>,
+    /// Only present in tenderbake blocks
+    pub header: Option<TenderbakeBlockHeader<'b>>,
 }
 
 impl<'b> BlockData<'b> {
@@ -64,9 +73,30 @@ impl<'b> BlockData<'b> {
         let operation_hash = arrayref::array_ref!(operation_hash, 0, 32);
 
         let (rem, fitness_size) = be_u32(rem)?;
-        let (_, fitness) = take(fitness_size)(rem)?;
+        let (rem, fitness) = take(fitness_size)(rem)?;
+
+        let (fitness_rem, fitness) = Fitness::from_bytes(fitness)?;
+        //the whole fitness has to be consumed
+        if !fitness_rem.is_empty() {
+            return Err(ParserError::InvalidPayload.into());
+        }
+
+        let (rem, header) = match &fitness {
+            Fitness::Emmy(_) => (rem, None),
+            Fitness::Tenderbake(tb) => {
+                let (rem, header) = TenderbakeBlockHeader::from_bytes(rem)?;
 
-        let (rem, fitness) = Fitness::from_bytes(fitness)?;
+                //the fitness repeats the level, and the payload can't come from a later round
+                if tb.level != level
+                    || header.payload_round > tb.round
+                    || tb.locked_round.map(|locked| locked >= tb.round) == Some(true)
+                {
+                    return Err(ParserError::InvalidPayload.into());
+                }
+
+                (rem, Some(header))
+            }
+        };
 
         Ok((
             rem,
@@ -79,10 +109,15 @@ impl<'b> BlockData<'b> {
                 validation_pass,
                 operation_hash,
                 fitness,
+                header,
             },
         ))
     }
 
+    pub fn payload_hash(&self) -> Option<&'b [u8; 32]> {
+        self.header.as_ref().map(|header| header.payload_hash)
+    }
+
     #[inline(never)]
     pub fn validate_with_watermark(&self, hw: &WaterMark) -> bool {
         WaterMark::is_valid_blocklevel(self.level)
@@ -93,35 +128,67 @@ impl<'b> BlockData<'b> {
                 //stored watermark is Emmy, and this is tenderbake
                 // so we know this is always higher
                 (WaterMark::Emmy { .. }, Fitness::Tenderbake(_)) => true,
-                //higher level OR same level with higher round
+                //higher level OR same level with higher round,
+                // keeping the payload endorsed at this level
                 (
-                    WaterMark::Tenderbake { level, round, .. },
+                    WaterMark::Tenderbake {
+                        level,
+                        round,
+                        had_endorsement,
+                        payload_hash,
+                        payload_attested,
+                        ..
+                    },
                     Fitness::Tenderbake(TenderbakeFitness {
                         round: self_round, ..
                     }),
-                ) => self.level > *level || (self.level == *level && self_round > round),
+                ) => {
+                    let conflicting_payload = (*had_endorsement || *payload_attested)
+                        && payload_hash.as_ref() != self.payload_hash();
+
+                    self.level > *level
+                        || (self.level == *level && self_round > round && !conflicting_payload)
+                }
             }
     }
 
-    pub fn derive_watermark(&self) -> WaterMark {
+    /// Computes the watermark after signing this block over `hw`
+    ///
+    /// At the same level the endorsed payload stays recorded,
+    /// as only blocks with that payload are signed afterwards
+    pub fn derive_watermark(&self, hw: &WaterMark) -> WaterMark {
         match self.fitness {
             Fitness::Emmy(_) => WaterMark::Emmy {
                 level: self.level,
                 had_endorsement: false,
             },
-            Fitness::Tenderbake(TenderbakeFitness { round, .. }) => WaterMark::Tenderbake {
-                level: self.level,
-                had_endorsement: false,
-                round,
-                had_preendorsement: false,
-            },
+            Fitness::Tenderbake(TenderbakeFitness { round, .. }) => {
+                let payload_attested = match *hw {
+                    WaterMark::Tenderbake {
+                        level,
+                        had_endorsement,
+                        payload_attested,
+                        ..
+                    } => level == self.level && (had_endorsement || payload_attested),
+                    WaterMark::Emmy { .. } => false,
+                };
+
+                WaterMark::Tenderbake {
+                    level: self.level,
+                    had_endorsement: false,
+                    round,
+                    had_preendorsement: false,
+                    payload_hash: self.payload_hash().copied(),
+                    payload_attested,
+                }
+            }
         }
     }
 }
 
 impl<'b> DisplayableItem for BlockData<'b> {
     fn num_items(&self) -> usize {
-        1 + 2
+        1 + 2 + if self.header.is_some() { 5 } else { 0 }
     }
 
     #[inline(never)]
@@ -134,14 +201,14 @@ impl<'b> DisplayableItem for BlockData<'b> {
     ) -> Result<u8, ViewError> {
         use lexical_core::{write as itoa, Number};
 
-        match item_n {
-            0 => {
+        match (item_n, &self.header) {
+            (0, _) => {
                 let title_content = pic_str!(b"Type");
                 title[..title_content.len()].copy_from_slice(title_content);
 
                 handle_ui_message(&pic_str!(b"Blocklevel")[..], message, page)
             }
-            1 => {
+            (1, _) => {
                 let title_content = pic_str!(b"ChainID");
                 title[..title_content.len()].copy_from_slice(title_content);
 
@@ -149,7 +216,7 @@ impl<'b> DisplayableItem for BlockData<'b> {
 
                 handle_ui_message(itoa(self.chain_id, &mut itoa_buf), message, page)
             }
-            2 => {
+            (2, _) => {
                 let title_content = pic_str!(b"Blocklevel");
                 title[..title_content.len()].copy_from_slice(title_content);
 
@@ -157,6 +224,52 @@ impl<'b> DisplayableItem for BlockData<'b> {
 
                 handle_ui_message(itoa(self.level, &mut itoa_buf), message, page)
             }
+            (3, Some(_)) => {
+                let title_content = pic_str!(b"Round");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut itoa_buf = [0u8; u32::FORMATTED_SIZE_DECIMAL];
+
+                handle_ui_message(itoa(self.fitness.round(), &mut itoa_buf), message, page)
+            }
+            (4, Some(header)) => {
+                let title_content = pic_str!(b"Payload Round");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut itoa_buf = [0u8; u32::FORMATTED_SIZE_DECIMAL];
+
+                handle_ui_message(itoa(header.payload_round, &mut itoa_buf), message, page)
+            }
+            (5, Some(header)) => {
+                let title_content = pic_str!(b"Payload Hash");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let mut hex_buf = [0; 32 * 2];
+                //this is impossible that will error since the sizes are all checked
+                hex::encode_to_slice(header.payload_hash, &mut hex_buf).unwrap();
+
+                handle_ui_message(&hex_buf[..], message, page)
+            }
+            (6, Some(header)) => {
+                let title_content = pic_str!(b"Liquidity Baking");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(
+                    header.votes.liquidity_baking.as_str().as_bytes(),
+                    message,
+                    page,
+                )
+            }
+            (7, Some(header)) => {
+                let title_content = pic_str!(b"Adaptive Issuance");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(
+                    header.votes.adaptive_issuance.as_str().as_bytes(),
+                    message,
+                    page,
+                )
+            }
             _ => Err(ViewError::NoData),
         }
     }
@@ -257,6 +370,30 @@ impl<'b> EndorsementData<'b> {
         }
     }
 
+    /// Describes the endorsement for the signing log
+    pub fn log_record(&self) -> Record {
+        match self {
+            EndorsementData::Emmy(EmmyEndorsement {
+                chain_id, level, ..
+            }) => Record::new(Kind::Endorsement, *chain_id, *level, 0, &[]),
+            EndorsementData::Tenderbake(TenderbakeEndorsement {
+                chain_id,
+                ty,
+                level,
+                round,
+                block_payload_hash,
+                ..
+            }) => {
+                let kind = match ty {
+                    EndorsementType::PreEndorsement => Kind::Preendorsement,
+                    EndorsementType::Endorsement => Kind::Endorsement,
+                };
+
+                Record::new(kind, *chain_id, *level, *round, &block_payload_hash[..])
+            }
+        }
+    }
+
     pub fn derive_watermark(&self) -> WaterMark {
         match self {
             EndorsementData::Emmy(EmmyEndorsement { level, .. }) => WaterMark::Emmy {
@@ -267,23 +404,29 @@ impl<'b> EndorsementData<'b> {
                 level,
                 round,
                 ty: EndorsementType::Endorsement,
+                block_payload_hash,
                 ..
             }) => WaterMark::Tenderbake {
                 level: *level,
                 had_endorsement: true,
                 round: *round,
                 had_preendorsement: true,
+                payload_hash: Some(**block_payload_hash),
+                payload_attested: true,
             },
             EndorsementData::Tenderbake(TenderbakeEndorsement {
                 level,
                 round,
                 ty: EndorsementType::PreEndorsement,
+                block_payload_hash,
                 ..
             }) => WaterMark::Tenderbake {
                 level: *level,
                 had_endorsement: false,
                 round: *round,
                 had_preendorsement: true,
+                payload_hash: Some(**block_payload_hash),
+                payload_attested: false,
             },
         }
     }
@@ -370,10 +513,259 @@ mod tests {
 
             assert_eq!(preemble, Preemble::TenderbakeBlock);
 
-            let (_, blockdata) = BlockData::from_bytes(rem)
+            let (rem, blockdata) = BlockData::from_bytes(rem)
                 .unwrap_or_else(|e| panic!("unable to parse blockdata: {:?}, idx #{}", e, i));
             std::dbg!(&blockdata);
-            assert!(matches!(blockdata.fitness, Fitness::Tenderbake(_)));
+            assert!(rem.is_empty(), "trailing data in block, idx #{}", i);
+            match (&blockdata.fitness, &blockdata.header) {
+                (Fitness::Tenderbake(fitness), Some(header)) => {
+                    assert_eq!(fitness.level, blockdata.level);
+                    assert!(header.payload_round <= fitness.round);
+                }
+                _ => panic!("not a tenderbake block, idx #{}", i),
+            }
         }
     }
+
+    fn tenderbake_block(level: u32, round: u32, payload_hash: &[u8; 32]) -> std::vec::Vec<u8> {
+        tenderbake_block_with(level, round, payload_hash, |_| {})
+    }
+
+    /// Tenderbake block proposing its payload for the first time,
+    /// with `edit` applied to the protocol data following the context
+    fn tenderbake_block_with(
+        level: u32,
+        round: u32,
+        payload_hash: &[u8; 32],
+        edit: impl FnOnce(&mut std::vec::Vec<u8>),
+    ) -> std::vec::Vec<u8> {
+        let mut block = std::vec::Vec::new();
+        block.extend_from_slice(&0u32.to_be_bytes()); //chain id
+        block.extend_from_slice(&level.to_be_bytes());
+        block.push(42); //proto
+        block.extend_from_slice(&[0; 32]); //predecessor
+        block.extend_from_slice(&0u64.to_be_bytes()); //timestamp
+        block.push(0); //validation pass
+        block.extend_from_slice(&[0; 32]); //operation hash
+        block.extend_from_slice(&33u32.to_be_bytes()); //fitness size
+        block.extend_from_slice(&[0, 0, 0, 1, 2]); //tenderbake protocol
+        block.extend_from_slice(&[0, 0, 0, 4]);
+        block.extend_from_slice(&level.to_be_bytes());
+        block.extend_from_slice(&[0, 0, 0, 0]); //no locked round
+        block.extend_from_slice(&[0, 0, 0, 4, 0xff, 0xff, 0xff, 0xff]); //predecessor round 0
+        block.extend_from_slice(&[0, 0, 0, 4]);
+        block.extend_from_slice(&round.to_be_bytes());
+        block.extend_from_slice(&[0; 32]); //context
+
+        let mut protocol_data = std::vec::Vec::new();
+        protocol_data.extend_from_slice(payload_hash);
+        protocol_data.extend_from_slice(&round.to_be_bytes()); //payload round
+        protocol_data.extend_from_slice(&[0; 8]); //proof of work nonce
+        protocol_data.push(0); //no seed nonce hash
+        protocol_data.push(0); //per block votes
+        edit(&mut protocol_data);
+
+        block.extend_from_slice(&protocol_data);
+        block
+    }
+
+    fn tenderbake_endorsement(
+        ty: EndorsementType,
+        level: u32,
+        round: u32,
+        block_payload_hash: &[u8; 32],
+    ) -> EndorsementData<'_> {
+        EndorsementData::Tenderbake(TenderbakeEndorsement {
+            chain_id: 0,
+            branch: &[0; 32],
+            ty,
+            slot: 0,
+            level,
+            round,
+            block_payload_hash,
+            dal_attestation: None,
+        })
+    }
+
+    #[test]
+    fn endorsement_same_round_other_payload() {
+        let preendorsement =
+            tenderbake_endorsement(EndorsementType::PreEndorsement, 10, 1, &[1; 32]);
+        let hw = preendorsement.derive_watermark();
+
+        let endorsement = tenderbake_endorsement(EndorsementType::Endorsement, 10, 1, &[1; 32]);
+        assert!(endorsement.validate_with_watermark(&hw));
+
+        let conflicting = tenderbake_endorsement(EndorsementType::Endorsement, 10, 1, &[2; 32]);
+        assert!(!conflicting.validate_with_watermark(&hw));
+
+        //other rounds can have a different payload
+        let next_round = tenderbake_endorsement(EndorsementType::PreEndorsement, 10, 2, &[2; 32]);
+        assert!(next_round.validate_with_watermark(&hw));
+    }
+
+    #[test]
+    fn block_payload_after_endorsement() {
+        let block = tenderbake_block(10, 1, &[1; 32]);
+        let (_, block) = BlockData::from_bytes(&block).expect("couldn't parse block");
+        assert_eq!(block.payload_hash(), Some(&[1; 32]));
+
+        let hw = WaterMark::reset(0, true);
+        assert!(block.validate_with_watermark(&hw));
+
+        //preendorsing another payload for the block's round is refused
+        let hw = block.derive_watermark(&hw);
+        let preendorsement =
+            tenderbake_endorsement(EndorsementType::PreEndorsement, 10, 1, &[2; 32]);
+        assert!(!preendorsement.validate_with_watermark(&hw));
+
+        let endorsement = tenderbake_endorsement(EndorsementType::Endorsement, 10, 1, &[1; 32]);
+        assert!(endorsement.validate_with_watermark(&hw));
+        let hw = endorsement.derive_watermark();
+
+        //a later round can only propose the endorsed payload again
+        let reproposal = tenderbake_block(10, 2, &[1; 32]);
+        let (_, reproposal) = BlockData::from_bytes(&reproposal).expect("couldn't parse block");
+        assert!(reproposal.validate_with_watermark(&hw));
+
+        let conflicting = tenderbake_block(10, 2, &[2; 32]);
+        let (_, conflicting) = BlockData::from_bytes(&conflicting).expect("couldn't parse block");
+        assert!(!conflicting.validate_with_watermark(&hw));
+
+        let next_level = tenderbake_block(11, 0, &[2; 32]);
+        let (_, next_level) = BlockData::from_bytes(&next_level).expect("couldn't parse block");
+        assert!(next_level.validate_with_watermark(&hw));
+
+        //the endorsed payload is kept after signing the reproposal
+        let hw = reproposal.derive_watermark(&hw);
+        let data: [u8; 52] = hw.clone().into();
+        assert_eq!(WaterMark::from(&data), hw);
+
+        let endorsement = tenderbake_endorsement(EndorsementType::Endorsement, 10, 2, &[1; 32]);
+        assert!(endorsement.validate_with_watermark(&hw));
+
+        let conflicting = tenderbake_block(10, 3, &[2; 32]);
+        let (_, conflicting) = BlockData::from_bytes(&conflicting).expect("couldn't parse block");
+        assert!(!conflicting.validate_with_watermark(&hw));
+
+        //but not at the next level
+        let hw = next_level.derive_watermark(&hw);
+        let next_round = tenderbake_block(11, 1, &[3; 32]);
+        let (_, next_round) = BlockData::from_bytes(&next_round).expect("couldn't parse block");
+        assert!(next_round.validate_with_watermark(&hw));
+    }
+
+    #[test]
+    fn tenderbake_block_header() {
+        let block = tenderbake_block_with(10, 2, &[1; 32], |data| {
+            data[32..36].copy_from_slice(&0u32.to_be_bytes()); //reproposal of round 0
+            data[44] = 0xff;
+            data.splice(45..45, [7; 32].iter().copied());
+            *data.last_mut().unwrap() = 0b1001;
+        });
+
+        let (rem, block) = BlockData::from_bytes(&block).expect("couldn't parse block");
+        assert!(rem.is_empty());
+
+        let header = block.header.expect("tenderbake header wasn't parsed");
+        assert_eq!(header.payload_hash, &[1; 32]);
+        assert_eq!(header.payload_round, 0);
+        assert_eq!(header.seed_nonce_hash, Some(&[7; 32]));
+        assert_eq!(
+            header.votes,
+            PerBlockVotes {
+                liquidity_baking: Vote::Off,
+                adaptive_issuance: Vote::Pass,
+            }
+        );
+
+        match block.fitness {
+            Fitness::Tenderbake(fitness) => {
+                assert_eq!(fitness.level, 10);
+                assert_eq!(fitness.locked_round, None);
+                assert_eq!(fitness.predecessor_round, 0);
+                assert_eq!(fitness.round, 2);
+            }
+            _ => panic!("not a tenderbake fitness"),
+        }
+    }
+
+    #[test]
+    fn inconsistent_block_header() {
+        let invalid = |block: std::vec::Vec<u8>| {
+            assert_eq!(
+                BlockData::from_bytes(&block).unwrap_err(),
+                nom::Err::Error(ParserError::InvalidPayload)
+            );
+        };
+
+        //payload from a later round
+        invalid(tenderbake_block_with(10, 1, &[1; 32], |data| {
+            data[32..36].copy_from_slice(&2u32.to_be_bytes());
+        }));
+
+        //unknown votes
+        invalid(tenderbake_block_with(10, 1, &[1; 32], |data| {
+            *data.last_mut().unwrap() = 0b11;
+        }));
+        invalid(tenderbake_block_with(10, 1, &[1; 32], |data| {
+            *data.last_mut().unwrap() = 0b10000;
+        }));
+
+        //the level of the fitness is another
+        let mut block = tenderbake_block(10, 1, &[1; 32]);
+        block[95..99].copy_from_slice(&11u32.to_be_bytes());
+        invalid(block);
+    }
+
+    #[test]
+    fn fitness_trailing_bytes() {
+        let mut block = tenderbake_block(10, 1, &[1; 32]);
+        BlockData::from_bytes(&block).expect("couldn't parse block");
+
+        //one more byte in the fitness, after the round
+        block[82..86].copy_from_slice(&34u32.to_be_bytes());
+        block.insert(86 + 33, 0);
+
+        assert_eq!(
+            BlockData::from_bytes(&block).unwrap_err(),
+            nom::Err::Error(ParserError::InvalidPayload)
+        );
+    }
+
+    #[test]
+    fn emmy_block_fitness() {
+        let mut block = std::vec::Vec::new();
+        block.extend_from_slice(&0u32.to_be_bytes()); //chain id
+        block.extend_from_slice(&10u32.to_be_bytes()); //level
+        block.push(42); //proto
+        block.extend_from_slice(&[0; 32]); //predecessor
+        block.extend_from_slice(&0u64.to_be_bytes()); //timestamp
+        block.push(0); //validation pass
+        block.extend_from_slice(&[0; 32]); //operation hash
+        block.extend_from_slice(&17u32.to_be_bytes()); //fitness size
+        block.extend_from_slice(&[0, 0, 0, 1, 1]); //emmy protocol
+        block.extend_from_slice(&[0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 1]);
+        block.extend_from_slice(&[0xAA; 4]); //rest of the header
+
+        let (rem, block) = BlockData::from_bytes(&block).expect("couldn't parse block");
+        assert_eq!(rem, &[0xAA; 4]);
+        match block.fitness {
+            Fitness::Emmy(fitness) => assert_eq!(fitness.fitness().len(), 12),
+            _ => panic!("not an emmy block"),
+        }
+    }
+
+    #[test]
+    fn tenderbake_watermark_roundtrip() {
+        let hw = tenderbake_endorsement(EndorsementType::Endorsement, 10, 1, &[3; 32])
+            .derive_watermark();
+
+        let data: [u8; 52] = hw.clone().into();
+        assert_eq!(WaterMark::from(&data), hw);
+
+        let hw = WaterMark::reset(10, true);
+        let data: [u8; 52] = hw.clone().into();
+        assert_eq!(WaterMark::from(&data), hw);
+    }
 }
@@ -17,6 +17,7 @@
 use nom::{
     branch::alt,
     bytes::complete::{tag, take},
+    combinator::rest,
     number::complete::be_u32,
     IResult,
 };
@@ -84,12 +85,14 @@ impl<'b> EmmyFitness<'b> {
             tag(&[Self::PROTOCOL_VERSION_EMMY_ZERO_TO_FOUR]),
             tag(&[Self::PROTOCOL_VERSION_EMMY_FIVE_TO_ELEVEN]),
         ))(rem)?;
+        //the rest of the fitness is opaque
+        let (rem, fitness) = rest(rem)?;
 
         Ok((
             rem,
             Self {
                 proto: proto[0],
-                fitness: rem,
+                fitness,
             },
         ))
     }
@@ -19,7 +19,10 @@ use nom::{
     IResult,
 };
 
-use crate::handlers::{hwm::WaterMark, parser_common::ParserError};
+use crate::{
+    handlers::{hwm::WaterMark, parser_common::ParserError},
+    parser::{boolean, Zarith},
+};
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -31,11 +34,13 @@ pub enum EndorsementType {
 impl EndorsementType {
     const PREENDORSEMENT_TAG: u8 = 20;
     const ENDORSEMENT_TAG: u8 = 21;
+    /// Endorsement followed by the DAL content
+    const ENDORSEMENT_WITH_DAL_TAG: u8 = 23;
 
     pub fn from_tag(tag: u8) -> Option<Self> {
         match tag {
             Self::PREENDORSEMENT_TAG => Some(Self::PreEndorsement),
-            Self::ENDORSEMENT_TAG => Some(Self::Endorsement),
+            Self::ENDORSEMENT_TAG | Self::ENDORSEMENT_WITH_DAL_TAG => Some(Self::Endorsement),
             _ => None,
         }
     }
@@ -50,6 +55,8 @@ pub struct TenderbakeEndorsement<'b> {
     pub level: u32,
     pub round: u32,
     pub block_payload_hash: &'b [u8; 32],
+    /// Bitset of the DAL slots attested, only in endorsements with DAL content
+    pub dal_attestation: Option<Zarith<'b>>,
 }
 
 impl<'b> TenderbakeEndorsement<'b> {
@@ -59,8 +66,8 @@ impl<'b> TenderbakeEndorsement<'b> {
         let (rem, branch) = take(32usize)(rem)?;
         let branch = arrayref::array_ref!(branch, 0, 32);
 
-        let (rem, tag) = le_u8(rem)?;
-        let tag = EndorsementType::from_tag(tag).ok_or(ParserError::InvalidEndorsementType)?;
+        let (rem, raw_tag) = le_u8(rem)?;
+        let tag = EndorsementType::from_tag(raw_tag).ok_or(ParserError::InvalidEndorsementType)?;
 
         let (rem, slot) = be_u16(rem)?;
         let (rem, level) = be_u32(rem)?;
@@ -68,6 +75,14 @@ impl<'b> TenderbakeEndorsement<'b> {
         let (rem, block_payload_hash) = take(32usize)(rem)?;
         let block_payload_hash = arrayref::array_ref!(block_payload_hash, 0, 32);
 
+        //the DAL content doesn't change how the endorsement is checked
+        let (rem, dal_attestation) = if raw_tag == EndorsementType::ENDORSEMENT_WITH_DAL_TAG {
+            let (rem, bitset) = Zarith::from_bytes(rem, false)?;
+            (rem, Some(bitset))
+        } else {
+            (rem, None)
+        };
+
         Ok((
             rem,
             Self {
@@ -78,6 +93,7 @@ impl<'b> TenderbakeEndorsement<'b> {
                 round,
                 ty: tag,
                 block_payload_hash,
+                dal_attestation,
             },
         ))
     }
@@ -94,20 +110,29 @@ impl<'b> TenderbakeEndorsement<'b> {
                     had_endorsement,
                     round,
                     had_preendorsement,
+                    payload_hash,
+                    ..
                 } => {
+                    //the payload signed last at this level and round, if any
+                    let same_payload = payload_hash
+                        .map(|hash| &hash == self.block_payload_hash)
+                        .unwrap_or(true);
+
                     //1. higher level OR same level with higher round
-                    //2. OR, same level, same round, but no endorsement done
+                    //2. OR, same level, same round, same payload, but no endorsement done
                     // and this is an endosement
-                    //3. OR, same level, same round, but no preendorsement OR endorsement done
+                    //3. OR, same level, same round, same payload, but no preendorsement OR endorsement done
                     // and this is a pre endorsement
                     self.level > level
                         || (self.level == level && self.round > round)
                         || (self.level == level
                             && self.round == round
+                            && same_payload
                             && self.ty == EndorsementType::Endorsement
                             && !had_endorsement)
                         || (self.level == level
                             && self.round == round
+                            && same_payload
                             && self.ty == EndorsementType::PreEndorsement
                             && !had_endorsement
                             && !had_preendorsement)
@@ -120,6 +145,10 @@ impl<'b> TenderbakeEndorsement<'b> {
 #[cfg_attr(test, derive(Debug))]
 pub struct TenderbakeFitness<'b> {
     fitness: &'b [u8],
+    pub level: u32,
+    /// Round of the payload the baker is locked on, if any
+    pub locked_round: Option<u32>,
+    pub predecessor_round: u32,
     pub round: u32,
 }
 
@@ -128,23 +157,153 @@ impl<'b> TenderbakeFitness<'b> {
 
     #[inline(never)]
     pub fn from_bytes(bytes: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
-        //TODO: figure out what this field is
-        let (rem, _pad) = take(4usize)(bytes)?;
-        let (rem, _proto) = tag(&[Self::PROTOCOL_VERSION_TENDERBAKE])(rem)?;
+        //each component of the fitness is prefixed by its length
+        let (rem, _) = tag(&[0, 0, 0, 1, Self::PROTOCOL_VERSION_TENDERBAKE])(bytes)?;
+        let fitness = rem;
+
+        let (rem, level) = Self::component(rem)?;
+
+        let (rem, locked_round_len) = be_u32(rem)?;
+        let (rem, locked_round) = match locked_round_len {
+            0 => (rem, None),
+            4 => {
+                let (rem, round) = be_u32(rem)?;
+                (rem, Some(round))
+            }
+            _ => return Err(ParserError::InvalidPayload.into()),
+        };
 
-        //the round is in the last 4 bytes
-        let (_, round) = be_u32(&rem[rem.len() - 4..])?;
+        //encoded as `-1 - predecessor_round`
+        let (rem, predecessor_round) = Self::component(rem)?;
+        let (rem, round) = Self::component(rem)?;
 
         Ok((
             rem,
             Self {
-                fitness: rem,
+                fitness,
+                level,
+                locked_round,
+                predecessor_round: !predecessor_round,
                 round,
             },
         ))
     }
 
+    fn component(bytes: &[u8]) -> IResult<&[u8], u32, ParserError> {
+        let (rem, _) = tag(&[0, 0, 0, 4])(bytes)?;
+        be_u32(rem)
+    }
+
     pub fn fitness(&self) -> &[u8] {
         self.fitness
     }
 }
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Vote {
+    On = 0,
+    Off = 1,
+    Pass = 2,
+}
+
+impl Vote {
+    fn from_u8(vote: u8) -> Option<Self> {
+        match vote {
+            0 => Some(Self::On),
+            1 => Some(Self::Off),
+            2 => Some(Self::Pass),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        use bolos::{pic_str, PIC};
+
+        match self {
+            Self::On => pic_str!("On"!),
+            Self::Off => pic_str!("Off"!),
+            Self::Pass => pic_str!("Pass"!),
+        }
+    }
+}
+
+/// Toggle votes cast with every block
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PerBlockVotes {
+    pub liquidity_baking: Vote,
+    pub adaptive_issuance: Vote,
+}
+
+impl PerBlockVotes {
+    /// Both votes are packed in a single byte:
+    /// the liquidity baking vote in bits 0-1 and the adaptive issuance vote in bits 2-3
+    pub fn from_bytes(bytes: &[u8]) -> IResult<&[u8], Self, ParserError> {
+        let (rem, votes) = le_u8(bytes)?;
+
+        let liquidity_baking = Vote::from_u8(votes & 0b11);
+        let adaptive_issuance = Vote::from_u8(votes >> 2);
+
+        match (liquidity_baking, adaptive_issuance) {
+            (Some(liquidity_baking), Some(adaptive_issuance)) => Ok((
+                rem,
+                Self {
+                    liquidity_baking,
+                    adaptive_issuance,
+                },
+            )),
+            _ => Err(ParserError::InvalidPayload.into()),
+        }
+    }
+}
+
+/// Rest of a tenderbake block header, following the fitness
+#[derive(Clone)]
+#[cfg_attr(test, derive(Debug))]
+pub struct TenderbakeBlockHeader<'b> {
+    /// Last field of the shell header
+    pub context: &'b [u8; 32],
+    pub payload_hash: &'b [u8; 32],
+    pub payload_round: u32,
+    pub proof_of_work_nonce: &'b [u8; 8],
+    pub seed_nonce_hash: Option<&'b [u8; 32]>,
+    pub votes: PerBlockVotes,
+}
+
+impl<'b> TenderbakeBlockHeader<'b> {
+    #[inline(never)]
+    pub fn from_bytes(bytes: &'b [u8]) -> IResult<&'b [u8], Self, ParserError> {
+        let (rem, context) = take(32usize)(bytes)?;
+        let context = arrayref::array_ref!(context, 0, 32);
+
+        let (rem, payload_hash) = take(32usize)(rem)?;
+        let payload_hash = arrayref::array_ref!(payload_hash, 0, 32);
+
+        let (rem, payload_round) = be_u32(rem)?;
+
+        let (rem, proof_of_work_nonce) = take(8usize)(rem)?;
+        let proof_of_work_nonce = arrayref::array_ref!(proof_of_work_nonce, 0, 8);
+
+        let (rem, has_seed_nonce_hash) = boolean(rem)?;
+        let (rem, seed_nonce_hash) = if has_seed_nonce_hash {
+            let (rem, hash) = take(32usize)(rem)?;
+            (rem, Some(arrayref::array_ref!(hash, 0, 32)))
+        } else {
+            (rem, None)
+        };
+
+        let (rem, votes) = PerBlockVotes::from_bytes(rem)?;
+
+        Ok((
+            rem,
+            Self {
+                context,
+                payload_hash,
+                payload_round,
+                proof_of_work_nonce,
+                seed_nonce_hash,
+                votes,
+            },
+        ))
+    }
+}
@@ -191,6 +191,7 @@ pub use operation_type::OperationType;
 
 mod activate_account;
 mod ballot;
+mod consensus_key;
 mod delegation;
 mod double_baking_evidence;
 mod endorsement;
@@ -199,10 +200,13 @@ mod origination;
 mod proposals;
 mod reveal;
 mod seed_nonce_revelation;
+mod set_deposits_limit;
+mod summary;
 mod transfer;
 
 pub use activate_account::ActivateAccount;
 pub use ballot::Ballot;
+pub use consensus_key::{DrainDelegate, UpdateConsensusKey};
 pub use delegation::Delegation;
 pub use double_baking_evidence::DoubleBakingEvidence;
 pub use endorsement::{DoubleEndorsementEvidence, Endorsement, EndorsementWithSlot};
@@ -211,7 +215,9 @@ pub use origination::Origination;
 pub use proposals::Proposals;
 pub use reveal::Reveal;
 pub use seed_nonce_revelation::SeedNonceRevelation;
-pub use transfer::Transfer;
+pub use set_deposits_limit::SetDepositsLimit;
+pub use summary::Summary;
+pub use transfer::{Entrypoint, Transfer};
 
 #[derive(Clone, Copy)]
 #[cfg_attr(test, derive(Debug))]
@@ -280,7 +286,7 @@ impl<'b> ContractID<'b> {
     pub const BASE58_LEN: usize = 37;
 
     #[cfg(test)]
-    fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
+    pub fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
         use nom::{dbg_basic, take, tuple as tuplem};
         use std::{eprintln, println};
 
@@ -303,13 +309,13 @@ impl<'b> ContractID<'b> {
                     input.len() - rem.len(),
                     input
                 );
-                Err(ParserError::parser_invalid_address.into())
+                Err(ParserError::InvalidAddress.into())
             }
         }
     }
 
     #[cfg(not(test))]
-    fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
+    pub fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
         let (rem, tag) = le_u8(input)?;
         match tag {
             0x00 => {
@@ -322,7 +328,7 @@ impl<'b> ContractID<'b> {
                 let hash = arrayref::array_ref!(hash, 0, 20);
                 Ok((rem, Self::Originated(hash)))
             }
-            _ => Err(ParserError::parser_invalid_address.into()),
+            _ => Err(ParserError::InvalidAddress.into()),
         }
     }
 
@@ -0,0 +1,364 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use core::{mem::MaybeUninit, ptr::addr_of_mut};
+use nom::{call, do_parse, IResult};
+use zemu_sys::ViewError;
+
+use crate::{
+    crypto::Curve,
+    handlers::{
+        address_book::render_address, handle_ui_message, intstr_to_fpstr_inplace,
+        parser_common::ParserError, public_key::Addr, settings::Settings,
+    },
+    parser::{operations::ContractID, public_key, public_key_hash, DisplayableItem, Zarith},
+};
+
+use super::reveal::{pk_to_base58, MAX_PK_BASE58_LEN};
+
+#[derive(Clone, Copy, PartialEq, property::Property)]
+#[cfg_attr(test, derive(Debug))]
+#[property(mut(disable), get(public), set(disable))]
+pub struct UpdateConsensusKey<'b> {
+    source: (Curve, &'b [u8; 20]),
+    fee: Zarith<'b>,
+    counter: Zarith<'b>,
+    gas_limit: Zarith<'b>,
+    storage_limit: Zarith<'b>,
+    public_key: (Curve, &'b [u8]),
+}
+
+impl<'b> UpdateConsensusKey<'b> {
+    /// Number of items shown outside of expert mode
+    ///
+    /// Type, source, public key and fee
+    pub const BASIC_ITEMS: usize = 4;
+
+    #[inline(never)]
+    pub fn from_bytes(input: &'b [u8]) -> IResult<&'b [u8], Self, ParserError> {
+        let (rem, (source, fee, counter, gas_limit, storage_limit, public_key)) = do_parse! {input,
+            source: public_key_hash >>
+            fee: call!(Zarith::from_bytes, false) >>
+            counter: call!(Zarith::from_bytes, false) >>
+            gas_limit: call!(Zarith::from_bytes, false) >>
+            storage_limit: call!(Zarith::from_bytes, false) >>
+            public_key: public_key >>
+            (source, fee, counter, gas_limit, storage_limit, public_key)
+        }?;
+
+        Ok((
+            rem,
+            Self {
+                source,
+                fee,
+                counter,
+                gas_limit,
+                storage_limit,
+                public_key,
+            },
+        ))
+    }
+
+    #[inline(never)]
+    pub fn from_bytes_into(
+        input: &'b [u8],
+        out: &mut MaybeUninit<Self>,
+    ) -> Result<&'b [u8], nom::Err<ParserError>> {
+        let (rem, data) = Self::from_bytes(input)?;
+
+        let out = out.as_mut_ptr();
+        //good ptr, no uninit reads
+        unsafe {
+            addr_of_mut!((*out).source).write(data.source);
+            addr_of_mut!((*out).fee).write(data.fee);
+            addr_of_mut!((*out).counter).write(data.counter);
+            addr_of_mut!((*out).gas_limit).write(data.gas_limit);
+            addr_of_mut!((*out).storage_limit).write(data.storage_limit);
+            addr_of_mut!((*out).public_key).write(data.public_key);
+        }
+
+        Ok(rem)
+    }
+}
+
+impl<'b> DisplayableItem for UpdateConsensusKey<'b> {
+    fn num_items(&self) -> usize {
+        1 + 6
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &self,
+        item_n: u8,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use bolos::{pic_str, PIC};
+        use lexical_core::{write as itoa, Number};
+
+        let mut zarith_buf = [0; usize::FORMATTED_SIZE_DECIMAL + 2]; //+2 for decimal formatting
+
+        match item_n {
+            //Homepage
+            0 => {
+                let title_content = pic_str!(b"Type");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(&pic_str!(b"Update Consensus Key")[..], message, page)
+            }
+            //source
+            1 => {
+                let title_content = pic_str!(b"Source");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (crv, hash) = self.source();
+                let addr = Addr::from_hash(hash, *crv).map_err(|_| ViewError::Unknown)?;
+
+                let (len, mex) = addr.base58();
+                handle_ui_message(&mex[..len], message, page)
+            }
+            //public key
+            2 => {
+                let title_content = pic_str!("Consensus Key");
+                title[..title_content.len()].copy_from_slice(title_content.as_bytes());
+
+                let mut public_key = [0; MAX_PK_BASE58_LEN];
+                let pk_len = pk_to_base58(self.public_key, &mut public_key)
+                    .map_err(|_| ViewError::Unknown)?;
+
+                handle_ui_message(&public_key[..pk_len], message, page)
+            }
+            //fee
+            3 => {
+                let title_content = pic_str!(b"Fee");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, fee) = self.fee().read_as::<usize>().ok_or(ViewError::Unknown)?;
+
+                itoa(fee, &mut zarith_buf);
+                handle_ui_message(
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
+                    message,
+                    page,
+                )
+            }
+            //gas_limit
+            4 => {
+                let title_content = pic_str!(b"Gas Limit");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, gas_limit) = self
+                    .gas_limit()
+                    .read_as::<usize>()
+                    .ok_or(ViewError::Unknown)?;
+
+                handle_ui_message(itoa(gas_limit, &mut zarith_buf), message, page)
+            }
+            //storage_limit
+            5 => {
+                let title_content = pic_str!(b"Storage Limit");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, storage_limit) = self
+                    .storage_limit()
+                    .read_as::<usize>()
+                    .ok_or(ViewError::Unknown)?;
+
+                handle_ui_message(itoa(storage_limit, &mut zarith_buf), message, page)
+            }
+            //counter
+            6 => {
+                let title_content = pic_str!(b"Counter");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, counter) = self
+                    .counter()
+                    .read_as::<usize>()
+                    .ok_or(ViewError::Unknown)?;
+
+                handle_ui_message(itoa(counter, &mut zarith_buf), message, page)
+            }
+            _ => Err(ViewError::NoData),
+        }
+    }
+}
+
+/// Moves all the spendable balance of `delegate` to `destination`,
+/// signed with the active consensus key of the delegate
+#[derive(Clone, Copy, PartialEq, property::Property)]
+#[cfg_attr(test, derive(Debug))]
+#[property(mut(disable), get(public), set(disable))]
+pub struct DrainDelegate<'b> {
+    consensus_key: (Curve, &'b [u8; 20]),
+    delegate: (Curve, &'b [u8; 20]),
+    destination: (Curve, &'b [u8; 20]),
+}
+
+impl<'b> DrainDelegate<'b> {
+    #[inline(never)]
+    pub fn from_bytes(input: &'b [u8]) -> IResult<&'b [u8], Self, ParserError> {
+        let (rem, (consensus_key, delegate, destination)) = do_parse! {input,
+            consensus_key: public_key_hash >>
+            delegate: public_key_hash >>
+            destination: public_key_hash >>
+            (consensus_key, delegate, destination)
+        }?;
+
+        Ok((
+            rem,
+            Self {
+                consensus_key,
+                delegate,
+                destination,
+            },
+        ))
+    }
+
+    #[inline(never)]
+    pub fn from_bytes_into(
+        input: &'b [u8],
+        out: &mut MaybeUninit<Self>,
+    ) -> Result<&'b [u8], nom::Err<ParserError>> {
+        let (rem, data) = Self::from_bytes(input)?;
+
+        //good ptr
+        unsafe {
+            out.as_mut_ptr().write(data);
+        }
+
+        Ok(rem)
+    }
+}
+
+impl<'b> DisplayableItem for DrainDelegate<'b> {
+    fn num_items(&self) -> usize {
+        1 + 3
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &self,
+        item_n: u8,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use bolos::{pic_str, PIC};
+
+        match item_n {
+            //Homepage
+            0 => {
+                let title_content = pic_str!(b"Type");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(&pic_str!(b"Drain Delegate")[..], message, page)
+            }
+            //consensus key
+            1 => {
+                let title_content = pic_str!(b"Consensus Key");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (crv, hash) = self.consensus_key;
+                render_address(ContractID::Implicit(crv, hash), message, page)
+            }
+            //delegate
+            2 => {
+                let title_content = pic_str!(b"Delegate");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (crv, hash) = self.delegate;
+                render_address(ContractID::Implicit(crv, hash), message, page)
+            }
+            //destination
+            3 => {
+                let title_content = pic_str!(b"Destination");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (crv, hash) = self.destination;
+                render_address(ContractID::Implicit(crv, hash), message, page)
+            }
+            _ => Err(ViewError::NoData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrayref::array_ref;
+
+    use crate::{crypto::Curve, parser::Zarith};
+
+    use super::{DrainDelegate, UpdateConsensusKey};
+
+    #[test]
+    fn update_consensus_key() {
+        const INPUT_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd\
+                                 904e\
+                                 01\
+                                 0a\
+                                 0a\
+                                 0103a5e05ef0e6e6bba15f1ffbd25b7ef6f2d1c7bbdd4ff5b6fd1df21a2b07fd9a11";
+
+        let input = hex::decode(INPUT_HEX).expect("invalid input hex");
+
+        let (rem, parsed) =
+            UpdateConsensusKey::from_bytes(&input).expect("failed to parse update consensus key");
+        assert_eq!(rem.len(), 0);
+
+        let expected = UpdateConsensusKey {
+            source: (Curve::Bip32Ed25519, array_ref!(input, 1, 20)),
+            fee: Zarith {
+                is_negative: None,
+                bytes: &input[21..23],
+            },
+            counter: Zarith {
+                is_negative: None,
+                bytes: &input[23..24],
+            },
+            gas_limit: Zarith {
+                is_negative: None,
+                bytes: &input[24..25],
+            },
+            storage_limit: Zarith {
+                is_negative: None,
+                bytes: &input[25..26],
+            },
+            public_key: (Curve::Secp256K1, array_ref!(input, 26 + 1, 33)),
+        };
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn drain_delegate() {
+        const INPUT_HEX: &str = "0135e993d8c7aaa42b5e3ccd86a33390ececc73abd\
+                                 00a2ea1c2be4bfdd1f4aa7a5d91b0cf1c1bd3e5e9a\
+                                 02c1fd0e1d2b8a2e3b1de4c1c1d0f2e3dcb6a2d1ef";
+
+        let input = hex::decode(INPUT_HEX).expect("invalid input hex");
+
+        let (rem, parsed) =
+            DrainDelegate::from_bytes(&input).expect("failed to parse drain delegate");
+        assert_eq!(rem.len(), 0);
+
+        let expected = DrainDelegate {
+            consensus_key: (Curve::Secp256K1, array_ref!(input, 1, 20)),
+            delegate: (Curve::Bip32Ed25519, array_ref!(input, 22, 20)),
+            destination: (Curve::Secp256R1, array_ref!(input, 43, 20)),
+        };
+        assert_eq!(parsed, expected);
+    }
+}
@@ -20,9 +20,10 @@ use zemu_sys::ViewError;
 use crate::{
     crypto::Curve,
     handlers::{
-        handle_ui_message, intstr_to_fpstr_inplace, parser_common::ParserError, public_key::Addr,
+        address_book::render_address, handle_ui_message, intstr_to_fpstr_inplace,
+        parser_common::ParserError, public_key::Addr, settings::Settings,
     },
-    parser::{boolean, public_key_hash, DisplayableItem, Zarith},
+    parser::{boolean, operations::ContractID, public_key_hash, DisplayableItem, Zarith},
 };
 
 #[derive(Clone, Copy, PartialEq, property::Property)]
@@ -38,6 +39,11 @@ pub struct Delegation<'b> {
 }
 
 impl<'b> Delegation<'b> {
+    /// Number of items shown outside of expert mode
+    ///
+    /// Type, source, delegate and fee
+    pub const BASIC_ITEMS: usize = 4;
+
     #[inline(never)]
     pub fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
         crate::sys::zemu_log_stack("Delegation::from_bytes\x00");
@@ -150,15 +156,7 @@ impl<'a> DisplayableItem for Delegation<'a> {
 
                 match self.delegate {
                     Some((crv, hash)) => {
-                        match baker_lookup(arrayref::array_ref!(crv.to_hash_prefix(), 0, 3), hash) {
-                            Ok(name) => handle_ui_message(name.as_bytes(), message, page),
-                            Err(_) => {
-                                let addr =
-                                    Addr::from_hash(hash, crv).map_err(|_| ViewError::Unknown)?;
-                                let (len, mex) = addr.base58();
-                                handle_ui_message(&mex[..len], message, page)
-                            }
-                        }
+                        render_address(ContractID::Implicit(crv, hash), message, page)
                     }
                     None => handle_ui_message(&pic_str!(b"<REVOKED>")[..], message, page),
                 }
@@ -172,7 +170,8 @@ impl<'a> DisplayableItem for Delegation<'a> {
 
                 itoa(fee, &mut zarith_buf);
                 handle_ui_message(
-                    intstr_to_fpstr_inplace(&mut zarith_buf, 6).map_err(|_| ViewError::Unknown)?,
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
                     message,
                     page,
                 )
@@ -312,11 +311,3 @@ mod tests {
         assert_eq!(parsed, expected);
     }
 }
-
-mod known_bakers {
-    use bolos::PIC;
-    use zemu_sys::zemu_log_stack;
-
-    ledger_tezos_derive::unroll!("vendor/BakersRegistryCoreUnfilteredData.json");
-}
-use known_bakers::baker_lookup;
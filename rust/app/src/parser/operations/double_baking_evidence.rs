@@ -158,7 +158,7 @@ impl<'b> FullBlockHeader<'b> {
             let (rem, length) = be_u32(rem)?;
             let length = length as usize;
             if length > rem.len() {
-                return Err(ParserError::parser_value_out_of_range.into());
+                return Err(ParserError::ValueOutOfRange.into());
             }
 
             //get the fitnesses slice
@@ -127,7 +127,7 @@ impl<'b> EndorsementWithSlot<'b> {
         };
         let (rem, endorsement_tag) = be_u8(rem)?;
         if endorsement_tag != 0x00 {
-            return Err(ParserError::parser_invalid_transaction_payload.into());
+            return Err(ParserError::InvalidPayload.into());
         }
 
         let (rem2, endorsement) = Endorsement::from_bytes(rem)?;
@@ -158,7 +158,7 @@ impl<'b> EndorsementWithSlot<'b> {
         };
         let (rem, endorsement_tag) = be_u8(rem)?;
         if endorsement_tag != 0x00 {
-            return Err(ParserError::parser_invalid_transaction_payload.into());
+            return Err(ParserError::InvalidPayload.into());
         }
 
         let (rem2, endorsement) = Endorsement::from_bytes(rem)?;
@@ -166,7 +166,7 @@ impl<'b> EndorsementWithSlot<'b> {
         let rem_len = rem.len() - rem2.len();
         let length = length
             .and_then(|len| len.checked_sub(rem_len))
-            .ok_or(ParserError::parser_value_out_of_range)?;
+            .ok_or(ParserError::ValueOutOfRange)?;
         let (rem, sig) = take(length)(rem2)?;
         let (rem, slot) = be_u16(rem)?;
 
@@ -277,7 +277,7 @@ impl<'b> DoubleEndorsementEvidence<'b> {
         };
         let (rem, endorsement_tag) = be_u8(rem)?;
         if endorsement_tag != 0x00 {
-            return Err(ParserError::parser_invalid_transaction_payload.into());
+            return Err(ParserError::InvalidPayload.into());
         }
 
         let (rem2, first_endorsement) = Endorsement::from_bytes(rem)?;
@@ -285,7 +285,7 @@ impl<'b> DoubleEndorsementEvidence<'b> {
         let rem_len = rem.len() - rem2.len();
         let length = length
             .and_then(|len| len.checked_sub(rem_len))
-            .ok_or(ParserError::parser_value_out_of_range)?;
+            .ok_or(ParserError::ValueOutOfRange)?;
         let (rem, first_signature) = take(length)(rem2)?;
 
         // --------- Second endorsement
@@ -297,7 +297,7 @@ impl<'b> DoubleEndorsementEvidence<'b> {
         };
         let (rem, endorsement_tag) = be_u8(rem)?;
         if endorsement_tag != 0x00 {
-            return Err(ParserError::parser_invalid_transaction_payload.into());
+            return Err(ParserError::InvalidPayload.into());
         }
 
         let (rem2, second_endorsement) = Endorsement::from_bytes(rem)?;
@@ -305,7 +305,7 @@ impl<'b> DoubleEndorsementEvidence<'b> {
         let rem_len = rem.len() - rem2.len();
         let length = length
             .and_then(|len| len.checked_sub(rem_len))
-            .ok_or(ParserError::parser_value_out_of_range)?;
+            .ok_or(ParserError::ValueOutOfRange)?;
         let (rem, second_signature) = take(length)(rem2)?;
 
         let (rem, slot) = be_u16(rem)?;
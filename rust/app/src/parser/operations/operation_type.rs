@@ -17,6 +17,8 @@ use super::*;
 
 use core::ptr::addr_of_mut;
 
+use crate::parser::Zarith;
+
 #[repr(u8)] //IMPORTANT
             //see OperationType comment
 enum OperationTypeKind {
@@ -30,6 +32,9 @@ enum OperationTypeKind {
     Origination,
     ActivateAccount,
     FailingNoop,
+    UpdateConsensusKey,
+    DrainDelegate,
+    SetDepositsLimit,
 }
 
 #[repr(C)]
@@ -63,6 +68,15 @@ struct ActivateAccountVariant<'b>(OperationTypeKind, ActivateAccount<'b>);
 #[repr(C)]
 struct FailingNoopVariant<'b>(OperationTypeKind, FailingNoop<'b>);
 
+#[repr(C)]
+struct UpdateConsensusKeyVariant<'b>(OperationTypeKind, UpdateConsensusKey<'b>);
+
+#[repr(C)]
+struct DrainDelegateVariant<'b>(OperationTypeKind, DrainDelegate<'b>);
+
+#[repr(C)]
+struct SetDepositsLimitVariant<'b>(OperationTypeKind, SetDepositsLimit<'b>);
+
 #[derive(Clone, Copy)]
 //ABSOLUTELY IMPORTANT, DO NOT CHANGE THIS
 #[repr(u8)]
@@ -79,7 +93,11 @@ pub enum OperationType<'b> {
     Origination(Origination<'b>),
     ActivateAccount(ActivateAccount<'b>),
     FailingNoop(FailingNoop<'b>),
-    UnknownOp(&'b [u8]),
+    UpdateConsensusKey(UpdateConsensusKey<'b>),
+    DrainDelegate(DrainDelegate<'b>),
+    SetDepositsLimit(SetDepositsLimit<'b>),
+    //(tag, bytes following the tag)
+    UnknownOp(u8, &'b [u8]),
     #[cfg(not(test))]
     AnonymousOp(()),
     #[cfg(test)]
@@ -157,6 +175,19 @@ impl<'b> OperationType<'b> {
                 }
                 rem
             }
+            0x09 => {
+                let out = out.as_mut_ptr() as *mut DrainDelegateVariant;
+                //valid pointer
+                let data = unsafe { &mut *addr_of_mut!((*out).1).cast() };
+
+                let rem = DrainDelegate::from_bytes_into(rem, data)?;
+
+                //pointer is valid
+                unsafe {
+                    addr_of_mut!((*out).0).write(OperationTypeKind::DrainDelegate);
+                }
+                rem
+            }
             0x0A => {
                 let out = out.as_mut_ptr() as *mut EndorsementWithSlotVariant;
                 //valid pointer
@@ -235,8 +266,34 @@ impl<'b> OperationType<'b> {
                 }
                 rem
             }
+            0x70 => {
+                let out = out.as_mut_ptr() as *mut SetDepositsLimitVariant;
+                //valid pointer
+                let data = unsafe { &mut *addr_of_mut!((*out).1).cast() };
+
+                let rem = SetDepositsLimit::from_bytes_into(rem, data)?;
+
+                //pointer is valid
+                unsafe {
+                    addr_of_mut!((*out).0).write(OperationTypeKind::SetDepositsLimit);
+                }
+                rem
+            }
+            0x72 => {
+                let out = out.as_mut_ptr() as *mut UpdateConsensusKeyVariant;
+                //valid pointer
+                let data = unsafe { &mut *addr_of_mut!((*out).1).cast() };
+
+                let rem = UpdateConsensusKey::from_bytes_into(rem, data)?;
+
+                //pointer is valid
+                unsafe {
+                    addr_of_mut!((*out).0).write(OperationTypeKind::UpdateConsensusKey);
+                }
+                rem
+            }
             _ => {
-                *out = MaybeUninit::new(Self::UnknownOp(rem));
+                *out = MaybeUninit::new(Self::UnknownOp(tag, rem));
                 &[] as _
             }
         };
@@ -248,24 +305,68 @@ impl<'b> OperationType<'b> {
         matches!(self, OperationType::Transfer(_))
     }
 
+    /// Returns the source of the operation, if the operation has one
+    ///
+    /// This is the account that is expected to sign the operation
+    pub fn source(&self) -> Option<(Curve, &'b [u8; 20])> {
+        match self {
+            Self::Transfer(tx) => Some(*tx.source()),
+            Self::Delegation(del) => Some(*del.source()),
+            Self::Reveal(rev) => Some(*rev.source()),
+            Self::Origination(orig) => Some(*orig.source()),
+            Self::Ballot(vote) => Some(*vote.source()),
+            Self::Proposals(prop) => Some(*prop.source()),
+            Self::UpdateConsensusKey(upd) => Some(*upd.source()),
+            //signed by the consensus key, not the delegate
+            Self::DrainDelegate(drain) => Some(*drain.consensus_key()),
+            Self::SetDepositsLimit(set) => Some(*set.source()),
+            _ => None,
+        }
+    }
+
+    /// Returns the fee and gas limit of manager operations
+    pub fn fee_and_gas_limit(&self) -> Option<(Zarith<'b>, Zarith<'b>)> {
+        match self {
+            Self::Transfer(tx) => Some((*tx.fee(), *tx.gas_limit())),
+            Self::Delegation(del) => Some((*del.fee(), *del.gas_limit())),
+            Self::Reveal(rev) => Some((*rev.fee(), *rev.gas_limit())),
+            Self::Origination(orig) => Some((*orig.fee(), *orig.gas_limit())),
+            Self::UpdateConsensusKey(upd) => Some((*upd.fee(), *upd.gas_limit())),
+            Self::SetDepositsLimit(set) => Some((*set.fee(), *set.gas_limit())),
+            _ => None,
+        }
+    }
+
     /// Returns the number of different items
     /// in a given `OperationType`
     ///
-    /// Usually, the number of fields of an operaton
-    pub fn ui_items(&self) -> usize {
-        match self {
-            Self::Transfer(tx) => tx.num_items(),
-            Self::Delegation(del) => del.num_items(),
-            Self::Endorsement(end) => end.num_items(),
-            Self::EndorsementWithSlot(end) => end.num_items(),
-            Self::Ballot(vote) => vote.num_items(),
-            Self::Reveal(rev) => rev.num_items(),
-            Self::Proposals(prop) => prop.num_items(),
-            Self::Origination(orig) => orig.num_items(),
-            Self::ActivateAccount(act) => act.num_items(),
-            Self::FailingNoop(fail) => fail.num_items(),
-            Self::UnknownOp(_) => 2,
-            Self::AnonymousOp(_) => 0,
+    /// Usually, the number of fields of an operaton.
+    /// Outside of expert mode the advanced fields
+    /// (limits, counter and entrypoint) are left out,
+    /// which are always the last items of an operation
+    pub fn ui_items(&self, expert: bool) -> usize {
+        let (all, basic) = match self {
+            Self::Transfer(tx) => (tx.num_items(), tx.basic_items()),
+            Self::Delegation(del) => (del.num_items(), Delegation::BASIC_ITEMS),
+            Self::Endorsement(end) => (end.num_items(), end.num_items()),
+            Self::EndorsementWithSlot(end) => (end.num_items(), end.num_items()),
+            Self::Ballot(vote) => (vote.num_items(), vote.num_items()),
+            Self::Reveal(rev) => (rev.num_items(), Reveal::BASIC_ITEMS),
+            Self::Proposals(prop) => (prop.num_items(), prop.num_items()),
+            Self::Origination(orig) => (orig.num_items(), Origination::BASIC_ITEMS),
+            Self::ActivateAccount(act) => (act.num_items(), act.num_items()),
+            Self::FailingNoop(fail) => (fail.num_items(), fail.num_items()),
+            Self::UpdateConsensusKey(upd) => (upd.num_items(), UpdateConsensusKey::BASIC_ITEMS),
+            Self::DrainDelegate(drain) => (drain.num_items(), drain.num_items()),
+            Self::SetDepositsLimit(set) => (set.num_items(), SetDepositsLimit::BASIC_ITEMS),
+            Self::UnknownOp(..) => (4, 2),
+            Self::AnonymousOp(_) => (0, 0),
+        };
+
+        if expert {
+            all
+        } else {
+            core::cmp::min(all, basic)
         }
     }
 
@@ -298,13 +399,14 @@ impl<'b> OperationType<'b> {
 
     #[inline(never)]
     pub fn render_unknown(
+        tag: u8,
         input: &'b [u8],
         item_n: u8,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
     ) -> Result<u8, ViewError> {
-        use crate::handlers::handle_ui_message;
+        use crate::handlers::{handle_ui_hex, handle_ui_message};
         use bolos::{pic_str, PIC};
 
         match item_n {
@@ -321,6 +423,19 @@ impl<'b> OperationType<'b> {
                 let (len, base58) = Self::hash_and_base58(input)?;
                 handle_ui_message(&base58[..len], message, page)
             }
+            //expert mode only
+            2 => {
+                let title_content = pic_str!(b"Tag");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_hex(&[tag], message, page)
+            }
+            3 => {
+                let title_content = pic_str!(b"Raw Data");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_hex(input, message, page)
+            }
             _ => Err(ViewError::NoData),
         }
     }
@@ -20,9 +20,10 @@ use zemu_sys::ViewError;
 use crate::{
     crypto::Curve,
     handlers::{
-        handle_ui_message, intstr_to_fpstr_inplace, parser_common::ParserError, public_key::Addr,
+        address_book::render_address, handle_ui_message, intstr_to_fpstr_inplace,
+        parser_common::ParserError, public_key::Addr, settings::Settings,
     },
-    parser::{boolean, public_key_hash, DisplayableItem, Zarith},
+    parser::{boolean, operations::ContractID, public_key_hash, DisplayableItem, Zarith},
 };
 
 #[derive(Clone, Copy, PartialEq, Eq, property::Property)]
@@ -63,6 +64,11 @@ pub struct Origination<'b> {
 }
 
 impl<'b> Origination<'b> {
+    /// Number of items shown outside of expert mode
+    ///
+    /// Type, source, balance, delegate, fee, code and storage
+    pub const BASIC_ITEMS: usize = 7;
+
     #[inline(never)]
     pub fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
         let (rem, (source, fee, counter, gas_limit, storage_limit, balance, delegate, script)) = do_parse! {input,
@@ -134,6 +140,7 @@ impl<'b> Origination<'b> {
         Ok(addr.base58())
     }
 
+    #[cfg(test)]
     fn delegate_base58(&self) -> Result<Option<(usize, [u8; Addr::BASE58_LEN])>, bolos::Error> {
         self.delegate
             .map(|(crv, hash)| Addr::from_hash(hash, crv).map(|a| a.base58()))
@@ -189,7 +196,8 @@ impl<'a> DisplayableItem for Origination<'a> {
 
                 itoa(amount, &mut zarith_buf);
                 handle_ui_message(
-                    intstr_to_fpstr_inplace(&mut zarith_buf, 6).map_err(|_| ViewError::Unknown)?,
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
                     message,
                     page,
                 )
@@ -199,8 +207,10 @@ impl<'a> DisplayableItem for Origination<'a> {
                 let title_content = pic_str!(b"Delegate");
                 title[..title_content.len()].copy_from_slice(title_content);
 
-                match self.delegate_base58().map_err(|_| ViewError::Unknown)? {
-                    Some((len, delegate)) => handle_ui_message(&delegate[..len], message, page),
+                match self.delegate {
+                    Some((crv, hash)) => {
+                        render_address(ContractID::Implicit(crv, hash), message, page)
+                    }
                     None => handle_ui_message(&pic_str!(b"no delegate")[..], message, page),
                 }
             }
@@ -213,7 +223,8 @@ impl<'a> DisplayableItem for Origination<'a> {
 
                 itoa(fee, &mut zarith_buf);
                 handle_ui_message(
-                    intstr_to_fpstr_inplace(&mut zarith_buf, 6).map_err(|_| ViewError::Unknown)?,
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
                     message,
                     page,
                 )
@@ -53,7 +53,7 @@ impl<'b> Proposals<'b> {
         }?;
 
         let proposals =
-            bytemuck::try_cast_slice(proposals).map_err(|_| ParserError::ProposalsLengthInvalid)?;
+            bytemuck::try_cast_slice(proposals).map_err(|_| ParserError::InvalidProposalsLength)?;
 
         Ok((
             rem,
@@ -79,7 +79,7 @@ impl<'b> Proposals<'b> {
         }?;
 
         let proposals =
-            bytemuck::try_cast_slice(proposals).map_err(|_| ParserError::ProposalsLengthInvalid)?;
+            bytemuck::try_cast_slice(proposals).map_err(|_| ParserError::InvalidProposalsLength)?;
 
         let out = out.as_mut_ptr();
         //dereferencing pointer from references is okay
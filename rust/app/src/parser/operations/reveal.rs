@@ -21,7 +21,7 @@ use crate::{
     crypto::Curve,
     handlers::{
         handle_ui_message, intstr_to_fpstr_inplace, parser_common::ParserError, public_key::Addr,
-        sha256x2,
+        settings::Settings, sha256x2,
     },
     parser::{public_key, public_key_hash, DisplayableItem, Zarith},
 };
@@ -39,6 +39,11 @@ pub struct Reveal<'b> {
 }
 
 impl<'b> Reveal<'b> {
+    /// Number of items shown outside of expert mode
+    ///
+    /// Type, source, public key and fee
+    pub const BASIC_ITEMS: usize = 4;
+
     #[inline(never)]
     pub fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
         let (rem, (source, fee, counter, gas_limit, storage_limit, public_key)) = do_parse! {input,
@@ -155,7 +160,8 @@ impl<'b> DisplayableItem for Reveal<'b> {
 
                 itoa(fee, &mut zarith_buf);
                 handle_ui_message(
-                    intstr_to_fpstr_inplace(&mut zarith_buf, 6).map_err(|_| ViewError::Unknown)?,
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
                     message,
                     page,
                 )
@@ -201,11 +207,11 @@ impl<'b> DisplayableItem for Reveal<'b> {
     }
 }
 
-const MAX_PK_BASE58_LEN: usize = 56;
+pub(super) const MAX_PK_BASE58_LEN: usize = 56;
 /// Encodes a public key as base58 on the provided `out` buffer
 ///
 /// returns the number of bytes written
-fn pk_to_base58(
+pub(super) fn pk_to_base58(
     (crv, bytes): (Curve, &[u8]),
     out: &mut [u8; MAX_PK_BASE58_LEN],
 ) -> Result<usize, bolos::Error> {
@@ -0,0 +1,285 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use core::{mem::MaybeUninit, ptr::addr_of_mut};
+use nom::{call, cond, do_parse, IResult};
+use zemu_sys::ViewError;
+
+use crate::{
+    crypto::Curve,
+    handlers::{
+        handle_ui_message, intstr_to_fpstr_inplace, parser_common::ParserError, public_key::Addr,
+        settings::Settings,
+    },
+    parser::{boolean, public_key_hash, DisplayableItem, Zarith},
+};
+
+#[derive(Clone, Copy, PartialEq, property::Property)]
+#[cfg_attr(test, derive(Debug))]
+#[property(mut(disable), get(public), set(disable))]
+pub struct SetDepositsLimit<'b> {
+    source: (Curve, &'b [u8; 20]),
+    fee: Zarith<'b>,
+    counter: Zarith<'b>,
+    gas_limit: Zarith<'b>,
+    storage_limit: Zarith<'b>,
+    /// `None` removes the limit
+    limit: Option<Zarith<'b>>,
+}
+
+impl<'b> SetDepositsLimit<'b> {
+    /// Number of items shown outside of expert mode
+    ///
+    /// Type, source, limit and fee
+    pub const BASIC_ITEMS: usize = 4;
+
+    #[inline(never)]
+    pub fn from_bytes(input: &'b [u8]) -> IResult<&'b [u8], Self, ParserError> {
+        let (rem, (source, fee, counter, gas_limit, storage_limit, limit)) = do_parse! {input,
+            source: public_key_hash >>
+            fee: call!(Zarith::from_bytes, false) >>
+            counter: call!(Zarith::from_bytes, false) >>
+            gas_limit: call!(Zarith::from_bytes, false) >>
+            storage_limit: call!(Zarith::from_bytes, false) >>
+            has_limit: boolean >>
+            limit: cond!(has_limit, call!(Zarith::from_bytes, false)) >>
+            (source, fee, counter, gas_limit, storage_limit, limit)
+        }?;
+
+        Ok((
+            rem,
+            Self {
+                source,
+                fee,
+                counter,
+                gas_limit,
+                storage_limit,
+                limit,
+            },
+        ))
+    }
+
+    #[inline(never)]
+    pub fn from_bytes_into(
+        input: &'b [u8],
+        out: &mut MaybeUninit<Self>,
+    ) -> Result<&'b [u8], nom::Err<ParserError>> {
+        let (rem, data) = Self::from_bytes(input)?;
+
+        let out = out.as_mut_ptr();
+        //good ptr, no uninit reads
+        unsafe {
+            addr_of_mut!((*out).source).write(data.source);
+            addr_of_mut!((*out).fee).write(data.fee);
+            addr_of_mut!((*out).counter).write(data.counter);
+            addr_of_mut!((*out).gas_limit).write(data.gas_limit);
+            addr_of_mut!((*out).storage_limit).write(data.storage_limit);
+            addr_of_mut!((*out).limit).write(data.limit);
+        }
+
+        Ok(rem)
+    }
+}
+
+impl<'b> DisplayableItem for SetDepositsLimit<'b> {
+    fn num_items(&self) -> usize {
+        1 + 6
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &self,
+        item_n: u8,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use bolos::{pic_str, PIC};
+        use lexical_core::{write as itoa, Number};
+
+        let mut zarith_buf = [0; usize::FORMATTED_SIZE_DECIMAL + 2]; //+2 for decimal formatting
+
+        match item_n {
+            //Homepage
+            0 => {
+                let title_content = pic_str!(b"Type");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                handle_ui_message(&pic_str!(b"Set Deposits Limit")[..], message, page)
+            }
+            //source
+            1 => {
+                let title_content = pic_str!(b"Source");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (crv, hash) = self.source();
+                let addr = Addr::from_hash(hash, *crv).map_err(|_| ViewError::Unknown)?;
+
+                let (len, mex) = addr.base58();
+                handle_ui_message(&mex[..len], message, page)
+            }
+            //limit
+            2 => {
+                let title_content = pic_str!(b"Limit");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                match self.limit {
+                    Some(limit) => {
+                        let (_, limit) = limit.read_as::<usize>().ok_or(ViewError::Unknown)?;
+
+                        itoa(limit, &mut zarith_buf);
+                        handle_ui_message(
+                            intstr_to_fpstr_inplace(
+                                &mut zarith_buf,
+                                Settings::load().unit().decimals(),
+                            )
+                            .map_err(|_| ViewError::Unknown)?,
+                            message,
+                            page,
+                        )
+                    }
+                    None => handle_ui_message(&pic_str!(b"<UNLIMITED>")[..], message, page),
+                }
+            }
+            //fee
+            3 => {
+                let title_content = pic_str!(b"Fee");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, fee) = self.fee().read_as::<usize>().ok_or(ViewError::Unknown)?;
+
+                itoa(fee, &mut zarith_buf);
+                handle_ui_message(
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
+                    message,
+                    page,
+                )
+            }
+            //gas_limit
+            4 => {
+                let title_content = pic_str!(b"Gas Limit");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, gas_limit) = self
+                    .gas_limit()
+                    .read_as::<usize>()
+                    .ok_or(ViewError::Unknown)?;
+
+                handle_ui_message(itoa(gas_limit, &mut zarith_buf), message, page)
+            }
+            //storage_limit
+            5 => {
+                let title_content = pic_str!(b"Storage Limit");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, storage_limit) = self
+                    .storage_limit()
+                    .read_as::<usize>()
+                    .ok_or(ViewError::Unknown)?;
+
+                handle_ui_message(itoa(storage_limit, &mut zarith_buf), message, page)
+            }
+            //counter
+            6 => {
+                let title_content = pic_str!(b"Counter");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let (_, counter) = self
+                    .counter()
+                    .read_as::<usize>()
+                    .ok_or(ViewError::Unknown)?;
+
+                handle_ui_message(itoa(counter, &mut zarith_buf), message, page)
+            }
+            _ => Err(ViewError::NoData),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrayref::array_ref;
+
+    use crate::{crypto::Curve, parser::Zarith};
+
+    use super::SetDepositsLimit;
+
+    #[test]
+    fn set_deposits_limit() {
+        const INPUT_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd\
+                                 904e\
+                                 01\
+                                 0a\
+                                 0a\
+                                 ff\
+                                 80c2d72f";
+
+        let input = hex::decode(INPUT_HEX).expect("invalid input hex");
+
+        let (rem, parsed) =
+            SetDepositsLimit::from_bytes(&input).expect("failed to parse set deposits limit");
+        assert_eq!(rem.len(), 0);
+
+        let expected = SetDepositsLimit {
+            source: (Curve::Bip32Ed25519, array_ref!(input, 1, 20)),
+            fee: Zarith {
+                is_negative: None,
+                bytes: &input[21..23],
+            },
+            counter: Zarith {
+                is_negative: None,
+                bytes: &input[23..24],
+            },
+            gas_limit: Zarith {
+                is_negative: None,
+                bytes: &input[24..25],
+            },
+            storage_limit: Zarith {
+                is_negative: None,
+                bytes: &input[25..26],
+            },
+            limit: Some(Zarith {
+                is_negative: None,
+                bytes: &input[27..31],
+            }),
+        };
+        assert_eq!(parsed, expected);
+        assert_eq!(
+            parsed
+                .limit
+                .and_then(|l| l.read_as::<u64>())
+                .map(|(_, l)| l),
+            Some(100_000_000)
+        );
+    }
+
+    #[test]
+    fn remove_deposits_limit() {
+        const INPUT_HEX: &str = "0035e993d8c7aaa42b5e3ccd86a33390ececc73abd\
+                                 904e\
+                                 01\
+                                 0a\
+                                 0a\
+                                 00";
+
+        let input = hex::decode(INPUT_HEX).expect("invalid input hex");
+
+        let (rem, parsed) =
+            SetDepositsLimit::from_bytes(&input).expect("failed to parse set deposits limit");
+        assert_eq!(rem.len(), 0);
+        assert_eq!(parsed.limit, None);
+    }
+}
@@ -0,0 +1,218 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+use zemu_sys::ViewError;
+
+use crate::{
+    handlers::{
+        handle_ui_message, intstr_to_fpstr_inplace, parser_common::ParserError, settings::Settings,
+    },
+    parser::{DisplayableItem, Zarith},
+};
+
+use super::OperationType;
+
+/// Aggregated totals of a group of operations
+#[derive(Clone, Copy, Default, PartialEq, Eq, property::Property)]
+#[cfg_attr(test, derive(Debug))]
+#[property(mut(disable), get(public), set(disable))]
+pub struct Summary {
+    n_ops: usize,
+    amount: u64,
+    fees: u64,
+    storage_limit: u64,
+}
+
+impl Summary {
+    /// Mutez burned for each byte of storage
+    pub const COST_PER_BYTE: u64 = 250;
+
+    /// Adds `op` to the totals
+    ///
+    /// Fails if any of the totals doesn't fit in an u64
+    pub fn add(&mut self, op: &OperationType<'_>) -> Result<(), ParserError> {
+        let (amount, fee, storage_limit) = match op {
+            OperationType::Transfer(tx) => (Some(*tx.amount()), *tx.fee(), *tx.storage_limit()),
+            OperationType::Origination(orig) => {
+                (Some(*orig.balance()), *orig.fee(), *orig.storage_limit())
+            }
+            OperationType::Delegation(del) => (None, *del.fee(), *del.storage_limit()),
+            OperationType::Reveal(rev) => (None, *rev.fee(), *rev.storage_limit()),
+            OperationType::UpdateConsensusKey(upd) => (None, *upd.fee(), *upd.storage_limit()),
+            OperationType::SetDepositsLimit(set) => (None, *set.fee(), *set.storage_limit()),
+            _ => {
+                self.n_ops += 1;
+                return Ok(());
+            }
+        };
+
+        if let Some(amount) = amount {
+            self.amount = Self::checked_add(self.amount, amount)?;
+        }
+        self.fees = Self::checked_add(self.fees, fee)?;
+        self.storage_limit = Self::checked_add(self.storage_limit, storage_limit)?;
+
+        self.n_ops += 1;
+        Ok(())
+    }
+
+    fn checked_add(total: u64, n: Zarith) -> Result<u64, ParserError> {
+        n.read_as::<u64>()
+            .and_then(|(_, n)| total.checked_add(n))
+            .ok_or(ParserError::ValueOutOfRange)
+    }
+
+    /// Returns the maximum amount of mutez that can be burned for storage
+    pub fn burn_cap(&self) -> Result<u64, ParserError> {
+        self.storage_limit
+            .checked_mul(Self::COST_PER_BYTE)
+            .ok_or(ParserError::ValueOutOfRange)
+    }
+}
+
+impl DisplayableItem for Summary {
+    fn num_items(&self) -> usize {
+        4
+    }
+
+    #[inline(never)]
+    fn render_item(
+        &self,
+        item_n: u8,
+        title: &mut [u8],
+        message: &mut [u8],
+        page: u8,
+    ) -> Result<u8, ViewError> {
+        use bolos::{pic_str, PIC};
+        use lexical_core::{write as itoa, Number};
+
+        let mut zarith_buf = [0; u64::FORMATTED_SIZE_DECIMAL + 2]; //+2 for fixed point formatting
+
+        let tez = match item_n {
+            //number of operations
+            0 => {
+                let title_content = pic_str!(b"Operations");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                return handle_ui_message(itoa(self.n_ops, &mut zarith_buf), message, page);
+            }
+            //total amount
+            1 => {
+                let title_content = pic_str!(b"Total Amount");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                self.amount
+            }
+            //total fees
+            2 => {
+                let title_content = pic_str!(b"Total Fees");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                self.fees
+            }
+            //total burn cap
+            3 => {
+                let title_content = pic_str!(b"Total Burn Cap");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                self.burn_cap().map_err(|_| ViewError::Unknown)?
+            }
+            _ => return Err(ViewError::NoData),
+        };
+
+        itoa(tez, &mut zarith_buf);
+        handle_ui_message(
+            intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                .map_err(|_| ViewError::Unknown)?,
+            message,
+            page,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{vec, vec::Vec};
+
+    use crate::handlers::parser_common::ParserError;
+
+    use super::{super::EncodedOperations, Summary};
+
+    fn from_ops(mut ops: EncodedOperations<'_>) -> Result<Summary, nom::Err<ParserError>> {
+        let mut summary = Summary::default();
+        while let Some(op) = ops.parse_next()? {
+            summary.add(&op)?;
+        }
+
+        //make sure the burn cap is representable too
+        summary.burn_cap()?;
+
+        Ok(summary)
+    }
+
+    fn transfer(amount: &[u8], fee: &[u8], storage_limit: &[u8]) -> Vec<u8> {
+        let mut op = vec![0x6C, 0x00];
+        op.extend_from_slice(&[0x42; 20]);
+        op.extend_from_slice(fee);
+        //counter, gas_limit
+        op.extend_from_slice(&[0x01, 0x01]);
+        op.extend_from_slice(storage_limit);
+        op.extend_from_slice(amount);
+        //implicit destination
+        op.extend_from_slice(&[0x00, 0x00]);
+        op.extend_from_slice(&[0xAA; 20]);
+        //no parameters
+        op.push(0x00);
+
+        op
+    }
+
+    #[test]
+    fn totals() {
+        let mut input = transfer(&[0x80, 0x01], &[0x0A], &[0x64]);
+        input.extend(transfer(&[0x10], &[0x0B], &[0x01]));
+
+        let summary = from_ops(EncodedOperations::new(&input)).unwrap();
+        assert_eq!(
+            summary,
+            Summary {
+                n_ops: 2,
+                amount: 0x80 + 0x10,
+                fees: 0x0A + 0x0B,
+                storage_limit: 0x64 + 0x01,
+            }
+        );
+        assert_eq!(summary.burn_cap().unwrap(), 101 * Summary::COST_PER_BYTE);
+    }
+
+    #[test]
+    fn total_overflow() {
+        //i64::MAX as zarith
+        let big = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F];
+
+        let mut input = transfer(&big, &[0x01], &[0x00]);
+        input.extend(transfer(&big, &[0x01], &[0x00]));
+        input.extend(transfer(&big, &[0x01], &[0x00]));
+
+        let err = from_ops(EncodedOperations::new(&input)).unwrap_err();
+        assert_eq!(err, nom::Err::Error(ParserError::ValueOutOfRange));
+
+        //the burn cap doesn't fit either
+        let input = transfer(&[0x01], &[0x01], &big);
+
+        let err = from_ops(EncodedOperations::new(&input)).unwrap_err();
+        assert_eq!(err, nom::Err::Error(ParserError::ValueOutOfRange));
+    }
+}
@@ -24,7 +24,8 @@ use zemu_sys::ViewError;
 use crate::{
     crypto::Curve,
     handlers::{
-        handle_ui_message, intstr_to_fpstr_inplace, parser_common::ParserError, public_key::Addr,
+        address_book::render_address, handle_ui_message, intstr_to_fpstr_inplace,
+        parser_common::ParserError, public_key::Addr, settings::Settings,
     },
     parser::{boolean, public_key_hash, DisplayableItem, Zarith},
 };
@@ -39,6 +40,7 @@ pub enum Entrypoint<'b> {
     Do,
     SetDelegate,
     RemoveDelegate,
+    SetDelegateParameters,
     Custom(&'b [u8]),
 }
 
@@ -52,6 +54,7 @@ impl<'b> Entrypoint<'b> {
             0x02 => Self::Do,
             0x03 => Self::SetDelegate,
             0x04 => Self::RemoveDelegate,
+            0x09 => Self::SetDelegateParameters,
             0xFF => {
                 let (rem2, length) = le_u8(rem)?;
                 let (rem2, name) = take!(rem2, length)?;
@@ -59,7 +62,7 @@ impl<'b> Entrypoint<'b> {
 
                 Self::Custom(name)
             }
-            _ => return Err(ParserError::parser_invalid_contract_name.into()),
+            _ => return Err(ParserError::InvalidEntrypoint.into()),
         };
 
         Ok((rem, data))
@@ -74,6 +77,7 @@ impl<'b> core::fmt::Display for Entrypoint<'b> {
             Entrypoint::Do => write!(f, "do"),
             Entrypoint::SetDelegate => write!(f, "set_delegate"),
             Entrypoint::RemoveDelegate => write!(f, "remove_delegate"),
+            Entrypoint::SetDelegateParameters => write!(f, "set_delegate_parameters"),
             Entrypoint::Custom(custom) => {
                 let custom = core::str::from_utf8(custom).expect("custom entrypoint was not utf8");
                 f.write_str(custom)
@@ -125,6 +129,18 @@ pub struct Transfer<'b> {
 }
 
 impl<'b> Transfer<'b> {
+    /// Number of items shown outside of expert mode
+    ///
+    /// Type, source, destination, amount and fee
+    pub const BASIC_ITEMS: usize = 5;
+
+    /// Number of items shown outside of expert mode for this transfer
+    ///
+    /// Contract calls also show their parameters
+    pub fn basic_items(&self) -> usize {
+        Self::BASIC_ITEMS + self.parameters.is_some() as usize
+    }
+
     #[cfg(not(test))]
     #[inline(never)]
     pub fn from_bytes(input: &'b [u8]) -> IResult<&[u8], Self, ParserError> {
@@ -248,7 +264,7 @@ impl<'b> Transfer<'b> {
 
 impl<'a> DisplayableItem for Transfer<'a> {
     fn num_items(&self) -> usize {
-        1 + 8
+        1 + 9
     }
 
     #[inline(never)]
@@ -295,12 +311,7 @@ impl<'a> DisplayableItem for Transfer<'a> {
                 };
                 title[..title_content.len()].copy_from_slice(title_content.as_bytes());
 
-                let (len, cid) = self
-                    .destination()
-                    .base58()
-                    .map_err(|_| ViewError::Unknown)?;
-
-                handle_ui_message(&cid[..len], message, page)
+                render_address(self.destination, message, page)
             }
             //amount
             3 => {
@@ -311,7 +322,8 @@ impl<'a> DisplayableItem for Transfer<'a> {
 
                 itoa(amount, &mut zarith_buf);
                 handle_ui_message(
-                    intstr_to_fpstr_inplace(&mut zarith_buf, 6).map_err(|_| ViewError::Unknown)?,
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
                     message,
                     page,
                 )
@@ -325,7 +337,8 @@ impl<'a> DisplayableItem for Transfer<'a> {
 
                 itoa(fee, &mut zarith_buf);
                 handle_ui_message(
-                    intstr_to_fpstr_inplace(&mut zarith_buf, 6).map_err(|_| ViewError::Unknown)?,
+                    intstr_to_fpstr_inplace(&mut zarith_buf, Settings::load().unit().decimals())
+                        .map_err(|_| ViewError::Unknown)?,
                     message,
                     page,
                 )
@@ -387,6 +400,28 @@ impl<'a> DisplayableItem for Transfer<'a> {
 
                 handle_ui_message(itoa(counter, &mut zarith_buf), message, page)
             }
+            //entrypoint
+            9 => {
+                let title_content = pic_str!(b"Entrypoint");
+                title[..title_content.len()].copy_from_slice(title_content);
+
+                let entrypoint = match self.parameters {
+                    Some(params) => params.entrypoint,
+                    None => Entrypoint::Default,
+                };
+
+                let mex = match entrypoint {
+                    Entrypoint::Default => &pic_str!(b"default")[..],
+                    Entrypoint::Root => &pic_str!(b"root")[..],
+                    Entrypoint::Do => &pic_str!(b"do")[..],
+                    Entrypoint::SetDelegate => &pic_str!(b"set_delegate")[..],
+                    Entrypoint::RemoveDelegate => &pic_str!(b"remove_delegate")[..],
+                    Entrypoint::SetDelegateParameters => &pic_str!(b"set_delegate_parameters")[..],
+                    Entrypoint::Custom(name) => name,
+                };
+
+                handle_ui_message(mex, message, page)
+            }
             _ => Err(ViewError::NoData),
         }
     }
@@ -463,7 +498,8 @@ impl<'b> Transfer<'b> {
                     | (Entrypoint::Root, "root")
                     | (Entrypoint::Do, "do")
                     | (Entrypoint::SetDelegate, "set_delegate")
-                    | (Entrypoint::RemoveDelegate, "remove_delegate") => {}
+                    | (Entrypoint::RemoveDelegate, "remove_delegate")
+                    | (Entrypoint::SetDelegateParameters, "set_delegate_parameters") => {}
                     (Entrypoint::Custom(s), js) if s == &js.as_bytes() => {}
                     (parsed, expected) => {
                         panic!("expected entrypoint: {}, parsed: {}", expected, parsed)
@@ -501,6 +537,10 @@ mod tests {
             Entrypoint::from_bytes(&[4]).expect("failed to parse remove_delegate entrypoint");
         assert_eq!(remove_delegate, Entrypoint::RemoveDelegate);
 
+        let (_, set_delegate_parameters) = Entrypoint::from_bytes(&[9])
+            .expect("failed to parse set_delegate_parameters entrypoint");
+        assert_eq!(set_delegate_parameters, Entrypoint::SetDelegateParameters);
+
         let (rem, custom) = Entrypoint::from_bytes(&[0xFF, 0x03, 0x61, 0x62, 0x63, 0xaa])
             .expect("failed to parse custom entrypoint");
         assert_eq!(rem.len(), 1);
@@ -25,13 +25,31 @@ use baking_prelude::*;
 
 const PATH: &[u32] = &[44, 1729, 0, 0];
 const CURVE: Curve = Curve::Bip32Ed25519;
+const ANY_CHAIN: u32 = 0;
+
+//the second line of the payload should be
+// gotten from the baking_utils `get_blockdata_info`
+// of the zemu folder.
+// the first line (0x11) is added manually (tenderbake block magic byte)
+// the following lines are the rest of the header: context, payload hash, payload round,
+// proof of work nonce, seed nonce hash (none) and the per block votes (all on)
+const TB_BLOCK_BLOB: &str = "11\
+                             000000000000007b2a00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\
+                             000000210000000102000000040000007b0000000000000004ffffffff00000004000001c8\
+                             0000000000000000000000000000000000000000000000000000000000000000\
+                             0000000000000000000000000000000000000000000000000000000000000000\
+                             00000000\
+                             0000000000000000\
+                             00\
+                             00";
 
 #[test]
 #[serial]
 fn baking_flow() {
     reset_state(0);
 
-    authorize_baking(PATH, CURVE);
+    //the samples aren't all for mainnet
+    setup_baking(PATH, CURVE, ANY_CHAIN);
 
     //prepare command
     let command = APDUCommand {
@@ -61,17 +79,12 @@ fn baking_flow() {
 #[test]
 #[serial]
 fn zemu_tb_block_blob() {
-    //the second line of the payload should be
-    // gotten from the baking_utils `get_blockdata_info`
-    // of the zemu folder.
-    // the first line (0x11) is added manually (tenderbake block magic byte)
-    const BLOB: &str = "11\
-                        000000000000007b2a00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000090202020202000001c8";
-    let data = hex::decode(BLOB).unwrap();
+    let data = hex::decode(TB_BLOCK_BLOB).unwrap();
 
     reset_state(0);
 
-    authorize_baking(PATH, CURVE);
+    //the blobs are for the chain 0x00000000
+    setup_baking(PATH, CURVE, ANY_CHAIN);
 
     //prepare command
     let command = APDUCommand {
@@ -86,6 +99,74 @@ fn zemu_tb_block_blob() {
     assert_eq!(answer.retcode(), ApduError::Success as u16);
 }
 
+#[test]
+#[serial]
+fn sign_log_block() {
+    let data = hex::decode(TB_BLOCK_BLOB).unwrap();
+
+    reset_state(0);
+
+    setup_baking(PATH, CURVE, ANY_CHAIN);
+
+    //prepare command
+    let command = APDUCommand {
+        cla: CLA,
+        ins: INS_BAKER_SIGN,
+        p1: PacketType::Init.into(),
+        p2: CURVE.into(),
+        data: prepare_path::<{ constants::BIP32_MAX_LENGTH }>(PATH),
+    };
+
+    let answer = process_apdu_chunks(command.clone(), &data);
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+
+    let query = APDUCommand {
+        cla: CLA,
+        ins: INS_QUERY_SIGN_LOG,
+        p1: 0,
+        p2: 0,
+        data: vec![],
+    };
+
+    let answer = process_apdu(&query);
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+
+    //the newest record is the block: kind, chain id, level, round and payload hash prefix
+    let data = answer.apdu_data();
+    assert!(data[0] >= 1);
+    let mut expected = vec![1, 0, 0, 0, 0, 0, 0, 0, 0x7b, 0, 0, 0x01, 0xc8];
+    expected.extend_from_slice(&[0; 8]);
+    assert_eq!(&data[1..1 + expected.len()], &expected[..]);
+}
+
+#[test]
+#[serial]
+fn tb_block_trailing_bytes() {
+    let data = hex::decode(TB_BLOCK_BLOB).unwrap();
+
+    reset_state(0);
+
+    setup_baking(PATH, CURVE, ANY_CHAIN);
+
+    //prepare command
+    let command = APDUCommand {
+        cla: CLA,
+        ins: INS_BAKER_SIGN,
+        p1: PacketType::Init.into(),
+        p2: CURVE.into(),
+        data: prepare_path::<{ constants::BIP32_MAX_LENGTH }>(PATH),
+    };
+
+    let mut trailing = data.clone();
+    trailing.push(0);
+
+    let answer = process_apdu_chunks(command.clone(), &trailing);
+    assert_eq!(answer.retcode(), ApduError::ParserInvalidPayload as u16);
+
+    let answer = process_apdu_chunks(command.clone(), &data);
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+}
+
 #[test]
 #[serial]
 fn zemu_endorsement_blob() {
@@ -104,7 +185,8 @@ fn zemu_endorsement_blob() {
 
     reset_state(0);
 
-    authorize_baking(PATH, CURVE);
+    //the blobs are for the chain 0x00000000
+    setup_baking(PATH, CURVE, ANY_CHAIN);
 
     //prepare command
     let command = APDUCommand {
@@ -126,3 +208,56 @@ fn zemu_endorsement_blob() {
         assert_eq!(answer.retcode(), ApduError::Success as u16);
     }
 }
+
+#[test]
+#[serial]
+fn unknown_chain() {
+    const MAINNET_CHAIN_ID: u32 = 0x7A06A770;
+    let data = hex::decode(TB_BLOCK_BLOB).unwrap();
+
+    reset_state(0);
+
+    setup_baking(PATH, CURVE, MAINNET_CHAIN_ID);
+
+    //prepare command
+    let command = APDUCommand {
+        cla: CLA,
+        ins: INS_BAKER_SIGN,
+        p1: PacketType::Init.into(),
+        p2: CURVE.into(),
+        data: prepare_path::<{ constants::BIP32_MAX_LENGTH }>(PATH),
+    };
+
+    let answer = process_apdu_chunks(command.clone(), &data);
+    assert_eq!(answer.retcode(), ApduError::UnknownChain as u16);
+}
+
+#[test]
+#[serial]
+fn vote_policy() {
+    let data = hex::decode(TB_BLOCK_BLOB).unwrap();
+
+    reset_state(0);
+
+    setup_baking(PATH, CURVE, ANY_CHAIN);
+
+    //liquidity baking: off or pass, adaptive issuance: any
+    set_vote_policy(&[1, 0b110, 0b111]);
+
+    //prepare command
+    let command = APDUCommand {
+        cla: CLA,
+        ins: INS_BAKER_SIGN,
+        p1: PacketType::Init.into(),
+        p2: CURVE.into(),
+        data: prepare_path::<{ constants::BIP32_MAX_LENGTH }>(PATH),
+    };
+
+    let answer = process_apdu_chunks(command.clone(), &data);
+    assert_eq!(answer.retcode(), ApduError::DataInvalid as u16);
+
+    set_vote_policy(&[]);
+
+    let answer = process_apdu_chunks(command.clone(), &data);
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+}
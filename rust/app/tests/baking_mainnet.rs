@@ -0,0 +1,102 @@
+/*******************************************************************************
+*   (c) 2021 Zondax GmbH
+*
+*  Licensed under the Apache License, Version 2.0 (the "License");
+*  you may not use this file except in compliance with the License.
+*  You may obtain a copy of the License at
+*
+*      http://www.apache.org/licenses/LICENSE-2.0
+*
+*  Unless required by applicable law or agreed to in writing, software
+*  distributed under the License is distributed on an "AS IS" BASIS,
+*  WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+*  See the License for the specific language governing permissions and
+*  limitations under the License.
+********************************************************************************/
+//! Baking authorized with `INS_AUTHORIZE_BAKING` on a device
+//! where no chain was ever set up, so only mainnet can be baked
+//!
+//! Kept apart from the other baking tests so the NVM starts empty
+#![cfg(feature = "baking")]
+
+const SAMPLES: &[(&str, usize, &str, &str)] = include!("signatory_samples.bin");
+
+mod prelude;
+use prelude::*;
+
+mod baking_prelude;
+use baking_prelude::*;
+
+const PATH: &[u32] = &[44, 1729, 0, 0];
+const CURVE: Curve = Curve::Bip32Ed25519;
+
+/// Tenderbake block at level 123 round 456 (see `TB_BLOCK_BLOB` in baking.rs)
+/// without the chain id
+const TB_BLOCK_NO_CHAIN: &str = "0000007b2a00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000\
+                                 000000210000000102000000040000007b0000000000000004ffffffff00000004000001c8\
+                                 0000000000000000000000000000000000000000000000000000000000000000\
+                                 0000000000000000000000000000000000000000000000000000000000000000\
+                                 00000000\
+                                 0000000000000000\
+                                 00\
+                                 00";
+
+fn tb_block(chain_id: u32) -> Vec<u8> {
+    let mut data = vec![0x11];
+    data.extend_from_slice(&chain_id.to_be_bytes());
+    data.extend_from_slice(&hex::decode(TB_BLOCK_NO_CHAIN).unwrap());
+
+    data
+}
+
+#[test]
+#[serial]
+fn authorize_baking_mainnet() {
+    const MAINNET_CHAIN_ID: u32 = 0x7A06A770;
+
+    reset_state(0);
+
+    authorize_baking(PATH, CURVE);
+
+    //prepare command
+    let command = APDUCommand {
+        cla: CLA,
+        ins: INS_BAKER_SIGN,
+        p1: PacketType::Init.into(),
+        p2: CURVE.into(),
+        data: prepare_path::<{ constants::BIP32_MAX_LENGTH }>(PATH),
+    };
+
+    let answer = process_apdu_chunks(command.clone(), &tb_block(MAINNET_CHAIN_ID));
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+
+    let answer = process_apdu_chunks(command, &tb_block(0x1234_5678));
+    assert_eq!(answer.retcode(), ApduError::UnknownChain as u16);
+}
+
+/// Testnet bakers that authorized with `INS_AUTHORIZE_BAKING` before the chain checks
+/// need to set up their chain with `INS_SETUP` after upgrading
+#[test]
+#[serial]
+fn authorize_baking_testnet() {
+    reset_state(0);
+
+    authorize_baking(PATH, CURVE);
+
+    //prepare command
+    let command = APDUCommand {
+        cla: CLA,
+        ins: INS_BAKER_SIGN,
+        p1: PacketType::Init.into(),
+        p2: CURVE.into(),
+        data: prepare_path::<{ constants::BIP32_MAX_LENGTH }>(PATH),
+    };
+
+    //the samples are for a testnet
+    for (i, (_, _, _, data)) in SAMPLES.iter().enumerate() {
+        let data = hex::decode(data).unwrap_or_else(|_| panic!("sample #{} data was not hex", i));
+
+        let answer = process_apdu_chunks(command.clone(), &data);
+        assert_eq!(answer.retcode(), ApduError::UnknownChain as u16);
+    }
+}
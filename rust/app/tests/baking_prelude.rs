@@ -46,3 +46,36 @@ pub fn authorize_baking(path: &[u32], curve: Curve) {
     let answer = process_apdu(&apdu);
     assert_eq!(answer.retcode(), ApduError::Success as u16);
 }
+
+/// Authorizes baking with the legacy setup, which also sets the chain
+/// that can be baked (0 for any chain)
+pub fn setup_baking(path: &[u32], curve: Curve, chain_id: u32) {
+    let mut data = chain_id.to_be_bytes().to_vec();
+    data.extend_from_slice(&[0; 8]); //main and test watermarks
+    data.extend_from_slice(&prepare_path::<{ constants::BIP32_MAX_LENGTH }>(path));
+
+    let apdu = APDUCommand {
+        cla: CLA,
+        ins: INS_LEGACY_SETUP,
+        p1: 0,
+        p2: curve.into(),
+        data,
+    };
+
+    let answer = process_apdu(&apdu);
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+}
+
+/// Sets the toggle votes allowed in blocks, an empty `policy` allows any vote
+pub fn set_vote_policy(policy: &[u8]) {
+    let apdu = APDUCommand {
+        cla: CLA,
+        ins: INS_SET_VOTE_POLICY,
+        p1: 0,
+        p2: 0,
+        data: policy.to_vec(),
+    };
+
+    let answer = process_apdu(&apdu);
+    assert_eq!(answer.retcode(), ApduError::Success as u16);
+}
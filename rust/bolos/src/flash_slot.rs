@@ -273,6 +273,24 @@ impl<'s, const S: usize> Wear<'s, S> {
             Ok(slot.payload)
         }
     }
+
+    /// Retrieves the slot written `n` writes before the last one
+    ///
+    /// Slots that were never written, or already overwritten, are `Uninitialized`
+    pub fn read_nth(&self, n: usize) -> Result<&[u8; SLOT_SIZE], WearError> {
+        if n >= S || n as u64 >= self.idx {
+            return Err(WearError::Uninitialized);
+        }
+
+        let idx = ((self.idx - n as u64) % (S as u64)) as usize;
+        let slot = self.slots.get_ref()[idx].as_slot()?;
+
+        if slot.counter == 0 {
+            Err(WearError::Uninitialized)
+        } else {
+            Ok(slot.payload)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -359,6 +377,28 @@ mod tests {
         assert_eq!(&MSG, wear.read().expect("no nvm/crc issues"));
     }
 
+    #[test]
+    fn read_nth() {
+        let mut wear = new_flash_slot!(3).expect("no nvm/crc issues");
+        wear.read_nth(0)
+            .expect_err("can't read without writing once first");
+
+        for i in 0..4 {
+            wear.write([i; SLOT_SIZE]).expect("no nvm issues");
+        }
+
+        assert_eq!(
+            &[3; SLOT_SIZE],
+            wear.read_nth(0).expect("no nvm/crc issues")
+        );
+        assert_eq!(
+            &[1; SLOT_SIZE],
+            wear.read_nth(2).expect("no nvm/crc issues")
+        );
+        wear.read_nth(3)
+            .expect_err("the oldest write was overwritten");
+    }
+
     #[test]
     fn no_uninitialized_read() {
         let wear = new_flash_slot!(1).expect("no nvm/crc issues");
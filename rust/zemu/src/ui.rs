@@ -36,6 +36,8 @@ pub(self) mod bindings {
 pub(crate) mod manual_vtable;
 use manual_vtable::RefMutDynViewable;
 
+use core::convert::TryFrom;
+
 //This is _terribly_ unsafe, as we assume the pointer hasn't been invalidated!
 #[bolos_derive::lazy_static]
 static mut CURRENT_VIEWABLE: Option<RefMutDynViewable> = None;
@@ -81,15 +83,52 @@ fn move_to_global_storage<T: Sized>(item: T) -> Option<&'static mut T> {
 
 impl<T: Viewable + Sized + 'static> Show for T {
     unsafe fn show(self, flags: &mut u32) -> Result<(), ShowTooBig> {
+        self.show_with_warnings(&[], flags)
+    }
+
+    unsafe fn show_with_warnings(
+        self,
+        warnings: &[Warning],
+        flags: &mut u32,
+    ) -> Result<(), ShowTooBig> {
         use crate::ui_toolkit::RUST_ZUI;
 
-        RUST_ZUI.show(self)?;
+        RUST_ZUI.show_with_warnings(self, warnings)?;
 
         *flags |= IO_ASYNCH_REPLY;
         Ok(())
     }
 }
 
+/// Shows a message (like the reason a request was refused)
+/// until the user dismisses it
+pub fn show_message(title: &str, message: &str) {
+    use crate::ui_toolkit::RUST_ZUI;
+
+    unsafe { RUST_ZUI.show_message(title, message) }
+}
+
+/// Returns whether the UI is in expert mode
+pub fn expert() -> bool {
+    use crate::ui_toolkit::RUST_ZUI;
+
+    unsafe { RUST_ZUI.expert() }
+}
+
+/// Sets expert mode without refreshing the UI
+pub fn set_expert(expert: bool) {
+    use crate::ui_toolkit::RUST_ZUI;
+
+    unsafe { RUST_ZUI.set_expert(expert) }
+}
+
+/// Toggles expert mode and refreshes the idle menu
+pub fn toggle_expert() {
+    use crate::ui_toolkit::RUST_ZUI;
+
+    unsafe { RUST_ZUI.toggle_expert() }
+}
+
 fn cleanup_ui() {
     unsafe {
         bindings::view_review_init(None, None, None);
@@ -115,8 +154,10 @@ unsafe extern "C" fn viewfunc_get_num_items(num_items: *mut u8) -> bindings::zxe
     match get_current_viewable() {
         Err(e) => e.into(),
         Ok((obj, _)) => match obj.num_items() {
+            //the C view can only address up to u8::MAX items
+            Ok(n) if n > u8::MAX as u16 => bindings::zxerr_t_zxerr_out_of_bounds,
             Ok(n) => {
-                num_items.write(n);
+                num_items.write(n as u8);
                 bindings::zxerr_t_zxerr_ok
             }
             Err(e) => e.into(),
@@ -142,7 +183,13 @@ unsafe extern "C" fn viewfunc_get_item(
             let out_val =
                 core::slice::from_raw_parts_mut(out_val as *mut cty::c_uchar, out_val_len as usize);
 
-            match obj.render_item(item_n as u8, out_key, out_val, page_idx) {
+            //the C view passes a signed index, negative ones can't be rendered
+            let item_n = match u8::try_from(item_n) {
+                Ok(n) => n as u16,
+                Err(_) => return bindings::zxerr_t_zxerr_out_of_bounds,
+            };
+
+            match obj.render_item(item_n, out_key, out_val, page_idx) {
                 Err(e @ ViewError::Reject) => {
                     viewfunc_reject();
                     e.into()
@@ -25,7 +25,7 @@ pub enum ViewError {
 /// show on screen something
 pub trait Viewable {
     /// Return the number of items to render
-    fn num_items(&mut self) -> Result<u8, ViewError>;
+    fn num_items(&mut self) -> Result<u16, ViewError>;
 
     /// Render `item_n` into `title` and `message`
     ///
@@ -33,7 +33,7 @@ pub trait Viewable {
     /// and each page can be retrieved via the `page` parameter
     fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
@@ -55,6 +55,20 @@ pub trait Viewable {
 
 pub struct ShowTooBig;
 
+/// Maximum number of warnings shown before a single review
+pub const MAX_WARNINGS: usize = 3;
+
+/// A screen shown before the review,
+/// which has to be accepted for the review to start
+///
+/// Rejecting a warning rejects the `Viewable` being shown
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "derive-debug", derive(Debug))]
+pub struct Warning {
+    pub title: &'static [u8],
+    pub message: &'static [u8],
+}
+
 pub trait Show: Viewable + Sized {
     /// This is to be called when you wish to show the item
     ///
@@ -69,4 +83,16 @@ pub trait Show: Viewable + Sized {
     // for now we consume the item so we can guarantee
     // safe usage
     unsafe fn show(self, flags: &mut u32) -> Result<(), ShowTooBig>;
+
+    /// Like `show`, but the review is preceded by the given `warnings`, in order
+    ///
+    /// An error is also returned if there are more than `MAX_WARNINGS` warnings
+    ///
+    /// # Safety
+    /// See `show`
+    unsafe fn show_with_warnings(
+        self,
+        warnings: &[Warning],
+        flags: &mut u32,
+    ) -> Result<(), ShowTooBig>;
 }
@@ -23,8 +23,8 @@ mod private {
     pub struct This(());
 }
 
-type NumItemsFn = unsafe fn(*mut This) -> Result<u8, ViewError>;
-type RenderItemFn = unsafe fn(*mut This, u8, &mut [u8], &mut [u8], u8) -> Result<u8, ViewError>;
+type NumItemsFn = unsafe fn(*mut This) -> Result<u16, ViewError>;
+type RenderItemFn = unsafe fn(*mut This, u16, &mut [u8], &mut [u8], u8) -> Result<u8, ViewError>;
 type AcceptFn = unsafe fn(*mut This, &mut [u8]) -> (usize, u16);
 type RejectFn = unsafe fn(*mut This, &mut [u8]) -> (usize, u16);
 type DropFn = unsafe fn(*mut This);
@@ -39,7 +39,7 @@ struct ViewableVTable {
 
 trait ViewableWithVTable: Viewable + Sized {
     const VTABLE: ViewableVTable = ViewableVTable {
-        num_items: |this: *mut This| -> Result<u8, ViewError> {
+        num_items: |this: *mut This| -> Result<u16, ViewError> {
             unsafe {
                 let this = this.cast::<Self>().as_mut().expect("Got NULL");
 
@@ -47,7 +47,7 @@ trait ViewableWithVTable: Viewable + Sized {
             }
         },
         render_item: |this: *mut This,
-                      item_n: u8,
+                      item_n: u16,
                       title: &mut [u8],
                       message: &mut [u8],
                       page: u8|
@@ -88,7 +88,7 @@ pub struct RefMutDynViewable {
 }
 
 impl RefMutDynViewable {
-    pub fn num_items(&mut self) -> Result<u8, ViewError> {
+    pub fn num_items(&mut self) -> Result<u16, ViewError> {
         let to_pic = self.vtable.num_items as usize;
         let picced = unsafe { PIC::manual(to_pic) };
         let ptr: NumItemsFn = unsafe { core::mem::transmute(picced) };
@@ -98,7 +98,7 @@ impl RefMutDynViewable {
 
     pub fn render_item(
         &mut self,
-        item_n: u8,
+        item_n: u16,
         title: &mut [u8],
         message: &mut [u8],
         page: u8,
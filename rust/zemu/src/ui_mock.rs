@@ -13,6 +13,8 @@
 *  See the License for the specific language governing permissions and
 *  limitations under the License.
 ********************************************************************************/
+use arrayvec::{ArrayString, ArrayVec};
+
 #[path = "ui/comm.rs"]
 mod comm;
 pub use comm::*;
@@ -61,8 +63,66 @@ pub fn get_out() -> Option<(usize, [u8; UI_OUT_SIZE])> {
     unsafe { OUT.flush() }
 }
 
+const MESSAGE_SIZE: usize = 64;
+
+static mut MESSAGE: Option<(ArrayString<MESSAGE_SIZE>, ArrayString<MESSAGE_SIZE>)> = None;
+
+pub fn show_message(title: &str, message: &str) {
+    let truncated = |s: &str| {
+        let mut out = ArrayString::new_const();
+        for c in s.chars() {
+            if out.try_push(c).is_err() {
+                break;
+            }
+        }
+        out
+    };
+
+    unsafe { MESSAGE = Some((truncated(title), truncated(message))) }
+}
+
+/// Returns the last message shown, if any
+pub fn get_message() -> Option<(ArrayString<MESSAGE_SIZE>, ArrayString<MESSAGE_SIZE>)> {
+    unsafe { MESSAGE.take() }
+}
+
+static mut EXPERT: bool = false;
+
+pub fn expert() -> bool {
+    unsafe { EXPERT }
+}
+
+pub fn set_expert(expert: bool) {
+    unsafe { EXPERT = expert }
+}
+
+pub fn toggle_expert() {
+    unsafe { EXPERT = !EXPERT }
+}
+
+static mut WARNINGS: ArrayVec<Warning, MAX_WARNINGS> = ArrayVec::new_const();
+
+/// Returns the warnings shown before the last review
+pub fn get_warnings() -> ArrayVec<Warning, MAX_WARNINGS> {
+    unsafe { core::mem::replace(&mut WARNINGS, ArrayVec::new_const()) }
+}
+
 impl<T: Viewable + Sized> Show for T {
-    unsafe fn show(mut self, _: &mut u32) -> Result<(), ShowTooBig> {
+    unsafe fn show(self, flags: &mut u32) -> Result<(), ShowTooBig> {
+        self.show_with_warnings(&[], flags)
+    }
+
+    unsafe fn show_with_warnings(
+        mut self,
+        warnings: &[Warning],
+        _: &mut u32,
+    ) -> Result<(), ShowTooBig> {
+        //warnings are all accepted, like the review
+        WARNINGS.clear();
+        WARNINGS
+            .try_extend_from_slice(warnings)
+            .map_err(|_| ShowTooBig)?;
+
         let out = OUT.as_mut();
 
         let (len, code) = self.accept(out);
@@ -15,9 +15,9 @@
 ********************************************************************************/
 use crate::{
     ui::{manual_vtable::RefMutDynViewable, Viewable},
-    ShowTooBig, ViewError,
+    ShowTooBig, ViewError, Warning, MAX_WARNINGS,
 };
-use arrayvec::ArrayString;
+use arrayvec::{ArrayString, ArrayVec};
 
 use bolos_derive::pic_str;
 use bolos_sys::pic::PIC;
@@ -38,6 +38,9 @@ pub struct ZUI<B: UIBackend<KS> + 'static, const KS: usize> {
     backend: &'static mut B,
 
     current_viewable: Option<RefMutDynViewable>,
+
+    //shown before `current_viewable`, the first one is on screen
+    warnings: ArrayVec<Warning, MAX_WARNINGS>,
 }
 
 impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
@@ -49,10 +52,22 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
             page_count: 0,
             backend: B::static_mut(),
             current_viewable: None,
+            warnings: ArrayVec::new_const(),
         }
     }
 
+    fn in_warning(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
     pub(crate) fn approve(&mut self) {
+        if self.in_warning() {
+            //warning acknowledged, show the next one or the review
+            self.warnings.remove(0);
+            B::show_review(self);
+            return;
+        }
+
         self.show_idle(0, None);
         self.backend.wait_ui();
 
@@ -70,6 +85,7 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
     }
 
     pub(crate) fn reject(&mut self) {
+        self.warnings.clear();
         self.show_idle(0, None);
         self.backend.wait_ui();
 
@@ -87,6 +103,7 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
     }
 
     pub(crate) fn accept_error(&mut self) {
+        self.warnings.clear();
         self.show_idle(0, None);
         self.backend.wait_ui();
 
@@ -176,13 +193,16 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
 
     //h_review_action
     pub fn review_action(&mut self) {
+        let in_warning = self.in_warning();
+
         if self.is_accept_item() {
             self.approve();
         } else if self.is_reject_item() {
             self.reject();
         }
 
-        if self.backend.expert() {
+        //don't skip the review that follows a warning
+        if !in_warning && self.backend.expert() {
             self.set_accept_item();
 
             B::update_review(self)
@@ -204,12 +224,15 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
 
         let key_bytes = self.backend.key_buf();
 
-        let render_item_result = viewable.render_item(
-            self.item_idx as u8,
-            &mut key_bytes[..],
-            message_bytes,
-            page_idx,
-        );
+        let render_item_result = match self.warnings.first() {
+            Some(warning) => render_warning(warning, &mut key_bytes[..], message_bytes, page_idx),
+            None => viewable.render_item(
+                self.item_idx as u16,
+                &mut key_bytes[..],
+                message_bytes,
+                page_idx,
+            ),
+        };
 
         let ascii_range = PIC::new(&(32..=0x7F)).into_inner();
 
@@ -247,12 +270,13 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
     }
 
     fn review_update_data(&mut self) -> Result<(), ViewError> {
-        self.item_count = self
-            .current_viewable
-            .as_mut()
-            .ok_or(ViewError::NoData)?
-            .num_items()? as usize
-            + 1;
+        let viewable = self.current_viewable.as_mut().ok_or(ViewError::NoData)?;
+        //a warning is a single item
+        self.item_count = if self.warnings.is_empty() {
+            viewable.num_items()? as usize
+        } else {
+            1
+        } + 1;
         self.page_count = 1;
 
         if B::INCLUDE_ACTIONS_COUNT == 1 {
@@ -260,7 +284,11 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
                 //put approve label as message
                 // and clear key
 
-                let approve = pic_str!(b"APPROVE");
+                let approve = if self.in_warning() {
+                    &pic_str!(b"CONTINUE")[..]
+                } else {
+                    &pic_str!(b"APPROVE")[..]
+                };
                 self.backend.key_buf()[0] = 0;
 
                 let mut tmp = self.backend.message_buf();
@@ -377,6 +405,26 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
         self.backend.show_error();
     }
 
+    //view_message_show
+    /// Shows `title` and `message` until the user dismisses them
+    pub fn show_message(&mut self, title: &str, message: &str) {
+        self.backend.show_message(title, message)
+    }
+
+    pub fn expert(&self) -> bool {
+        self.backend.expert()
+    }
+
+    /// Sets expert mode, without refreshing the UI
+    pub fn set_expert(&mut self, expert: bool) {
+        self.backend.set_expert(expert)
+    }
+
+    /// Toggles expert mode, refreshing the idle menu
+    pub fn toggle_expert(&mut self) {
+        self.backend.toggle_expert()
+    }
+
     //view_idle_show
     fn show_idle(&mut self, item_idx: usize, status: Option<&[u8]>) {
         self.backend.show_idle(item_idx, status)
@@ -384,8 +432,23 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
 
     //view_review_show
     pub fn show(&mut self, viewable: impl Viewable + Sized + 'static) -> Result<(), ShowTooBig> {
+        self.show_with_warnings(viewable, &[])
+    }
+
+    /// Shows each of `warnings`, in order, and then the review of `viewable`
+    pub fn show_with_warnings(
+        &mut self,
+        viewable: impl Viewable + Sized + 'static,
+        warnings: &[Warning],
+    ) -> Result<(), ShowTooBig> {
+        let mut queue = ArrayVec::new_const();
+        queue
+            .try_extend_from_slice(warnings)
+            .map_err(|_| ShowTooBig)?;
+
         let viewable = self.backend.store_viewable(viewable).ok_or(ShowTooBig)?;
         self.current_viewable.replace(viewable);
+        self.warnings = queue;
 
         B::show_review(self);
 
@@ -393,6 +456,35 @@ impl<B: UIBackend<KS>, const KS: usize> ZUI<B, KS> {
     }
 }
 
+/// Renders `warning` like an item of a `Viewable`
+fn render_warning(
+    warning: &Warning,
+    title: &mut [u8],
+    message: &mut [u8],
+    page: u8,
+) -> Result<u8, ViewError> {
+    //the texts might be already null terminated
+    let text = |s: &'static [u8]| &s[..strlen(s).unwrap_or(s.len())];
+    let (warning_title, warning_message) = (text(warning.title), text(warning.message));
+
+    //leave space for the null terminator
+    let title_len = core::cmp::min(title.len() - 1, warning_title.len());
+    title[..title_len].copy_from_slice(&warning_title[..title_len]);
+    title[title_len] = 0;
+
+    let m_len = message.len() - 1;
+    let n_pages = 1 + warning_message.len().saturating_sub(1) / m_len;
+
+    let chunk = warning_message
+        .chunks(m_len)
+        .nth(page as usize)
+        .unwrap_or(&[]);
+    message[..chunk.len()].copy_from_slice(chunk);
+    message[chunk.len()] = 0;
+
+    Ok(n_pages as u8)
+}
+
 struct StrNotNullTerminated;
 
 /// This function returns the index of the first null byte in the slice
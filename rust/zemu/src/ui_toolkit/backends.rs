@@ -55,6 +55,9 @@ pub trait UIBackend<const KEY_SIZE: usize>: Sized {
 
     fn toggle_expert(&mut self);
 
+    //like `toggle_expert` but without refreshing the UI
+    fn set_expert(&mut self, expert: bool);
+
     fn update_expert(&mut self);
 
     fn accept_reject_out(&mut self) -> &mut [u8];
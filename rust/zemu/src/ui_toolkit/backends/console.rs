@@ -94,6 +94,10 @@ impl UIBackend<KEY_SIZE> for ConsoleBackend {
         self.expert = !self.expert;
     }
 
+    fn set_expert(&mut self, expert: bool) {
+        self.expert = expert;
+    }
+
     fn update_expert(&mut self) {
         todo!("update_expert")
     }
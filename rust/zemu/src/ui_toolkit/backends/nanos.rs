@@ -126,8 +126,10 @@ impl UIBackend<KEY_SIZE> for NanoSBackend {
 
             let title = title.as_bytes();
 
-            let len = core::cmp::min(self.key.len(), title.len());
+            //leave space for the null terminator
+            let len = core::cmp::min(self.key.len() - 1, title.len());
             self.key[..len].copy_from_slice(&title[..len]);
+            self.key[len] = 0;
         }
 
         unsafe {
@@ -175,6 +177,10 @@ impl UIBackend<KEY_SIZE> for NanoSBackend {
         self.show_idle(1, None);
     }
 
+    fn set_expert(&mut self, expert: bool) {
+        self.expert = expert;
+    }
+
     fn update_expert(&mut self) {
         let msg = if self.expert {
             &pic_str!(b"enabled")[..]
@@ -159,8 +159,21 @@ impl UIBackend<KEY_SIZE> for NanoXBackend {
         }
     }
 
-    fn show_message(&mut self, _title: &str, _message: &str) {
-        panic!("capability not supported on nanox yet?")
+    fn show_message(&mut self, title: &str, message: &str) {
+        //leave space for the null terminator
+        let copy = |buf: &mut [u8], text: &str| {
+            let len = core::cmp::min(buf.len() - 1, text.len());
+            buf[..len].copy_from_slice(&text.as_bytes()[..len]);
+            buf[len] = 0;
+        };
+        copy(&mut self.key[..], title);
+        copy(&mut self.message[..], message);
+
+        //shown with the error flow, which goes back to idle once acknowledged
+
+        unsafe {
+            bindings::crapoline_ux_show_error();
+        }
     }
 
     fn show_review(ui: &mut ZUI<Self, KEY_SIZE>) {
@@ -206,6 +219,10 @@ impl UIBackend<KEY_SIZE> for NanoXBackend {
         }
     }
 
+    fn set_expert(&mut self, expert: bool) {
+        self.expert = expert;
+    }
+
     fn accept_reject_out(&mut self) -> &mut [u8] {
         use bolos_sys::raw::G_io_apdu_buffer as APDU_BUFFER;
 
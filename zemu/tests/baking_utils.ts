@@ -29,7 +29,7 @@ export function get_endorsement_info(chain_id: number, branch: Buffer, level: nu
 }
 
 export function get_blocklevel_info(chain_id: number, level: number, round?: number): Buffer {
-  const result = Buffer.allocUnsafe(100); //should be enough for what we are writing
+  const result = Buffer.allocUnsafe(250); //should be enough for what we are writing
   let offset = 0;
 
   offset = result.writeUInt32BE(chain_id, offset)
@@ -42,12 +42,18 @@ export function get_blocklevel_info(chain_id: number, level: number, round?: num
 
   let fitness;
   if (round) {
-    //write tenderbake protocol (2)
-    // allocate 4 padding bytes
-    // and allocate 4 more bytes for the round
-    fitness = Buffer.alloc(9, 2)
-    //write the round after the padding + tag
-    fitness.writeUInt32BE(round!, 5)
+    //tenderbake fitness, each component prefixed by its length:
+    // protocol (2), level, no locked round, predecessor round (0, as -1 - round) and round
+    fitness = Buffer.alloc(33, 0)
+    let fitness_offset = fitness.writeUInt32BE(1, 0)
+    fitness_offset = fitness.writeUInt8(2, fitness_offset)
+    fitness_offset = fitness.writeUInt32BE(4, fitness_offset)
+    fitness_offset = fitness.writeUInt32BE(level, fitness_offset)
+    fitness_offset = fitness.writeUInt32BE(0, fitness_offset)
+    fitness_offset = fitness.writeUInt32BE(4, fitness_offset)
+    fitness_offset = fitness.writeInt32BE(-1, fitness_offset)
+    fitness_offset = fitness.writeUInt32BE(4, fitness_offset)
+    fitness.writeUInt32BE(round!, fitness_offset)
   } else {
     // allocate 4 padding bytes +
     // emmy protocol 5 to 11 (0x01)
@@ -56,5 +62,14 @@ export function get_blocklevel_info(chain_id: number, level: number, round?: num
   offset = result.writeUInt32BE(fitness.length, offset);
   offset = offset + fitness.copy(result, offset)
 
+  if (round) {
+    offset = offset + Buffer.alloc(32, 0).copy(result, offset) //context
+    offset = offset + Buffer.alloc(32, 0).copy(result, offset) //payload hash
+    offset = result.writeUInt32BE(0, offset) //payload round
+    offset = offset + Buffer.alloc(8, 0).copy(result, offset) //proof of work nonce
+    offset = result.writeUInt8(0, offset) //no seed nonce hash
+    offset = result.writeUInt8(0, offset) //per block votes
+  }
+
   return result.subarray(0, offset)
 }